@@ -1,11 +1,12 @@
 use crate::{Pointer, Result};
 use ruint::aliases::U256;
-use std::{fmt, path::Path};
+use std::{fmt, mem, path::Path};
 
 /// Target machine.
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Target {
     /// The host machine.
+    #[default]
     Native,
     /// LLVM-style target triple.
     ///
@@ -20,12 +21,6 @@ pub enum Target {
     },
 }
 
-impl Default for Target {
-    fn default() -> Self {
-        Self::Native
-    }
-}
-
 impl std::str::FromStr for Target {
     type Err = std::convert::Infallible;
 
@@ -55,6 +50,34 @@ impl Target {
     }
 }
 
+/// The parts of a target's data layout that translated code depends on.
+///
+/// Translated code accesses Rust structs through field offsets computed on the host, so the target
+/// must lay them out in the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TargetDataLayout {
+    /// Whether the target is big-endian.
+    pub big_endian: bool,
+    /// The size of a pointer, in bytes.
+    pub pointer_size: u32,
+    /// The ABI alignment of a pointer, in bytes.
+    pub pointer_align: u32,
+    /// The ABI alignment of a 64-bit integer, in bytes.
+    pub i64_align: u32,
+}
+
+impl TargetDataLayout {
+    /// Returns the data layout of the host machine.
+    pub const fn host() -> Self {
+        Self {
+            big_endian: cfg!(target_endian = "big"),
+            pointer_size: mem::size_of::<usize>() as u32,
+            pointer_align: mem::align_of::<usize>() as u32,
+            i64_align: mem::align_of::<u64>() as u32,
+        }
+    }
+}
+
 /// Optimization level.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum OptimizationLevel {
@@ -211,6 +234,13 @@ pub trait Backend: BackendTypes + TypeMethods {
 
     fn is_aot(&self) -> bool;
 
+    /// Returns the name and version of the code generator, such as `LLVM 18.1.8`.
+    fn codegen_version(&self) -> String;
+    fn target_triple(&self) -> String;
+    fn target_cpu(&self) -> String;
+    fn target_features(&self) -> String;
+    fn target_data_layout(&self) -> TargetDataLayout;
+
     fn function_name_is_unique(&self, name: &str) -> bool;
 
     fn build_function(
@@ -225,8 +255,24 @@ pub trait Backend: BackendTypes + TypeMethods {
     fn optimize_module(&mut self) -> Result<()>;
     fn write_object<W: std::io::Write>(&mut self, w: W) -> Result<()>;
     fn jit_function(&mut self, id: Self::FuncId) -> Result<usize>;
+    /// Returns the size in bytes of the machine code of a function returned by
+    /// [`jit_function`](Self::jit_function), if it is known.
+    fn jit_function_size(&self, id: Self::FuncId) -> Option<usize>;
+    /// Returns `true` if [`jit_function`](Self::jit_function) moves the module out of the backend
+    /// and replaces it with a new one, so that more functions can be translated afterwards.
+    fn jit_detaches_module(&self) -> bool;
+    /// Loads an object previously emitted by [`write_object`](Self::write_object) into the JIT
+    /// session and returns the address of the `name` symbol.
+    ///
+    /// Undefined symbols are resolved from `symbols` first, and then from the current process.
+    ///
+    /// If an object defining `name` was already loaded, the existing address is returned.
+    fn jit_object(&mut self, name: &str, object: &[u8], symbols: &[(&str, usize)])
+        -> Result<usize>;
     unsafe fn free_function(&mut self, id: Self::FuncId) -> Result<()>;
     unsafe fn free_all_functions(&mut self) -> Result<()>;
+    /// Frees all objects loaded with [`jit_object`](Self::jit_object).
+    unsafe fn free_all_objects(&mut self) -> Result<()>;
 }
 
 pub trait TypeMethods: BackendTypes {
@@ -249,6 +295,16 @@ pub trait Builder: BackendTypes + TypeMethods {
 
     fn add_comment_to_current_inst(&mut self, comment: &str);
 
+    /// Attaches debug info to the current function, with `file` as its source file.
+    ///
+    /// Backends that do not support debug info ignore this.
+    fn set_debug_file(&mut self, file: &Path);
+    /// Sets the line in the source file of the current function that the instructions built
+    /// afterwards are attributed to. Line `0` means no line.
+    ///
+    /// Has no effect if [`set_debug_file`](Self::set_debug_file) was not called.
+    fn set_debug_location(&mut self, line: u32);
+
     fn fn_param(&mut self, index: usize) -> Self::Value;
     fn num_fn_params(&self) -> usize;
 
@@ -273,6 +329,8 @@ pub trait Builder: BackendTypes + TypeMethods {
 
     fn load(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value;
     fn store(&mut self, value: Self::Value, ptr: Self::Value);
+    fn load_unaligned(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value;
+    fn store_unaligned(&mut self, value: Self::Value, ptr: Self::Value);
 
     fn nop(&mut self);
     fn ret(&mut self, values: &[Self::Value]);
@@ -404,6 +462,9 @@ pub trait Builder: BackendTypes + TypeMethods {
 
     fn get_function(&mut self, name: &str) -> Option<Self::Function>;
 
+    /// Returns the address of `function` as a pointer value.
+    fn func_addr(&mut self, function: Self::Function) -> Self::Value;
+
     fn get_printf_function(&mut self) -> Self::Function;
 
     /// Adds a function to the module that's located at `address`.
@@ -0,0 +1,437 @@
+use revmc::{
+    eyre::{bail, ensure, eyre, WrapErr},
+    primitives::{hex, SpecId},
+    AotContract, EvmCompiler, EvmLlvmBackend, OptimizationLevel, Result, Target,
+    AOT_CONTRACT_INFO_SYMBOL, AOT_LOOKUP_SYMBOL,
+};
+use serde::Deserialize;
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Ahead-of-time compiles EVM bytecodes in a build script.
+///
+/// The bytecodes are compiled as a contract set, see `EvmCompiler::translate_contract_set`, into a
+/// static archive that is linked into the crate, and a Rust module is generated at
+/// `$OUT_DIR/<name>.rs` with:
+/// - an [`extern_revmc!`] declaration for each function;
+/// - `contracts`, which returns the name, spec ID, code hash and function of each contract;
+/// - `get_function`, which returns the function for a code hash.
+///
+/// The generated module references `revmc_context`, which must be a dependency of the crate, and
+/// the builtins of `revmc-builtins` must be linked into the final binary. Since the lookup
+/// functions of a contract set have fixed symbol names, only one `Build` can be linked into the
+/// final binary.
+///
+/// # Examples
+///
+/// In `build.rs`:
+///
+/// ```no_run
+/// use revmc_build::{Build, SpecId};
+///
+/// Build::new()
+///     .contract(
+///         "fibonacci",
+///         "5f355f60015b8215601a578181019150909160019003916005565b9150505f5260205ff3",
+///         SpecId::CANCUN,
+///     )
+///     .contract_file("weth", "contracts/weth.hex", SpecId::CANCUN)
+///     .manifest("contracts.toml")
+///     .compile()
+///     .unwrap();
+/// ```
+///
+/// Then in the crate:
+///
+/// ```ignore
+/// include!(concat!(env!("OUT_DIR"), "/revmc_contracts.rs"));
+/// ```
+///
+/// [`extern_revmc!`]: https://docs.rs/revmc-context/latest/revmc_context/macro.extern_revmc.html
+#[derive(Debug)]
+#[must_use]
+pub struct Build {
+    name: String,
+    opt_level: OptimizationLevel,
+    contracts: Vec<Contract>,
+    manifests: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+}
+
+impl Default for Build {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A bytecode to compile.
+#[derive(Debug)]
+struct Contract {
+    name: String,
+    source: Source,
+    spec_id: SpecId,
+}
+
+/// Where the bytecode of a [`Contract`] comes from.
+#[derive(Debug)]
+enum Source {
+    Hex(String),
+    File(PathBuf),
+}
+
+/// A TOML manifest of bytecodes to compile.
+///
+/// ```toml
+/// [[contract]]
+/// name = "fibonacci"
+/// code = "0x5f355f60015b8215601a578181019150909160019003916005565b9150505f5260205ff3"
+/// spec_id = "Cancun"
+///
+/// [[contract]]
+/// name = "weth"
+/// # Relative to the manifest.
+/// path = "contracts/weth.hex"
+/// spec_id = "Cancun"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    contract: Vec<ManifestContract>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestContract {
+    name: String,
+    code: Option<String>,
+    path: Option<PathBuf>,
+    spec_id: String,
+}
+
+impl Build {
+    /// Creates a new builder.
+    pub fn new() -> Self {
+        Self {
+            name: "revmc_contracts".into(),
+            opt_level: OptimizationLevel::Aggressive,
+            contracts: Vec::new(),
+            manifests: Vec::new(),
+            out_dir: None,
+        }
+    }
+
+    /// Sets the name of the static archive and of the generated module.
+    ///
+    /// Defaults to `revmc_contracts`.
+    pub fn name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Sets the optimization level.
+    ///
+    /// Defaults to [`OptimizationLevel::Aggressive`].
+    pub fn opt_level(&mut self, opt_level: OptimizationLevel) -> &mut Self {
+        self.opt_level = opt_level;
+        self
+    }
+
+    /// Sets the output directory.
+    ///
+    /// Defaults to `$OUT_DIR`.
+    pub fn out_dir(&mut self, out_dir: impl Into<PathBuf>) -> &mut Self {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+
+    /// Adds a bytecode given as a hex string, with or without the `0x` prefix.
+    ///
+    /// `name` is the name of the function, which must be a valid Rust identifier.
+    pub fn contract(
+        &mut self,
+        name: impl Into<String>,
+        code: impl Into<String>,
+        spec_id: SpecId,
+    ) -> &mut Self {
+        let source = Source::Hex(code.into());
+        self.contracts.push(Contract { name: name.into(), source, spec_id });
+        self
+    }
+
+    /// Adds a bytecode read from a file containing a hex string.
+    ///
+    /// `name` is the name of the function, which must be a valid Rust identifier.
+    pub fn contract_file(
+        &mut self,
+        name: impl Into<String>,
+        path: impl Into<PathBuf>,
+        spec_id: SpecId,
+    ) -> &mut Self {
+        let source = Source::File(path.into());
+        self.contracts.push(Contract { name: name.into(), source, spec_id });
+        self
+    }
+
+    /// Adds the bytecodes listed in a TOML manifest.
+    ///
+    /// Each `[[contract]]` entry has a `name`, a `spec_id` as named by revm, such as `"Cancun"`,
+    /// and either the hex `code` or the `path` of a hex file, relative to the manifest.
+    pub fn manifest(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.manifests.push(path.into());
+        self
+    }
+
+    /// Compiles the bytecodes, links the static archive into the crate and generates the module.
+    ///
+    /// Emits `cargo:rerun-if-changed` for every manifest and bytecode file.
+    pub fn compile(&self) -> Result<()> {
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => PathBuf::from(env("OUT_DIR")?),
+        };
+
+        let mut contracts = Vec::new();
+        for contract in &self.contracts {
+            let code = contract.source.read()?;
+            contracts.push((contract.name.clone(), code, contract.spec_id));
+        }
+        for path in &self.manifests {
+            contracts.extend(read_manifest(path)?);
+        }
+        ensure!(!contracts.is_empty(), "no contracts to compile");
+        for (name, _, _) in &contracts {
+            ensure!(is_ident(name), "contract name `{name}` is not a valid identifier");
+        }
+
+        // Compile.
+        let target = match (env("TARGET"), env("HOST")) {
+            (Ok(target), Ok(host)) if target != host => Target::triple(target),
+            _ => Target::Native,
+        };
+        let cx = revmc::llvm::inkwell::context::Context::create();
+        let backend = EvmLlvmBackend::new_for_target(&cx, true, self.opt_level, &target)?;
+        let mut compiler = EvmCompiler::new(backend);
+        compiler.set_module_name(&self.name);
+        compiler
+            .translate_contract_set(
+                contracts
+                    .iter()
+                    .map(|(name, code, spec_id)| AotContract::new(name, &code[..], *spec_id)),
+            )
+            .wrap_err("failed to compile the contracts")?;
+        let object = out_dir.join(&self.name).with_extension("o");
+        compiler.write_object_to_file(&object)?;
+
+        // Link.
+        cc::Build::new()
+            .object(&object)
+            .static_flag(true)
+            .out_dir(&out_dir)
+            .try_compile(&self.name)?;
+
+        // Generate the module.
+        let module = generate_module(&contracts);
+        fs::write(out_dir.join(&self.name).with_extension("rs"), module)?;
+
+        Ok(())
+    }
+}
+
+impl Source {
+    fn read(&self) -> Result<Vec<u8>> {
+        match self {
+            Self::Hex(code) => decode_hex(code),
+            Self::File(path) => {
+                rerun_if_changed(path);
+                let code = fs::read_to_string(path)
+                    .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+                decode_hex(&code)
+                    .wrap_err_with(|| format!("invalid bytecode in {}", path.display()))
+            }
+        }
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<Vec<(String, Vec<u8>, SpecId)>> {
+    rerun_if_changed(path);
+    let manifest =
+        fs::read_to_string(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    let manifest = toml::from_str::<Manifest>(&manifest)
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+    let root = path.parent().unwrap_or(Path::new(""));
+    manifest
+        .contract
+        .into_iter()
+        .map(|ManifestContract { name, code, path, spec_id }| {
+            let source = match (code, path) {
+                (Some(code), None) => Source::Hex(code),
+                (None, Some(path)) => Source::File(root.join(path)),
+                _ => bail!("contract `{name}` must have exactly one of `code` and `path`"),
+            };
+            let spec_id = parse_spec_id(&spec_id)
+                .ok_or_else(|| eyre!("contract `{name}` has an unknown spec ID: {spec_id}"))?;
+            Ok((name, source.read()?, spec_id))
+        })
+        .collect()
+}
+
+fn generate_module(contracts: &[(String, Vec<u8>, SpecId)]) -> String {
+    let mut s = String::new();
+    s.push_str("// @generated by revmc-build. Do not edit.\n\n");
+
+    s.push_str("revmc_context::extern_revmc! {\n");
+    for (name, _, spec_id) in contracts {
+        let _ = writeln!(s, "    /// Compiled for `{spec_id:?}`.");
+        let _ = writeln!(s, "    pub fn {name};");
+    }
+    s.push_str("}\n\n");
+
+    // `B256` is a transparent wrapper around `[u8; 32]`.
+    s.push_str("extern \"C\" {\n");
+    let _ = writeln!(s, "    #[link_name = \"{AOT_LOOKUP_SYMBOL}\"]");
+    s.push_str(
+        "    fn __revmc_lookup(code_hash: &[u8; 32]) -> Option<revmc_context::RawEvmCompilerFn>;\n",
+    );
+    let _ = writeln!(s, "    #[link_name = \"{AOT_CONTRACT_INFO_SYMBOL}\"]");
+    s.push_str("    fn __revmc_contract_info(\n");
+    s.push_str("        index: usize,\n");
+    s.push_str("        info: &mut ::core::mem::MaybeUninit<revmc_context::AotContractInfo>,\n");
+    s.push_str("    ) -> bool;\n");
+    s.push_str("}\n\n");
+
+    s.push_str(
+        "/// Returns the name, spec ID, code hash and function of each compiled bytecode.\n",
+    );
+    s.push_str("pub fn contracts() -> impl Iterator<Item = revmc_context::AotContractInfo> {\n");
+    s.push_str("    (0..).map_while(|index| {\n");
+    s.push_str("        let mut info = ::core::mem::MaybeUninit::uninit();\n");
+    s.push_str("        unsafe { __revmc_contract_info(index, &mut info) }\n");
+    s.push_str("            .then(|| unsafe { info.assume_init() })\n");
+    s.push_str("    })\n");
+    s.push_str("}\n\n");
+
+    s.push_str("/// Returns the compiled function of the bytecode with the given code hash.\n");
+    s.push_str(
+        "pub fn get_function(code_hash: &[u8; 32]) -> Option<revmc_context::EvmCompilerFn> {\n",
+    );
+    s.push_str("    unsafe { __revmc_lookup(code_hash) }.map(revmc_context::EvmCompilerFn::new)\n");
+    s.push_str("}\n");
+    s
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>> {
+    hex::decode(s.trim()).map_err(Into::into)
+}
+
+/// Parses a spec ID as named by revm, such as `Cancun`.
+fn parse_spec_id(s: &str) -> Option<SpecId> {
+    // Unknown names are converted to `LATEST`.
+    let spec_id = SpecId::from(s);
+    (<&str>::from(spec_id) == s).then_some(spec_id)
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+fn env(key: &str) -> Result<String> {
+    std::env::var(key).wrap_err_with(|| format!("environment variable `{key}` is not set"))
+}
+
+fn rerun_if_changed(path: &Path) {
+    println!("cargo:rerun-if-changed={}", path.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idents() {
+        for s in ["a", "_", "_a1", "fibonacci", "Weth9"] {
+            assert!(is_ident(s), "{s}");
+        }
+        for s in ["", "1a", "a-b", "a b", "a::b", "é"] {
+            assert!(!is_ident(s), "{s}");
+        }
+    }
+
+    #[test]
+    fn spec_ids() {
+        assert_eq!(parse_spec_id("Cancun"), Some(SpecId::CANCUN));
+        assert_eq!(parse_spec_id("PragueEOF"), Some(SpecId::PRAGUE_EOF));
+        assert_eq!(parse_spec_id("Latest"), Some(SpecId::LATEST));
+        assert_eq!(parse_spec_id("CANCUN"), None);
+        assert_eq!(parse_spec_id("Unknown"), None);
+    }
+
+    #[test]
+    fn manifest() {
+        let tmp = tempfile::tempdir().expect("could not create temp dir");
+        fs::create_dir(tmp.path().join("contracts")).unwrap();
+        fs::write(tmp.path().join("contracts/b.hex"), "0x6002\n").unwrap();
+        let path = tmp.path().join("contracts.toml");
+        let read = |manifest: &str| {
+            fs::write(&path, manifest).unwrap();
+            read_manifest(&path)
+        };
+
+        let contracts = read(
+            r#"
+            [[contract]]
+            name = "a"
+            code = "6001"
+            spec_id = "Cancun"
+
+            [[contract]]
+            name = "b"
+            path = "contracts/b.hex"
+            spec_id = "Shanghai"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            contracts,
+            [
+                ("a".to_string(), vec![0x60, 0x01], SpecId::CANCUN),
+                ("b".to_string(), vec![0x60, 0x02], SpecId::SHANGHAI),
+            ]
+        );
+
+        let err = read("[[contract]]\nname = \"a\"\nspec_id = \"Cancun\"").unwrap_err();
+        assert!(err.to_string().contains("exactly one of"), "{err}");
+        let err = read("[[contract]]\nname = \"a\"\ncode = \"00\"\nspec_id = \"X\"").unwrap_err();
+        assert!(err.to_string().contains("unknown spec ID"), "{err}");
+        let err = read("[[contract]]\nname = \"a\"\ncode = \"00\"\nfoo = 1").unwrap_err();
+        assert!(err.to_string().contains("failed to parse"), "{err}");
+    }
+
+    #[test]
+    fn module() {
+        let contracts = [
+            ("a".to_string(), vec![], SpecId::CANCUN),
+            ("b".to_string(), vec![], SpecId::PRAGUE_EOF),
+        ];
+        let module = generate_module(&contracts);
+        let lookup = format!("    #[link_name = \"{AOT_LOOKUP_SYMBOL}\"]\n    fn __revmc_lookup(");
+        let info = format!(
+            "    #[link_name = \"{AOT_CONTRACT_INFO_SYMBOL}\"]\n    fn __revmc_contract_info("
+        );
+        for line in [
+            "    /// Compiled for `CANCUN`.\n    pub fn a;\n",
+            "    /// Compiled for `PRAGUE_EOF`.\n    pub fn b;\n",
+            lookup.as_str(),
+            info.as_str(),
+            "pub fn contracts() -> impl Iterator<Item = revmc_context::AotContractInfo> {\n",
+            "pub fn get_function(code_hash: &[u8; 32]) -> Option<revmc_context::EvmCompilerFn> {\n",
+        ] {
+            assert!(module.contains(line), "{line}\n---\n{module}");
+        }
+    }
+}
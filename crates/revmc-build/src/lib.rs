@@ -2,6 +2,13 @@
 #![cfg_attr(not(test), warn(unused_extern_crates))]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+#[cfg(feature = "compile")]
+mod compile;
+#[cfg(feature = "compile")]
+pub use compile::Build;
+#[cfg(feature = "compile")]
+pub use revmc::{primitives::SpecId, OptimizationLevel};
+
 // Must be kept in sync with `remvc-builtins`.
 const MANGLE_PREFIX: &str = "__revmc_builtin_";
 
@@ -102,6 +102,8 @@ macro_rules! builtins {
         impl Builtin {
             pub const COUNT: usize = builtins!(@count $($ident),*);
 
+            pub const ALL: [Self; Self::COUNT] = [$(Self::$ident,)*];
+
             pub const fn name(self) -> &'static str {
                 match self {
                     $(Self::$ident => stringify!($name),)*
@@ -153,10 +155,14 @@ macro_rules! builtins {
                 const PANIC: u8 = 0;
                 const LOG: u8 = LOG0;
                 const DORETURN: u8 = RETURN;
+                const KECCAK256FOR32: u8 = KECCAK256;
+                const KECCAK256FOR64: u8 = KECCAK256;
                 const RESIZEMEMORY: u8 = 0;
+                const GROWMEMORY: u8 = 0;
                 const FUNCSTACKPUSH: u8 = 0;
                 const FUNCSTACKPOP: u8 = 0;
                 const FUNCSTACKGROW: u8 = 0;
+                const STEP: u8 = 0;
 
                 match self {
                     $(Self::$ident => [<$ident:upper>]),*
@@ -221,6 +227,8 @@ builtins! {
     MulMod         = __revmc_builtin_mulmod(@[sp] ptr) None,
     Exp            = __revmc_builtin_exp(@[ecx] ptr, @[sp] ptr, u8) Some(u8),
     Keccak256      = __revmc_builtin_keccak256(@[ecx] ptr, @[sp] ptr) Some(u8),
+    Keccak256For32 = __revmc_builtin_keccak256_32(ptr, ptr) None,
+    Keccak256For64 = __revmc_builtin_keccak256_64(ptr, ptr) None,
     Balance        = __revmc_builtin_balance(@[ecx] ptr, @[sp] ptr, u8) Some(u8),
     CallDataCopy   = __revmc_builtin_calldatacopy(@[ecx] ptr, @[sp] ptr) Some(u8),
     CodeSize       = __revmc_builtin_codesize(@[ecx] ptr) Some(usize),
@@ -259,4 +267,7 @@ builtins! {
     FuncStackGrow  = __revmc_builtin_func_stack_grow(@[ecx] ptr) None,
 
     ResizeMemory   = __revmc_builtin_resize_memory(@[ecx] ptr, usize) Some(u8),
+    GrowMemory     = __revmc_builtin_grow_memory(@[ecx] ptr, usize) None,
+
+    Step           = __revmc_builtin_step(@[ecx] ptr, @[sp_dyn] ptr, usize, usize, u8) None,
 }
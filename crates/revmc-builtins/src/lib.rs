@@ -20,7 +20,7 @@ use revm_primitives::{
     eof::EofHeader, Address, Bytes, CreateScheme, Eof, Log, LogData, SpecId, KECCAK_EMPTY,
     MAX_INITCODE_SIZE, U256,
 };
-use revmc_context::{EvmContext, EvmWord};
+use revmc_context::{EvmContext, EvmWord, InstructionStep};
 
 pub mod gas;
 
@@ -148,6 +148,16 @@ pub unsafe extern "C" fn __revmc_builtin_keccak256(
     InstructionResult::Continue
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn __revmc_builtin_keccak256_32(out: &mut EvmWord, data: &[u8; 32]) {
+    *out = EvmWord::from_be_bytes(revm_primitives::keccak256(data).0);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn __revmc_builtin_keccak256_64(out: &mut EvmWord, data: &[u8; 64]) {
+    *out = EvmWord::from_be_bytes(revm_primitives::keccak256(data).0);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn __revmc_builtin_balance(
     ecx: &mut EvmContext<'_>,
@@ -907,3 +917,23 @@ pub unsafe extern "C" fn __revmc_builtin_resize_memory(
 ) -> InstructionResult {
     resize_memory(ecx, new_size)
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn __revmc_builtin_grow_memory(ecx: &mut EvmContext<'_>, new_len: usize) {
+    ecx.memory.resize(new_len);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn __revmc_builtin_step(
+    ecx: &mut EvmContext<'_>,
+    stack: *const EvmWord,
+    stack_len: usize,
+    pc: usize,
+    opcode: u8,
+) {
+    // Take the inspector out of the context so that it can be passed the context mutably.
+    let Some(inspector) = ecx.inspector.take() else { return };
+    let stack = core::slice::from_raw_parts(stack, stack_len);
+    inspector.step(&InstructionStep { pc, opcode, stack }, ecx);
+    ecx.inspector = Some(inspector);
+}
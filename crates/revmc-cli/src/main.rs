@@ -71,6 +71,9 @@ struct Cli {
     no_validate: bool,
     #[arg(long)]
     debug_assertions: bool,
+    /// Emit debug info that maps the compiled code back to the bytecode.
+    #[arg(long)]
+    debug_info: bool,
     #[arg(long)]
     no_gas: bool,
     #[arg(long)]
@@ -98,6 +101,7 @@ fn main() -> Result<()> {
     unsafe { compiler.stack_bound_checks(!cli.no_len_checks) };
     compiler.frame_pointers(true);
     compiler.debug_assertions(cli.debug_assertions);
+    compiler.debug_info(cli.debug_info);
     compiler.validate_eof(!cli.no_validate);
 
     let Bench { name, bytecode, calldata, stack_input, native: _ } = if cli.bench_name == "custom" {
@@ -186,7 +190,8 @@ fn main() -> Result<()> {
         // Link.
         if !cli.no_link {
             let so = out_dir.join("a.so");
-            let linker = revmc::Linker::new();
+            let mut linker = revmc::Linker::new();
+            linker.strip_debug(!cli.debug_info);
             linker.link(&so, [obj.to_str().unwrap()])?;
             ensure!(so.exists(), "Failed to link object file");
             eprintln!("Linked shared object file to {}", so.display());
@@ -5,13 +5,13 @@
 
 extern crate alloc;
 
-use alloc::vec::Vec;
+use alloc::{boxed::Box, vec, vec::Vec};
 use core::{fmt, mem::MaybeUninit, ptr};
 use revm_interpreter::{
     Contract, FunctionStack, Gas, Host, InstructionResult, Interpreter, InterpreterAction,
     InterpreterResult, SharedMemory, EMPTY_SHARED_MEMORY,
 };
-use revm_primitives::{Address, Bytes, Env, U256};
+use revm_primitives::{Address, Bytes, Env, SpecId, B256, U256};
 
 #[cfg(feature = "host-ext-any")]
 use core::any::Any;
@@ -39,6 +39,14 @@ pub struct EvmContext<'a> {
     pub is_static: bool,
     /// Whether the context is EOF init.
     pub is_eof_init: bool,
+    /// The instruction inspector.
+    ///
+    /// Only called by functions compiled with instruction inspection enabled.
+    pub inspector: Option<&'a mut dyn InstructionInspector>,
+    /// The cache of warm storage slots of the current contract.
+    ///
+    /// Only used by functions compiled with the storage cache enabled.
+    pub storage_cache: Option<&'a mut StorageCache>,
     /// An index that is used internally to keep track of where execution should resume.
     /// `0` is the initial state.
     #[doc(hidden)]
@@ -79,6 +87,8 @@ impl<'a> EvmContext<'a> {
             func_stack: &mut interpreter.function_stack,
             is_static: interpreter.is_static,
             is_eof_init: interpreter.is_eof_init,
+            inspector: None,
+            storage_cache: None,
             resume_at,
         };
         (this, stack, stack_len)
@@ -108,6 +118,194 @@ impl<'a> EvmContext<'a> {
     }
 }
 
+/// An inspector of the instructions executed by a compiled function.
+///
+/// This is the equivalent of revm's `Inspector::step` for compiled functions, which do not go
+/// through the interpreter loop. Instructions are only inspected if the function was compiled with
+/// instruction inspection enabled, and the inspector is set in [`EvmContext::inspector`].
+///
+/// There is no equivalent of `Inspector::step_end`, as compiled instructions do not go back to a
+/// common point once executed: they fall through, branch or return directly. The next call to
+/// [`step`](Self::step), or the result of the function, can be used instead. Similarly, revm's
+/// `Inspector`s cannot be adapted to this trait, as they are given an `Interpreter` and revm's
+/// `EvmContext`, which do not exist while a compiled function is running.
+pub trait InstructionInspector {
+    /// Called before the instruction is executed and before its gas is charged.
+    ///
+    /// By default, gas is charged per section rather than per instruction: the static gas of all
+    /// the instructions in a section is charged at once, after this is called for its first
+    /// instruction. For the other instructions of the section, `ecx.gas` has therefore already
+    /// been charged the static gas of the instruction and of the ones after it in the section. See
+    /// `EvmCompiler::section_gas_metering`.
+    fn step(&mut self, step: &InstructionStep<'_>, ecx: &mut EvmContext<'_>);
+}
+
+/// The state of the function before an instruction is executed. See [`InstructionInspector`].
+#[derive(Clone, Copy, Debug)]
+pub struct InstructionStep<'a> {
+    /// The program counter of the instruction.
+    pub pc: usize,
+    /// The opcode of the instruction.
+    pub opcode: u8,
+    /// The stack, with the top of the stack as the last element.
+    ///
+    /// Pushes of constant jump targets are elided, so the stack does not contain them.
+    pub stack: &'a [EvmWord],
+}
+
+/// A direct-mapped cache of the warm storage slots of the current contract, provided by the host.
+///
+/// Functions compiled with the storage cache enabled look up `SLOAD` and `SSTORE` keys in this
+/// cache before calling into the host. On a hit, the instruction is executed inline: `SLOAD` reads
+/// [`present`](StorageCacheEntry::present), and `SSTORE` writes it and marks the entry as
+/// [`DIRTY`](StorageCacheEntry::DIRTY). On a miss, the host is called as usual.
+///
+/// Entries must only be inserted for slots of the current contract that are warm as per
+/// EIP-2929, with their original and present values as per EIP-2200.
+///
+/// The host is responsible for keeping the cache consistent with its own state: dirty entries must
+/// be written back with [`take_dirty`](Self::take_dirty) whenever the function returns or is
+/// suspended, and entries must be updated or removed if the storage is modified elsewhere.
+///
+/// Only used in specs starting from Berlin.
+#[repr(C)]
+pub struct StorageCache {
+    // NOTE: The first two fields are read by compiled functions.
+    /// Pointer to the first entry of `buf`.
+    entries: *mut StorageCacheEntry,
+    /// `buf.len() - 1`.
+    mask: usize,
+    buf: Box<[StorageCacheEntry]>,
+}
+
+// SAFETY: `entries` points into `buf`, which is owned.
+unsafe impl Send for StorageCache {}
+unsafe impl Sync for StorageCache {}
+
+impl fmt::Debug for StorageCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.buf.iter().filter(|entry| entry.is_occupied())).finish()
+    }
+}
+
+impl StorageCache {
+    /// The multiplier used in [`index`](Self::index).
+    pub const HASH_MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    /// Creates a new empty cache with at least `capacity` entries.
+    ///
+    /// The capacity is rounded up to the next power of two.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1).next_power_of_two();
+        let mut buf = vec![StorageCacheEntry::EMPTY; capacity].into_boxed_slice();
+        Self { entries: buf.as_mut_ptr(), mask: capacity - 1, buf }
+    }
+
+    /// Returns the number of entries of the cache.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the entries of the cache, including empty ones.
+    #[inline]
+    pub fn entries(&self) -> &[StorageCacheEntry] {
+        &self.buf
+    }
+
+    /// Returns the index of the entry that `key` maps to.
+    ///
+    /// This is the XOR of the 64-bit limbs of `key`, multiplied by
+    /// [`HASH_MULTIPLIER`](Self::HASH_MULTIPLIER), shifted right by 32 bits, and masked to the
+    /// capacity.
+    #[inline]
+    pub fn index(&self, key: U256) -> usize {
+        let [a, b, c, d] = *key.as_limbs();
+        let hash = (a ^ b ^ c ^ d).wrapping_mul(Self::HASH_MULTIPLIER) >> 32;
+        hash as usize & self.mask
+    }
+
+    /// Returns the entry of `key`, if any.
+    #[inline]
+    pub fn get(&self, key: U256) -> Option<&StorageCacheEntry> {
+        let entry = &self.buf[self.index(key)];
+        (entry.is_occupied() && entry.key.to_u256() == key).then_some(entry)
+    }
+
+    /// Inserts a warm slot into the cache, returning the entry it replaced, if any.
+    ///
+    /// If the replaced entry is dirty, it must be written back by the caller.
+    pub fn insert(
+        &mut self,
+        key: U256,
+        original: U256,
+        present: U256,
+    ) -> Option<StorageCacheEntry> {
+        let index = self.index(key);
+        let new = StorageCacheEntry {
+            key: key.into(),
+            original: original.into(),
+            present: present.into(),
+            flags: StorageCacheEntry::OCCUPIED,
+        };
+        let old = core::mem::replace(&mut self.buf[index], new);
+        old.is_occupied().then_some(old)
+    }
+
+    /// Returns the `(key, present)` pairs of the dirty entries, marking them as clean.
+    pub fn take_dirty(&mut self) -> impl Iterator<Item = (U256, U256)> + '_ {
+        self.buf.iter_mut().filter(|entry| entry.is_dirty()).map(|entry| {
+            entry.flags &= !StorageCacheEntry::DIRTY;
+            (entry.key.to_u256(), entry.present.to_u256())
+        })
+    }
+
+    /// Removes all the entries from the cache.
+    ///
+    /// Dirty entries are discarded.
+    pub fn clear(&mut self) {
+        self.buf.fill(StorageCacheEntry::EMPTY);
+    }
+}
+
+/// An entry of a [`StorageCache`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StorageCacheEntry {
+    /// The storage key.
+    pub key: EvmWord,
+    /// The value of the slot at the start of the transaction.
+    pub original: EvmWord,
+    /// The current value of the slot.
+    pub present: EvmWord,
+    /// The entry flags. See [`OCCUPIED`](Self::OCCUPIED) and [`DIRTY`](Self::DIRTY).
+    pub flags: usize,
+}
+
+impl StorageCacheEntry {
+    /// An empty entry.
+    pub const EMPTY: Self =
+        Self { key: EvmWord::ZERO, original: EvmWord::ZERO, present: EvmWord::ZERO, flags: 0 };
+
+    /// Set if the entry holds a warm slot.
+    pub const OCCUPIED: usize = 1 << 0;
+    /// Set if `present` was written by a compiled function and has not yet been written back to
+    /// the host.
+    pub const DIRTY: usize = 1 << 1;
+
+    /// Returns `true` if the entry holds a warm slot.
+    #[inline]
+    pub const fn is_occupied(&self) -> bool {
+        self.flags & Self::OCCUPIED != 0
+    }
+
+    /// Returns `true` if the entry was written by a compiled function.
+    #[inline]
+    pub const fn is_dirty(&self) -> bool {
+        self.flags & Self::DIRTY != 0
+    }
+}
+
 /// Extension trait for [`Host`].
 #[cfg(not(feature = "host-ext-any"))]
 pub trait HostExt: Host {}
@@ -263,11 +461,65 @@ impl EvmCompilerFn {
         self,
         interpreter: &mut Interpreter,
         host: &mut dyn HostExt,
+    ) -> InterpreterAction {
+        self.call_with_interpreter_inner(interpreter, host, None, None)
+    }
+
+    /// Calls the function by re-using the interpreter's resources, forwarding the executed
+    /// instructions to `inspector`.
+    ///
+    /// The function must have been compiled with instruction inspection enabled, otherwise this
+    /// behaves the same as [`call_with_interpreter`](Self::call_with_interpreter).
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the function is safe to call.
+    #[inline]
+    pub unsafe fn call_with_interpreter_and_inspector(
+        self,
+        interpreter: &mut Interpreter,
+        host: &mut dyn HostExt,
+        inspector: &mut dyn InstructionInspector,
+    ) -> InterpreterAction {
+        self.call_with_interpreter_inner(interpreter, host, Some(inspector), None)
+    }
+
+    /// Calls the function by re-using the interpreter's resources, executing `SLOAD` and `SSTORE`
+    /// inline on hits of `storage_cache`.
+    ///
+    /// The function must have been compiled with the storage cache enabled, otherwise this behaves
+    /// the same as [`call_with_interpreter`](Self::call_with_interpreter). See [`StorageCache`]
+    /// for the requirements on the cache.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the function is safe to call.
+    #[inline]
+    pub unsafe fn call_with_interpreter_and_storage_cache(
+        self,
+        interpreter: &mut Interpreter,
+        host: &mut dyn HostExt,
+        storage_cache: &mut StorageCache,
+    ) -> InterpreterAction {
+        self.call_with_interpreter_inner(interpreter, host, None, Some(storage_cache))
+    }
+
+    #[inline]
+    unsafe fn call_with_interpreter_inner(
+        self,
+        interpreter: &mut Interpreter,
+        host: &mut dyn HostExt,
+        inspector: Option<&mut dyn InstructionInspector>,
+        storage_cache: Option<&mut StorageCache>,
     ) -> InterpreterAction {
         interpreter.next_action = InterpreterAction::None;
 
         let (mut ecx, stack, stack_len) =
             EvmContext::from_interpreter_with_stack(interpreter, host);
+        if let Some(inspector) = inspector {
+            ecx.inspector = Some(inspector);
+        }
+        ecx.storage_cache = storage_cache;
         let result = self.call(Some(stack), Some(stack_len), &mut ecx);
 
         // Set the remaining gas to 0 if the result is `OutOfGas`,
@@ -345,6 +597,79 @@ impl EvmCompilerFn {
     }
 }
 
+/// The symbol of the [`AotLookupFn`] of a compiled contract set.
+pub const AOT_LOOKUP_SYMBOL: &str = "revmc_lookup";
+
+/// The symbol of the [`AotContractInfoFn`] of a compiled contract set.
+pub const AOT_CONTRACT_INFO_SYMBOL: &str = "revmc_contract_info";
+
+/// Returns the function of the contract with the given code hash, or `None` if it is not part of
+/// the contract set.
+///
+/// Exported as [`AOT_LOOKUP_SYMBOL`] by objects written after
+/// `EvmCompiler::translate_contract_set`.
+pub type AotLookupFn = unsafe extern "C" fn(code_hash: &B256) -> Option<RawEvmCompilerFn>;
+
+/// Writes the information of the contract at `index` in the contract set to `info`.
+///
+/// Returns `false`, without writing anything, if `index` is out of bounds.
+///
+/// Exported as [`AOT_CONTRACT_INFO_SYMBOL`] by objects written after
+/// `EvmCompiler::translate_contract_set`.
+pub type AotContractInfoFn =
+    unsafe extern "C" fn(index: usize, info: &mut MaybeUninit<AotContractInfo>) -> bool;
+
+/// Information about a contract of a compiled contract set. See [`AotContractInfoFn`].
+// When changing the layout, also update `EvmCompiler::translate_contract_set`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AotContractInfo {
+    /// The hash of the bytecode.
+    pub code_hash: B256,
+    /// The pointer to the UTF-8 name of the function.
+    pub name: *const u8,
+    /// The length of the name.
+    pub name_len: usize,
+    /// The compiled function.
+    pub function: RawEvmCompilerFn,
+    /// The spec ID that the function was compiled for.
+    pub spec_id: u8,
+}
+
+impl fmt::Debug for AotContractInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AotContractInfo")
+            .field("code_hash", &self.code_hash)
+            .field("name", &self.name_str())
+            .field("function", &self.function)
+            .field("spec_id", &self.spec_id())
+            .finish()
+    }
+}
+
+impl AotContractInfo {
+    /// Returns the name of the function.
+    #[inline]
+    pub fn name_str(&self) -> &str {
+        // SAFETY: The name is a string constant in the same library as the function.
+        unsafe {
+            core::str::from_utf8_unchecked(core::slice::from_raw_parts(self.name, self.name_len))
+        }
+    }
+
+    /// Returns the spec ID that the function was compiled for.
+    #[inline]
+    pub fn spec_id(&self) -> Option<SpecId> {
+        SpecId::try_from_u8(self.spec_id)
+    }
+
+    /// Returns the compiled function.
+    #[inline]
+    pub fn function(&self) -> EvmCompilerFn {
+        EvmCompilerFn::new(self.function)
+    }
+}
+
 /// EVM context stack.
 #[repr(C)]
 #[allow(missing_debug_implementations)]
@@ -781,6 +1106,29 @@ mod tests {
         assert_eq!(usize::try_from(&mut word), Ok(0));
     }
 
+    #[test]
+    fn storage_cache() {
+        let mut cache = StorageCache::new(3);
+        assert_eq!(cache.capacity(), 4);
+        assert_eq!(cache.get(U256::from(1)), None);
+
+        assert_eq!(cache.insert(U256::from(1), U256::ZERO, U256::from(2)), None);
+        let entry = cache.get(U256::from(1)).unwrap();
+        assert_eq!(entry.present, EvmWord::from(2u8));
+        assert!(entry.is_occupied() && !entry.is_dirty());
+        assert_eq!(cache.take_dirty().count(), 0);
+
+        // Written by a compiled function.
+        let index = cache.index(U256::from(1));
+        cache.buf[index].present = EvmWord::from(3u8);
+        cache.buf[index].flags |= StorageCacheEntry::DIRTY;
+        assert_eq!(cache.take_dirty().collect::<Vec<_>>(), [(U256::from(1), U256::from(3))]);
+        assert!(!cache.get(U256::from(1)).unwrap().is_dirty());
+
+        cache.clear();
+        assert_eq!(cache.get(U256::from(1)), None);
+    }
+
     extern_revmc! {
         #[link_name = "__test_fn"]
         fn test_fn;
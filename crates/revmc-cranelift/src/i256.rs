@@ -0,0 +1,451 @@
+//! Lowering of 256-bit integers.
+//!
+//! Cranelift does not have an `i256` type, so each `i256` value is represented by 4 `i64` limbs,
+//! least significant first. The builder hands out a placeholder [`Value`] for each `i256` and keeps
+//! track of its limbs; see `EvmCraneliftBuilder::i256_value`.
+//!
+//! Addresses (`i160`) are represented the same way, with the upper 96 bits cleared; they are only
+//! loaded, byte-swapped and extended.
+//!
+//! Most operations are expanded inline. Division and remainder call out-of-line [`Helper`]s.
+
+use cranelift::{codegen::ir::StackSlot, prelude::*};
+use revmc_backend::U256;
+
+/// The type that stands in for `i256` in the backend's API.
+///
+/// It is never used by instructions, but it has the correct size for stack slots and bit widths.
+pub(crate) const I256: Type = types::I64X4;
+
+/// The type that stands in for `i160`.
+///
+/// Unlike [`I256`], its size is not that of the integer; use [`type_bits`] and [`type_bytes`].
+pub(crate) const I160: Type = types::I32X4;
+
+/// The limbs of an `i256` value, least significant first.
+pub(crate) type Limbs = [Value; 4];
+
+/// Returns `true` if `ty` is represented with limbs.
+pub(crate) fn is_wide(ty: Type) -> bool {
+    ty == I256 || ty == I160
+}
+
+/// Returns the bit width of the integer that `ty` stands for.
+pub(crate) fn type_bits(ty: Type) -> u32 {
+    if ty == I160 {
+        160
+    } else {
+        ty.bits()
+    }
+}
+
+/// Returns the size in bytes of the integer that `ty` stands for.
+pub(crate) fn type_bytes(ty: Type) -> u32 {
+    type_bits(ty).div_ceil(8)
+}
+
+/// Returns the number of ABI values of `ty`.
+pub(crate) fn abi_len(ty: Type) -> usize {
+    if ty == I256 {
+        4
+    } else {
+        1
+    }
+}
+
+/// Returns the type of each ABI value of `ty`.
+pub(crate) fn abi_type(ty: Type) -> Type {
+    if ty == I256 {
+        types::I64
+    } else {
+        ty
+    }
+}
+
+/// Returns the offset of the `i`th limb in memory. Only little-endian targets are supported.
+const fn limb_offset(i: usize) -> i32 {
+    i as i32 * 8
+}
+
+/// Memory accesses are not necessarily aligned to 8 bytes.
+fn mem_flags() -> MemFlags {
+    MemFlags::new().with_notrap()
+}
+
+pub(crate) fn iconst(bcx: &mut FunctionBuilder<'_>, value: U256) -> Limbs {
+    value.into_limbs().map(|limb| bcx.ins().iconst(types::I64, limb as i64))
+}
+
+/// Sign-extends `imm` to 256 bits.
+pub(crate) fn imm(bcx: &mut FunctionBuilder<'_>, imm: i64) -> Limbs {
+    let low = bcx.ins().iconst(types::I64, imm);
+    let high = bcx.ins().iconst(types::I64, imm >> 63);
+    [low, high, high, high]
+}
+
+pub(crate) fn load(bcx: &mut FunctionBuilder<'_>, ty: Type, ptr: Value) -> Limbs {
+    if ty == I160 {
+        let zero = bcx.ins().iconst(types::I64, 0);
+        let low = bcx.ins().load(types::I64, mem_flags(), ptr, 0);
+        let mid = bcx.ins().load(types::I64, mem_flags(), ptr, 8);
+        let high = bcx.ins().uload32(mem_flags(), ptr, 16);
+        return [low, mid, high, zero];
+    }
+    std::array::from_fn(|i| bcx.ins().load(types::I64, mem_flags(), ptr, limb_offset(i)))
+}
+
+pub(crate) fn store(bcx: &mut FunctionBuilder<'_>, ty: Type, x: Limbs, ptr: Value) {
+    if ty == I160 {
+        bcx.ins().store(mem_flags(), x[0], ptr, 0);
+        bcx.ins().store(mem_flags(), x[1], ptr, 8);
+        bcx.ins().istore32(mem_flags(), x[2], ptr, 16);
+        return;
+    }
+    for (i, limb) in x.into_iter().enumerate() {
+        bcx.ins().store(mem_flags(), limb, ptr, limb_offset(i));
+    }
+}
+
+pub(crate) fn stack_load(bcx: &mut FunctionBuilder<'_>, slot: StackSlot) -> Limbs {
+    std::array::from_fn(|i| bcx.ins().stack_load(types::I64, slot, limb_offset(i)))
+}
+
+pub(crate) fn stack_store(bcx: &mut FunctionBuilder<'_>, x: Limbs, slot: StackSlot) {
+    for (i, limb) in x.into_iter().enumerate() {
+        bcx.ins().stack_store(limb, slot, limb_offset(i));
+    }
+}
+
+/// Zero- or sign-extends an integer of at most 128 bits.
+pub(crate) fn extend(bcx: &mut FunctionBuilder<'_>, value: Value, signed: bool) -> Limbs {
+    let ty = bcx.func.dfg.value_type(value);
+    let (low, high) = match ty.bits() {
+        128 => {
+            let (low, high) = bcx.ins().isplit(value);
+            (low, Some(high))
+        }
+        64 => (value, None),
+        _ if signed => (bcx.ins().sextend(types::I64, value), None),
+        _ => (bcx.ins().uextend(types::I64, value), None),
+    };
+    let fill = if signed {
+        bcx.ins().sshr_imm(high.unwrap_or(low), 63)
+    } else {
+        bcx.ins().iconst(types::I64, 0)
+    };
+    [low, high.unwrap_or(fill), fill, fill]
+}
+
+/// Truncates to an integer of at most 128 bits.
+pub(crate) fn reduce(bcx: &mut FunctionBuilder<'_>, ty: Type, x: Limbs) -> Value {
+    match ty.bits() {
+        128 => bcx.ins().iconcat(x[0], x[1]),
+        64 => x[0],
+        _ => bcx.ins().ireduce(ty, x[0]),
+    }
+}
+
+/// Returns `a + b` and the carry flag.
+pub(crate) fn add(bcx: &mut FunctionBuilder<'_>, a: Limbs, b: Limbs) -> (Limbs, Value) {
+    let mut carry = None;
+    let r = std::array::from_fn(|i| {
+        let (mut sum, mut c) = bcx.ins().uadd_overflow(a[i], b[i]);
+        if let Some(carry) = carry {
+            let carry = bcx.ins().uextend(types::I64, carry);
+            let (sum2, c2) = bcx.ins().uadd_overflow(sum, carry);
+            // At most one of the two additions can overflow.
+            (sum, c) = (sum2, bcx.ins().bor(c, c2));
+        }
+        carry = Some(c);
+        sum
+    });
+    (r, carry.unwrap())
+}
+
+/// Returns `a - b` and the borrow flag.
+pub(crate) fn sub(bcx: &mut FunctionBuilder<'_>, a: Limbs, b: Limbs) -> (Limbs, Value) {
+    let mut borrow = None;
+    let r = std::array::from_fn(|i| {
+        let (mut diff, mut c) = bcx.ins().usub_overflow(a[i], b[i]);
+        if let Some(borrow) = borrow {
+            let borrow = bcx.ins().uextend(types::I64, borrow);
+            let (diff2, c2) = bcx.ins().usub_overflow(diff, borrow);
+            (diff, c) = (diff2, bcx.ins().bor(c, c2));
+        }
+        borrow = Some(c);
+        diff
+    });
+    (r, borrow.unwrap())
+}
+
+/// Returns `a * b`, truncated to 256 bits.
+pub(crate) fn mul(bcx: &mut FunctionBuilder<'_>, a: Limbs, b: Limbs) -> Limbs {
+    let zero = bcx.ins().iconst(types::I64, 0);
+    let mut r = [zero; 4];
+    for (i, &a) in a.iter().enumerate() {
+        let mut carry = zero;
+        for (j, &b) in b.iter().take(4 - i).enumerate() {
+            let k = i + j;
+            let low = bcx.ins().imul(a, b);
+            let (sum, c1) = bcx.ins().uadd_overflow(r[k], low);
+            let (sum, c2) = bcx.ins().uadd_overflow(sum, carry);
+            r[k] = sum;
+            if k < 3 {
+                // `a[i] * b[j] + r[k] + carry` always fits in 128 bits.
+                let high = bcx.ins().umulhi(a, b);
+                let c1 = bcx.ins().uextend(types::I64, c1);
+                let c2 = bcx.ins().uextend(types::I64, c2);
+                let high = bcx.ins().iadd(high, c1);
+                carry = bcx.ins().iadd(high, c2);
+            }
+        }
+    }
+    r
+}
+
+pub(crate) fn icmp(bcx: &mut FunctionBuilder<'_>, cond: IntCC, a: Limbs, b: Limbs) -> Value {
+    let unsigned = cond.unsigned();
+    if cond != unsigned {
+        // Flipping the sign bits maps signed order onto unsigned order.
+        let flip = |bcx: &mut FunctionBuilder<'_>, mut x: Limbs| {
+            x[3] = bcx.ins().bxor_imm(x[3], i64::MIN);
+            x
+        };
+        let a = flip(bcx, a);
+        let b = flip(bcx, b);
+        return icmp(bcx, unsigned, a, b);
+    }
+    match cond {
+        IntCC::Equal => eq(bcx, a, b),
+        IntCC::NotEqual => {
+            let r = eq(bcx, a, b);
+            bcx.ins().bxor_imm(r, 1)
+        }
+        IntCC::UnsignedLessThan => ult(bcx, a, b),
+        IntCC::UnsignedGreaterThan => ult(bcx, b, a),
+        IntCC::UnsignedGreaterThanOrEqual => {
+            let r = ult(bcx, a, b);
+            bcx.ins().bxor_imm(r, 1)
+        }
+        IntCC::UnsignedLessThanOrEqual => {
+            let r = ult(bcx, b, a);
+            bcx.ins().bxor_imm(r, 1)
+        }
+        _ => unreachable!("{cond}"),
+    }
+}
+
+fn eq(bcx: &mut FunctionBuilder<'_>, a: Limbs, b: Limbs) -> Value {
+    let diff = bitwise(bcx, a, b, |bcx, a, b| bcx.ins().bxor(a, b));
+    let any = or_all(bcx, &diff);
+    bcx.ins().icmp_imm(IntCC::Equal, any, 0)
+}
+
+fn ult(bcx: &mut FunctionBuilder<'_>, a: Limbs, b: Limbs) -> Value {
+    let mut lt = bcx.ins().icmp(IntCC::UnsignedLessThan, a[0], b[0]);
+    for (&a, &b) in a.iter().zip(&b).skip(1) {
+        let limb_eq = bcx.ins().icmp(IntCC::Equal, a, b);
+        let limb_lt = bcx.ins().icmp(IntCC::UnsignedLessThan, a, b);
+        lt = bcx.ins().select(limb_eq, lt, limb_lt);
+    }
+    lt
+}
+
+/// Returns the bitwise OR of `limbs`.
+pub(crate) fn or_all(bcx: &mut FunctionBuilder<'_>, limbs: &[Value]) -> Value {
+    limbs.iter().copied().reduce(|a, b| bcx.ins().bor(a, b)).unwrap()
+}
+
+pub(crate) fn bitwise(
+    bcx: &mut FunctionBuilder<'_>,
+    a: Limbs,
+    b: Limbs,
+    mut f: impl FnMut(&mut FunctionBuilder<'_>, Value, Value) -> Value,
+) -> Limbs {
+    std::array::from_fn(|i| f(bcx, a[i], b[i]))
+}
+
+pub(crate) fn select(bcx: &mut FunctionBuilder<'_>, cond: Value, a: Limbs, b: Limbs) -> Limbs {
+    bitwise(bcx, a, b, |bcx, a, b| bcx.ins().select(cond, a, b))
+}
+
+pub(crate) fn bswap(bcx: &mut FunctionBuilder<'_>, ty: Type, x: Limbs) -> Limbs {
+    let r = std::array::from_fn(|i| bcx.ins().bswap(x[3 - i]));
+    if ty == I160 {
+        let shift = imm(bcx, 96);
+        return ushr(bcx, r, shift);
+    }
+    r
+}
+
+/// Clears the bits above `ty`.
+pub(crate) fn truncate(bcx: &mut FunctionBuilder<'_>, ty: Type, mut x: Limbs) -> Limbs {
+    if ty == I160 {
+        x[2] = bcx.ins().band_imm(x[2], u32::MAX as i64);
+        x[3] = bcx.ins().iconst(types::I64, 0);
+    }
+    x
+}
+
+pub(crate) fn shl(bcx: &mut FunctionBuilder<'_>, x: Limbs, shift: Limbs) -> Limbs {
+    let (bits, limbs, inv_bits) = split_shift(bcx, shift);
+    let zero = bcx.ins().iconst(types::I64, 0);
+    let t: Limbs = std::array::from_fn(|i| {
+        let high = bcx.ins().ishl(x[i], bits);
+        if i == 0 {
+            return high;
+        }
+        // `x[i - 1] >> (64 - bits)`, which must be 0 when `bits == 0`.
+        let low = bcx.ins().ushr_imm(x[i - 1], 1);
+        let low = bcx.ins().ushr(low, inv_bits);
+        bcx.ins().bor(high, low)
+    });
+    shift_limbs(bcx, limbs, |i, n| i.checked_sub(n).map_or(zero, |j| t[j]))
+}
+
+pub(crate) fn ushr(bcx: &mut FunctionBuilder<'_>, x: Limbs, shift: Limbs) -> Limbs {
+    let zero = bcx.ins().iconst(types::I64, 0);
+    shr(bcx, x, shift, zero, |bcx, x, bits| bcx.ins().ushr(x, bits))
+}
+
+pub(crate) fn sshr(bcx: &mut FunctionBuilder<'_>, x: Limbs, shift: Limbs) -> Limbs {
+    let sign = bcx.ins().sshr_imm(x[3], 63);
+    shr(bcx, x, shift, sign, |bcx, x, bits| bcx.ins().sshr(x, bits))
+}
+
+fn shr(
+    bcx: &mut FunctionBuilder<'_>,
+    x: Limbs,
+    shift: Limbs,
+    fill: Value,
+    shr_top: impl FnOnce(&mut FunctionBuilder<'_>, Value, Value) -> Value,
+) -> Limbs {
+    let (bits, limbs, inv_bits) = split_shift(bcx, shift);
+    let mut t: Limbs = std::array::from_fn(|i| {
+        if i == 3 {
+            return x[i];
+        }
+        let low = bcx.ins().ushr(x[i], bits);
+        // `x[i + 1] << (64 - bits)`, which must be 0 when `bits == 0`.
+        let high = bcx.ins().ishl_imm(x[i + 1], 1);
+        let high = bcx.ins().ishl(high, inv_bits);
+        bcx.ins().bor(high, low)
+    });
+    t[3] = shr_top(bcx, x[3], bits);
+    shift_limbs(bcx, limbs, |i, n| t.get(i + n).copied().unwrap_or(fill))
+}
+
+/// Splits a shift amount into `(bits % 64, bits / 64, 63 - bits % 64)`.
+///
+/// Only the low 8 bits of the shift are used; the translator handles shifts of 256 or more.
+fn split_shift(bcx: &mut FunctionBuilder<'_>, shift: Limbs) -> (Value, Value, Value) {
+    let bits = bcx.ins().band_imm(shift[0], 63);
+    let limbs = bcx.ins().ushr_imm(shift[0], 6);
+    let limbs = bcx.ins().band_imm(limbs, 3);
+    let inv_bits = bcx.ins().bxor_imm(bits, 63);
+    (bits, limbs, inv_bits)
+}
+
+/// Builds each limb `i` of the result by selecting `get(i, n)` where `n` is the dynamic number of
+/// limbs to shift by.
+fn shift_limbs(
+    bcx: &mut FunctionBuilder<'_>,
+    limbs: Value,
+    get: impl Fn(usize, usize) -> Value,
+) -> Limbs {
+    std::array::from_fn(|i| {
+        let mut r = get(i, 0);
+        for n in 1..4 {
+            let is_n = bcx.ins().icmp_imm(IntCC::Equal, limbs, n as i64);
+            r = bcx.ins().select(is_n, get(i, n), r);
+        }
+        r
+    })
+}
+
+/// Out-of-line `i256` operations.
+///
+/// Called with pointers to the result and to the operands:
+/// `fn(out: *mut i256, lhs: *const i256, rhs: *const i256)`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Helper {
+    UDiv,
+    SDiv,
+    URem,
+    SRem,
+}
+
+impl Helper {
+    pub(crate) const fn name(self) -> &'static str {
+        match self {
+            Self::UDiv => "__revmc_cranelift_i256_udiv",
+            Self::SDiv => "__revmc_cranelift_i256_sdiv",
+            Self::URem => "__revmc_cranelift_i256_urem",
+            Self::SRem => "__revmc_cranelift_i256_srem",
+        }
+    }
+
+    pub(crate) fn addr(self) -> usize {
+        match self {
+            Self::UDiv => __revmc_cranelift_i256_udiv as *const () as usize,
+            Self::SDiv => __revmc_cranelift_i256_sdiv as *const () as usize,
+            Self::URem => __revmc_cranelift_i256_urem as *const () as usize,
+            Self::SRem => __revmc_cranelift_i256_srem as *const () as usize,
+        }
+    }
+}
+
+// Division by zero returns zero instead of trapping; the translator selects the EVM result anyway.
+
+#[no_mangle]
+unsafe extern "C" fn __revmc_cranelift_i256_udiv(out: *mut U256, a: *const U256, b: *const U256) {
+    binop(out, a, b, |a, b| a.checked_div(b).unwrap_or_default())
+}
+
+#[no_mangle]
+unsafe extern "C" fn __revmc_cranelift_i256_urem(out: *mut U256, a: *const U256, b: *const U256) {
+    binop(out, a, b, |a, b| a.checked_rem(b).unwrap_or_default())
+}
+
+#[no_mangle]
+unsafe extern "C" fn __revmc_cranelift_i256_sdiv(out: *mut U256, a: *const U256, b: *const U256) {
+    binop(out, a, b, |a, b| {
+        let r = abs(a).checked_div(abs(b)).unwrap_or_default();
+        if is_negative(a) != is_negative(b) {
+            r.wrapping_neg()
+        } else {
+            r
+        }
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn __revmc_cranelift_i256_srem(out: *mut U256, a: *const U256, b: *const U256) {
+    binop(out, a, b, |a, b| {
+        // The result has the sign of the dividend.
+        let r = abs(a).checked_rem(abs(b)).unwrap_or_default();
+        if is_negative(a) {
+            r.wrapping_neg()
+        } else {
+            r
+        }
+    })
+}
+
+/// The operands live in Cranelift stack slots, which are not necessarily aligned.
+#[inline(always)]
+unsafe fn binop(out: *mut U256, a: *const U256, b: *const U256, f: fn(U256, U256) -> U256) {
+    out.write_unaligned(f(a.read_unaligned(), b.read_unaligned()));
+}
+
+fn is_negative(x: U256) -> bool {
+    x.bit(255)
+}
+
+fn abs(x: U256) -> U256 {
+    if is_negative(x) {
+        x.wrapping_neg()
+    } else {
+        x
+    }
+}
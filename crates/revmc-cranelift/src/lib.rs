@@ -2,7 +2,7 @@
 #![cfg_attr(not(test), warn(unused_extern_crates))]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
-use codegen::ir::Function;
+use codegen::ir::{Function, UserFuncName};
 use cranelift::{
     codegen::ir::{FuncRef, StackSlot},
     prelude::*,
@@ -13,7 +13,7 @@ use cranelift_object::{ObjectBuilder, ObjectModule};
 use pretty_clif::CommentWriter;
 use revmc_backend::{
     eyre::eyre, Backend, BackendTypes, Builder, OptimizationLevel, Result, TailCallKind,
-    TypeMethods, U256,
+    TargetDataLayout, TypeMethods, U256,
 };
 use std::{
     collections::HashMap,
@@ -22,6 +22,7 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+mod i256;
 mod pretty_clif;
 
 pub use cranelift;
@@ -47,8 +48,13 @@ pub struct EvmCraneliftBackend {
     symbols: Symbols,
 
     opt_level: OptimizationLevel,
+    is_dumping: bool,
     comments: CommentWriter,
+    /// Functions that have been built but not defined yet.
     functions: Vec<FuncId>,
+    /// The sizes of the machine code of the defined functions.
+    function_sizes: HashMap<FuncId, usize>,
+    i256_values: HashMap<Value, (Type, i256::Limbs)>,
 }
 
 #[allow(clippy::new_without_default)]
@@ -75,11 +81,28 @@ impl EvmCraneliftBackend {
             module,
             symbols,
             opt_level,
+            is_dumping: false,
             comments: CommentWriter::new(),
             functions: Vec::new(),
+            function_sizes: HashMap::new(),
+            i256_values: HashMap::new(),
         }
     }
 
+    /// Defines the functions that have been built so far.
+    ///
+    /// The context only holds one function at a time, so this must be called before building the
+    /// next one.
+    fn define_functions(&mut self) -> Result<()> {
+        for id in self.functions.drain(..) {
+            self.module.get_mut().define_function(id, &mut self.ctx)?;
+            if let Some(code) = self.ctx.compiled_code() {
+                self.function_sizes.insert(id, code.code_info().total_size as usize);
+            }
+        }
+        Ok(())
+    }
+
     fn finish_module(&mut self) -> Result<Option<ObjectModule>> {
         let aot = match self.module {
             ModuleWrapper::Jit(_) => {
@@ -100,6 +123,8 @@ impl EvmCraneliftBackend {
             }
         };
         self.module.get().clear_context(&mut self.ctx);
+        self.functions.clear();
+        self.function_sizes.clear();
         Ok(aot)
     }
 }
@@ -109,7 +134,8 @@ impl BackendTypes for EvmCraneliftBackend {
     type Value = Value;
     type StackSlot = StackSlot;
     type BasicBlock = Block;
-    type Function = FuncRef;
+    /// Function references are local to the function they are used in, so IDs are used instead.
+    type Function = FuncId;
 }
 
 impl TypeMethods for EvmCraneliftBackend {
@@ -122,15 +148,15 @@ impl TypeMethods for EvmCraneliftBackend {
     }
 
     fn type_int(&self, bits: u32) -> Self::Type {
-        bits.try_into().ok().and_then(Type::int).unwrap_or_else(|| unimplemented!("type: i{bits}"))
+        convert_int_type(bits)
     }
 
     fn type_array(&self, ty: Self::Type, size: u32) -> Self::Type {
-        unimplemented!("type: [{size} x {ty}]")
+        convert_array_type(ty, size)
     }
 
     fn type_bit_width(&self, ty: Self::Type) -> u32 {
-        ty.bits()
+        i256::type_bits(ty)
     }
 }
 
@@ -142,11 +168,16 @@ impl Backend for EvmCraneliftBackend {
         "clif"
     }
 
+    fn codegen_version(&self) -> String {
+        format!("Cranelift {}", cranelift::VERSION)
+    }
+
     fn set_module_name(&mut self, name: &str) {
         let _ = name;
     }
 
     fn set_is_dumping(&mut self, yes: bool) {
+        self.is_dumping = yes;
         self.ctx.set_disasm(yes);
     }
 
@@ -168,6 +199,32 @@ impl Backend for EvmCraneliftBackend {
         self.module.is_aot()
     }
 
+    fn target_triple(&self) -> String {
+        self.module.get().isa().triple().to_string()
+    }
+
+    fn target_cpu(&self) -> String {
+        // Cranelift does not have a concept of a target CPU, only ISA flags.
+        String::new()
+    }
+
+    fn target_features(&self) -> String {
+        let flags = self.module.get().isa().isa_flags();
+        flags.iter().map(ToString::to_string).collect::<Vec<_>>().join(",")
+    }
+
+    fn target_data_layout(&self) -> TargetDataLayout {
+        let isa = self.module.get().isa();
+        let pointer_size = isa.pointer_bytes() as u32;
+        TargetDataLayout {
+            big_endian: isa.endianness() == codegen::ir::Endianness::Big,
+            pointer_size,
+            // Cranelift aligns integers to their size.
+            pointer_align: pointer_size,
+            i64_align: 8,
+        }
+    }
+
     fn function_name_is_unique(&self, name: &str) -> bool {
         self.module.get().get_name(name).is_none()
     }
@@ -183,7 +240,7 @@ impl Backend for EvmCraneliftBackend {
     }
 
     fn dump_disasm(&mut self, path: &Path) -> Result<()> {
-        if let Some(disasm) = &self.ctx.compiled_code().unwrap().vcode {
+        if let Some(disasm) = self.ctx.compiled_code().and_then(|code| code.vcode.as_ref()) {
             crate::pretty_clif::write_ir_file(path, |file| file.write_all(disasm.as_bytes()))
         }
         Ok(())
@@ -197,13 +254,10 @@ impl Backend for EvmCraneliftBackend {
         param_names: &[&str],
         linkage: revmc_backend::Linkage,
     ) -> Result<(Self::Builder<'_>, FuncId)> {
-        self.ctx.func.clear();
-        if let Some(ret) = ret {
-            self.ctx.func.signature.returns.push(AbiParam::new(ret));
-        }
-        for param in params {
-            self.ctx.func.signature.params.push(AbiParam::new(*param));
-        }
+        self.define_functions()?;
+        self.module.get().clear_context(&mut self.ctx);
+        self.ctx.set_disasm(self.is_dumping);
+        self.ctx.func.signature = make_signature(self.module.get(), params, ret);
         let _ = param_names;
         let ptr_type = self.type_ptr();
         let id = self.module.get_mut().declare_function(
@@ -211,7 +265,10 @@ impl Backend for EvmCraneliftBackend {
             convert_linkage(linkage),
             &self.ctx.func.signature,
         )?;
+        self.ctx.func.name = UserFuncName::user(0, id.as_u32());
         self.functions.push(id);
+        self.builder_context = FunctionBuilderContext::new();
+        self.i256_values.clear();
         let bcx = FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_context);
         let mut builder = EvmCraneliftBuilder {
             module: &mut self.module,
@@ -219,9 +276,12 @@ impl Backend for EvmCraneliftBackend {
             bcx,
             ptr_type,
             symbols: self.symbols.clone(),
+            params: params.to_vec(),
+            i256_values: &mut self.i256_values,
+            switch_defaults: HashMap::new(),
+            func_refs: HashMap::new(),
         };
-        let entry = builder.bcx.create_block();
-        builder.bcx.append_block_params_for_function_params(entry);
+        builder.switch_to_entry_block();
         Ok((builder, id))
     }
 
@@ -235,13 +295,8 @@ impl Backend for EvmCraneliftBackend {
         // cannot finish relocations until all functions to be called are
         // defined. For this toy demo for now, we'll just finalize the
         // function below.
-        for &id in &self.functions {
-            self.module.get_mut().define_function(id, &mut self.ctx)?;
-        }
-        self.functions.clear();
-
-        // Now that compilation is finished, we can clear out the context state.
-        self.module.get().clear_context(&mut self.ctx);
+        // The context is kept until the next function is built so that it can be dumped.
+        self.define_functions()?;
 
         // Finalize the functions which we just defined, which resolves any outstanding relocations
         // (patching in addresses, now that they're available).
@@ -264,6 +319,24 @@ impl Backend for EvmCraneliftBackend {
         self.module.get_finalized_function(id).map(|ptr| ptr as usize)
     }
 
+    fn jit_function_size(&self, id: Self::FuncId) -> Option<usize> {
+        self.function_sizes.get(&id).copied()
+    }
+
+    fn jit_detaches_module(&self) -> bool {
+        false
+    }
+
+    fn jit_object(
+        &mut self,
+        name: &str,
+        object: &[u8],
+        symbols: &[(&str, usize)],
+    ) -> Result<usize> {
+        let _ = (name, object, symbols);
+        Err(eyre!("loading objects is not supported by the Cranelift backend"))
+    }
+
     unsafe fn free_function(&mut self, id: Self::FuncId) -> Result<()> {
         // This doesn't exist yet.
         let _ = id;
@@ -273,6 +346,10 @@ impl Backend for EvmCraneliftBackend {
     unsafe fn free_all_functions(&mut self) -> Result<()> {
         self.finish_module().map(drop)
     }
+
+    unsafe fn free_all_objects(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// The Cranelift-based EVM bytecode compiler function builder.
@@ -283,6 +360,14 @@ pub struct EvmCraneliftBuilder<'a> {
     bcx: FunctionBuilder<'a>,
     ptr_type: Type,
     symbols: Symbols,
+    /// The parameter types of the current function, with `i256`s not yet split into limbs.
+    params: Vec<Type>,
+    /// The limbs of the `i256` values of the current function.
+    i256_values: &'a mut HashMap<Value, (Type, i256::Limbs)>,
+    /// Blocks that end in a `switch`, and the block that branches to the switch's default block.
+    switch_defaults: HashMap<Block, Block>,
+    /// Functions declared in the current function.
+    func_refs: HashMap<FuncId, FuncRef>,
 }
 
 impl<'a> BackendTypes for EvmCraneliftBuilder<'a> {
@@ -303,15 +388,15 @@ impl<'a> TypeMethods for EvmCraneliftBuilder<'a> {
     }
 
     fn type_int(&self, bits: u32) -> Self::Type {
-        bits.try_into().ok().and_then(Type::int).unwrap_or_else(|| unimplemented!("type: i{bits}"))
+        convert_int_type(bits)
     }
 
     fn type_array(&self, ty: Self::Type, size: u32) -> Self::Type {
-        unimplemented!("type: [{size} x {ty}]")
+        convert_array_type(ty, size)
     }
 
     fn type_bit_width(&self, ty: Self::Type) -> u32 {
-        ty.bits()
+        i256::type_bits(ty)
     }
 }
 
@@ -326,7 +411,10 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
 
     fn create_block_after(&mut self, after: Self::BasicBlock, name: &str) -> Self::BasicBlock {
         let block = self.create_block(name);
-        self.bcx.insert_block_after(block, after);
+        // Blocks are only inserted into the layout once they have instructions.
+        if self.bcx.func.layout.is_block_inserted(after) {
+            self.bcx.insert_block_after(block, after);
+        }
         block
     }
 
@@ -360,13 +448,29 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         self.comments.add_comment(inst, comment);
     }
 
+    fn set_debug_file(&mut self, file: &Path) {
+        // Cranelift does not emit DWARF line tables.
+        let _ = file;
+    }
+
+    fn set_debug_location(&mut self, line: u32) {
+        let _ = line;
+    }
+
     fn fn_param(&mut self, index: usize) -> Self::Value {
-        let block = self.current_block().unwrap();
-        self.bcx.block_params(block)[index]
+        let entry = self.bcx.func.layout.entry_block().unwrap();
+        let start = self.params[..index].iter().map(|&ty| i256::abi_len(ty)).sum::<usize>();
+        let params = &self.bcx.block_params(entry)[start..];
+        if self.params[index] == i256::I256 {
+            let limbs = [params[0], params[1], params[2], params[3]];
+            self.i256_value(limbs)
+        } else {
+            params[0]
+        }
     }
 
     fn num_fn_params(&self) -> usize {
-        self.bcx.func.signature.params.len()
+        self.params.len()
     }
 
     fn bool_const(&mut self, value: bool) -> Self::Value {
@@ -374,16 +478,22 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
     }
 
     fn iconst(&mut self, ty: Self::Type, value: i64) -> Self::Value {
+        if ty == i256::I256 {
+            return self.i256_imm(value);
+        }
         self.bcx.ins().iconst(ty, value)
     }
 
     fn uconst(&mut self, ty: Self::Type, value: u64) -> Self::Value {
+        if ty == i256::I256 {
+            return self.iconst_256(U256::from(value));
+        }
         self.iconst(ty, value as i64)
     }
 
     fn iconst_256(&mut self, value: U256) -> Self::Value {
-        let _ = value;
-        todo!("no i256 :(")
+        let limbs = i256::iconst(&mut self.bcx, value);
+        self.i256_value(limbs)
     }
 
     fn str_const(&mut self, value: &str) -> Self::Value {
@@ -442,39 +552,72 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         let _ = name;
         self.bcx.create_sized_stack_slot(StackSlotData {
             kind: StackSlotKind::ExplicitSlot,
-            size: ty.bytes(),
+            size: i256::type_bytes(ty),
             align_shift: 1,
         })
     }
 
     fn stack_load(&mut self, ty: Self::Type, slot: Self::StackSlot, name: &str) -> Self::Value {
         let _ = name;
+        if ty == i256::I256 {
+            let limbs = i256::stack_load(&mut self.bcx, slot);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().stack_load(ty, slot, 0)
     }
 
     fn stack_store(&mut self, value: Self::Value, slot: Self::StackSlot) {
+        if let Some(limbs) = self.limbs(value) {
+            return i256::stack_store(&mut self.bcx, limbs, slot);
+        }
         self.bcx.ins().stack_store(value, slot, 0);
     }
 
     fn stack_addr(&mut self, ty: Self::Type, slot: Self::StackSlot) -> Self::Value {
-        self.bcx.ins().stack_addr(ty, slot, 0)
+        // `ty` is the type of the slot's contents, the address is always pointer-sized.
+        let _ = ty;
+        self.bcx.ins().stack_addr(self.ptr_type, slot, 0)
     }
 
     fn load(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value {
         let _ = name;
+        if i256::is_wide(ty) {
+            let limbs = i256::load(&mut self.bcx, ty, ptr);
+            return self.wide_value(ty, limbs);
+        }
         self.bcx.ins().load(ty, MemFlags::trusted(), ptr, 0)
     }
 
     fn store(&mut self, value: Self::Value, ptr: Self::Value) {
+        if let Some(&(ty, limbs)) = self.i256_values.get(&value) {
+            return i256::store(&mut self.bcx, ty, limbs, ptr);
+        }
         self.bcx.ins().store(MemFlags::trusted(), value, ptr, 0);
     }
 
+    fn load_unaligned(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value {
+        let _ = name;
+        if i256::is_wide(ty) {
+            let limbs = i256::load(&mut self.bcx, ty, ptr);
+            return self.wide_value(ty, limbs);
+        }
+        self.bcx.ins().load(ty, MemFlags::new().with_notrap(), ptr, 0)
+    }
+
+    fn store_unaligned(&mut self, value: Self::Value, ptr: Self::Value) {
+        if let Some(&(ty, limbs)) = self.i256_values.get(&value) {
+            return i256::store(&mut self.bcx, ty, limbs, ptr);
+        }
+        self.bcx.ins().store(MemFlags::new().with_notrap(), value, ptr, 0);
+    }
+
     fn nop(&mut self) {
         self.bcx.ins().nop();
     }
 
     fn ret(&mut self, values: &[Self::Value]) {
-        self.bcx.ins().return_(values);
+        let values = self.expand_values(values);
+        self.bcx.ins().return_(&values);
     }
 
     fn icmp(
@@ -483,10 +626,17 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         lhs: Self::Value,
         rhs: Self::Value,
     ) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            return i256::icmp(&mut self.bcx, convert_intcc(cond), a, b);
+        }
         self.bcx.ins().icmp(convert_intcc(cond), lhs, rhs)
     }
 
     fn icmp_imm(&mut self, cond: revmc_backend::IntCC, lhs: Self::Value, rhs: i64) -> Self::Value {
+        if self.limbs(lhs).is_some() {
+            let rhs = self.i256_imm(rhs);
+            return self.icmp(cond, lhs, rhs);
+        }
         self.bcx.ins().icmp_imm(convert_intcc(cond), lhs, rhs)
     }
 
@@ -519,28 +669,61 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         default_is_cold: bool,
     ) {
         let _ = default_is_cold;
+        let current = self.current_block().unwrap();
+
+        // All the edges to `default` go through a single block, so that `phi` can find them.
+        let default_edge = self.bcx.create_block();
+        self.switch_defaults.insert(current, default_edge);
+
+        let index = if let Some(limbs) = self.limbs(index) {
+            // Values that do not fit in the low limb can only go to the default block.
+            let high = i256::or_all(&mut self.bcx, &limbs[1..]);
+            let low = self.bcx.create_block();
+            self.bcx.ins().brif(high, default_edge, &[], low, &[]);
+            self.bcx.seal_block(low);
+            self.bcx.switch_to_block(low);
+            limbs[0]
+        } else {
+            index
+        };
+
         let mut switch = cranelift::frontend::Switch::new();
         for (value, block) in targets {
             switch.set_entry(*value as u128, *block);
         }
-        switch.emit(&mut self.bcx, index, default)
+        switch.emit(&mut self.bcx, index, default_edge);
+
+        self.bcx.seal_block(default_edge);
+        self.bcx.switch_to_block(default_edge);
+        self.bcx.ins().jump(default, &[]);
     }
 
     fn br_indirect(&mut self, _address: Self::Value, _destinations: &[Self::BasicBlock]) {
-        unimplemented!()
+        unreachable!("`block_addr` is not supported, so there are no addresses to branch to")
     }
 
     fn phi(&mut self, ty: Self::Type, incoming: &[(Self::Value, Self::BasicBlock)]) -> Self::Value {
         let current = self.current_block().unwrap();
-        let param = self.bcx.append_block_param(current, ty);
-        for &(value, block) in incoming {
-            self.bcx.switch_to_block(block);
-            let last_inst = self.bcx.func.layout.last_inst(block).unwrap();
-            let src = self.bcx.ins().jump(current, &[value]);
-            self.bcx.func.transplant_inst(last_inst, src);
+        let params = (0..i256::abi_len(ty))
+            .map(|_| self.bcx.append_block_param(current, i256::abi_type(ty)))
+            .collect::<Vec<_>>();
+        for (i, &(value, block)) in incoming.iter().enumerate() {
+            // All the edges of a block are updated at once.
+            if incoming[..i].iter().any(|&(_, b)| b == block) {
+                continue;
+            }
+            let args = self.expand_values(&[value]);
+            if !self.append_branch_args(block, current, &args) {
+                let default_edge = self.switch_defaults[&block];
+                let found = self.append_branch_args(default_edge, current, &args);
+                assert!(found, "{block} does not branch to {current}");
+            }
+        }
+        if ty == i256::I256 {
+            self.i256_value([params[0], params[1], params[2], params[3]])
+        } else {
+            params[0]
         }
-        self.bcx.switch_to_block(current);
-        param
     }
 
     fn select(
@@ -549,6 +732,10 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         then_value: Self::Value,
         else_value: Self::Value,
     ) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(then_value, else_value) {
+            let limbs = i256::select(&mut self.bcx, cond, a, b);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().select(cond, then_value, else_value)
     }
 
@@ -566,54 +753,90 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         };
         let else_block = self.create_block_after(then_block, "else");
         let done_block = self.create_block_after(else_block, "contd");
-        let done_value = self.bcx.append_block_param(done_block, ty);
+        let done_values = (0..i256::abi_len(ty))
+            .map(|_| self.bcx.append_block_param(done_block, i256::abi_type(ty)))
+            .collect::<Vec<_>>();
 
         self.brif(cond, then_block, else_block);
 
         self.seal_block(then_block);
         self.switch_to_block(then_block);
         let then_value = then_value(self);
-        self.bcx.ins().jump(done_block, &[then_value]);
+        let then_values = self.expand_values(&[then_value]);
+        self.bcx.ins().jump(done_block, &then_values);
 
         self.seal_block(else_block);
         self.switch_to_block(else_block);
         let else_value = else_value(self);
-        self.bcx.ins().jump(done_block, &[else_value]);
+        let else_values = self.expand_values(&[else_value]);
+        self.bcx.ins().jump(done_block, &else_values);
 
         self.seal_block(done_block);
         self.switch_to_block(done_block);
-        done_value
+        if ty == i256::I256 {
+            self.i256_value([done_values[0], done_values[1], done_values[2], done_values[3]])
+        } else {
+            done_values[0]
+        }
     }
 
     fn iadd(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::add(&mut self.bcx, a, b).0;
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().iadd(lhs, rhs)
     }
 
     fn isub(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::sub(&mut self.bcx, a, b).0;
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().isub(lhs, rhs)
     }
 
     fn imul(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::mul(&mut self.bcx, a, b);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().imul(lhs, rhs)
     }
 
     fn udiv(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            return self.call_i256_helper(i256::Helper::UDiv, a, b);
+        }
         self.bcx.ins().udiv(lhs, rhs)
     }
 
     fn sdiv(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            return self.call_i256_helper(i256::Helper::SDiv, a, b);
+        }
         self.bcx.ins().sdiv(lhs, rhs)
     }
 
     fn urem(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            return self.call_i256_helper(i256::Helper::URem, a, b);
+        }
         self.bcx.ins().urem(lhs, rhs)
     }
 
     fn srem(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            return self.call_i256_helper(i256::Helper::SRem, a, b);
+        }
         self.bcx.ins().srem(lhs, rhs)
     }
 
     fn iadd_imm(&mut self, lhs: Self::Value, rhs: i64) -> Self::Value {
+        if self.limbs(lhs).is_some() {
+            let rhs = self.i256_imm(rhs);
+            return self.iadd(lhs, rhs);
+        }
         self.bcx.ins().iadd_imm(lhs, rhs)
     }
 
@@ -622,82 +845,191 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
     }
 
     fn imul_imm(&mut self, lhs: Self::Value, rhs: i64) -> Self::Value {
+        if self.limbs(lhs).is_some() {
+            let rhs = self.i256_imm(rhs);
+            return self.imul(lhs, rhs);
+        }
         self.bcx.ins().imul_imm(lhs, rhs)
     }
 
     fn uadd_overflow(&mut self, lhs: Self::Value, rhs: Self::Value) -> (Self::Value, Self::Value) {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let (limbs, overflow) = i256::add(&mut self.bcx, a, b);
+            return (self.i256_value(limbs), overflow);
+        }
         self.bcx.ins().uadd_overflow(lhs, rhs)
     }
 
     fn usub_overflow(&mut self, lhs: Self::Value, rhs: Self::Value) -> (Self::Value, Self::Value) {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let (limbs, overflow) = i256::sub(&mut self.bcx, a, b);
+            return (self.i256_value(limbs), overflow);
+        }
         self.bcx.ins().usub_overflow(lhs, rhs)
     }
 
     fn uadd_sat(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let (sum, overflow) = i256::add(&mut self.bcx, a, b);
+            let max = i256::iconst(&mut self.bcx, U256::MAX);
+            let limbs = i256::select(&mut self.bcx, overflow, max, sum);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().uadd_sat(lhs, rhs)
     }
 
     fn umax(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let cond = i256::icmp(&mut self.bcx, IntCC::UnsignedGreaterThan, a, b);
+            let limbs = i256::select(&mut self.bcx, cond, a, b);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().umax(lhs, rhs)
     }
 
     fn umin(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let cond = i256::icmp(&mut self.bcx, IntCC::UnsignedLessThan, a, b);
+            let limbs = i256::select(&mut self.bcx, cond, a, b);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().umin(lhs, rhs)
     }
 
     fn bswap(&mut self, value: Self::Value) -> Self::Value {
+        if let Some(&(ty, x)) = self.i256_values.get(&value) {
+            let limbs = i256::bswap(&mut self.bcx, ty, x);
+            return self.wide_value(ty, limbs);
+        }
         self.bcx.ins().bswap(value)
     }
 
     fn bitor(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::bitwise(&mut self.bcx, a, b, |bcx, a, b| bcx.ins().bor(a, b));
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().bor(lhs, rhs)
     }
 
     fn bitand(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::bitwise(&mut self.bcx, a, b, |bcx, a, b| bcx.ins().band(a, b));
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().band(lhs, rhs)
     }
 
     fn bitxor(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::bitwise(&mut self.bcx, a, b, |bcx, a, b| bcx.ins().bxor(a, b));
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().bxor(lhs, rhs)
     }
 
     fn bitnot(&mut self, value: Self::Value) -> Self::Value {
+        if let Some(x) = self.limbs(value) {
+            let limbs = x.map(|limb| self.bcx.ins().bnot(limb));
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().bnot(value)
     }
 
     fn bitor_imm(&mut self, lhs: Self::Value, rhs: i64) -> Self::Value {
+        if self.limbs(lhs).is_some() {
+            let rhs = self.i256_imm(rhs);
+            return self.bitor(lhs, rhs);
+        }
         self.bcx.ins().bor_imm(lhs, rhs)
     }
 
     fn bitand_imm(&mut self, lhs: Self::Value, rhs: i64) -> Self::Value {
+        if self.limbs(lhs).is_some() {
+            let rhs = self.i256_imm(rhs);
+            return self.bitand(lhs, rhs);
+        }
         self.bcx.ins().band_imm(lhs, rhs)
     }
 
     fn bitxor_imm(&mut self, lhs: Self::Value, rhs: i64) -> Self::Value {
+        if self.limbs(lhs).is_some() {
+            let rhs = self.i256_imm(rhs);
+            return self.bitxor(lhs, rhs);
+        }
         self.bcx.ins().bxor_imm(lhs, rhs)
     }
 
     fn ishl(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::shl(&mut self.bcx, a, b);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().ishl(lhs, rhs)
     }
 
     fn ushr(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::ushr(&mut self.bcx, a, b);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().ushr(lhs, rhs)
     }
 
     fn sshr(&mut self, lhs: Self::Value, rhs: Self::Value) -> Self::Value {
+        if let Some((a, b)) = self.limbs2(lhs, rhs) {
+            let limbs = i256::sshr(&mut self.bcx, a, b);
+            return self.i256_value(limbs);
+        }
         self.bcx.ins().sshr(lhs, rhs)
     }
 
     fn zext(&mut self, ty: Self::Type, value: Self::Value) -> Self::Value {
+        if i256::is_wide(ty) {
+            // Wide values always have their upper bits cleared.
+            let limbs = match self.limbs(value) {
+                Some(x) => x,
+                None => i256::extend(&mut self.bcx, value, false),
+            };
+            let limbs = i256::truncate(&mut self.bcx, ty, limbs);
+            return self.wide_value(ty, limbs);
+        }
+        // Booleans are already `i8`s.
+        if self.bcx.func.dfg.value_type(value) == ty {
+            return value;
+        }
         self.bcx.ins().uextend(ty, value)
     }
 
     fn sext(&mut self, ty: Self::Type, value: Self::Value) -> Self::Value {
+        if ty == i256::I256 {
+            let limbs = match self.i256_values.get(&value) {
+                Some(&(from, x)) => {
+                    let shift = i256::imm(&mut self.bcx, (256 - i256::type_bits(from)) as i64);
+                    let x = i256::shl(&mut self.bcx, x, shift);
+                    i256::sshr(&mut self.bcx, x, shift)
+                }
+                None => i256::extend(&mut self.bcx, value, true),
+            };
+            return self.i256_value(limbs);
+        }
+        if self.bcx.func.dfg.value_type(value) == ty {
+            return value;
+        }
         self.bcx.ins().sextend(ty, value)
     }
 
     fn ireduce(&mut self, to: Self::Type, value: Self::Value) -> Self::Value {
+        if let Some(x) = self.limbs(value) {
+            if i256::is_wide(to) {
+                let limbs = i256::truncate(&mut self.bcx, to, x);
+                return self.wide_value(to, limbs);
+            }
+            return i256::reduce(&mut self.bcx, to, x);
+        }
+        if self.bcx.func.dfg.value_type(value) == to {
+            return value;
+        }
         self.bcx.ins().ireduce(to, value)
     }
 
@@ -709,7 +1041,15 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         name: &str,
     ) -> Self::Value {
         let _ = name;
-        let offset = self.bcx.ins().imul_imm(*indexes.first().unwrap(), ty.bytes() as i64);
+        // Arrays are vector types, so each subsequent index is into the lane type.
+        let (first, rest) = indexes.split_first().unwrap();
+        let mut offset = self.bcx.ins().imul_imm(*first, ty.bytes() as i64);
+        let mut ty = ty;
+        for &index in rest {
+            ty = ty.lane_type();
+            let index_offset = self.bcx.ins().imul_imm(index, ty.bytes() as i64);
+            offset = self.bcx.ins().iadd(offset, index_offset);
+        }
         self.bcx.ins().iadd(ptr, offset)
     }
 
@@ -719,11 +1059,19 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         args: &[Self::Value],
         tail_call: TailCallKind,
     ) -> Option<Self::Value> {
-        if tail_call != TailCallKind::None {
-            todo!();
+        // `Tail` and `NoTail` are only hints, so a regular call is correct.
+        // Guaranteed tail calls require the `tail` calling convention on both sides, which is not
+        // used for any function.
+        assert!(tail_call != TailCallKind::MustTail, "guaranteed tail calls are not supported");
+        let function = self.func_ref(function);
+        let args = self.expand_values(args);
+        let ins = self.bcx.ins().call(function, &args);
+        match self.bcx.inst_results(ins).to_vec()[..] {
+            [] => None,
+            [value] => Some(value),
+            [a, b, c, d] => Some(self.i256_value([a, b, c, d])),
+            ref results => unreachable!("unexpected number of results: {}", results.len()),
         }
-        let ins = self.bcx.ins().call(function, args);
-        self.bcx.inst_results(ins).first().copied()
     }
 
     fn is_compile_time_known(&mut self, _value: Self::Value) -> Option<Self::Value> {
@@ -751,46 +1099,49 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
             return f;
         }
 
-        let mut sig = self.module.get().make_signature();
-        if let Some(ret) = ret {
-            sig.returns.push(AbiParam::new(ret));
-        }
-        for param in params {
-            sig.params.push(AbiParam::new(*param));
-        }
-
+        let sig = make_signature(self.module.get(), params, ret);
         let id =
             self.module.get_mut().declare_function(name, convert_linkage(linkage), &sig).unwrap();
 
-        let mut func = Function::new();
-        func.signature = sig;
+        let mut func = Function::with_name_signature(UserFuncName::user(0, id.as_u32()), sig);
         let mut builder_ctx = FunctionBuilderContext::new();
         let new_bcx = FunctionBuilder::new(&mut func, &mut builder_ctx);
         // TODO: SAFETY: Not really safe, lifetime extension.
         let new_bcx =
             unsafe { std::mem::transmute::<FunctionBuilder<'_>, FunctionBuilder<'a>>(new_bcx) };
         let old_bcx = std::mem::replace(&mut self.bcx, new_bcx);
+        let old_params = std::mem::replace(&mut self.params, params.to_vec());
+        let old_i256_values = std::mem::take(self.i256_values);
+        let old_switch_defaults = std::mem::take(&mut self.switch_defaults);
+        let old_func_refs = std::mem::take(&mut self.func_refs);
 
-        let f = self.module.get_mut().declare_func_in_func(id, self.bcx.func);
-
-        let entry = self.bcx.create_block();
-        self.bcx.append_block_params_for_function_params(entry);
+        self.switch_to_entry_block();
         build(self);
+        self.bcx.seal_all_blocks();
 
-        self.bcx = old_bcx;
+        std::mem::replace(&mut self.bcx, old_bcx).finalize();
+        self.params = old_params;
+        *self.i256_values = old_i256_values;
+        self.switch_defaults = old_switch_defaults;
+        self.func_refs = old_func_refs;
 
-        f
+        let mut ctx = self.module.get().make_context();
+        ctx.func = func;
+        self.module.get_mut().define_function(id, &mut ctx).unwrap();
+
+        id
     }
 
     fn get_function(&mut self, name: &str) -> Option<Self::Function> {
-        self.module
-            .get()
-            .get_name(name)
-            .and_then(|id| match id {
-                FuncOrDataId::Func(f) => Some(f),
-                FuncOrDataId::Data(_) => None,
-            })
-            .map(|id| self.module.get_mut().declare_func_in_func(id, self.bcx.func))
+        self.module.get().get_name(name).and_then(|id| match id {
+            FuncOrDataId::Func(f) => Some(f),
+            FuncOrDataId::Data(_) => None,
+        })
+    }
+
+    fn func_addr(&mut self, function: Self::Function) -> Self::Value {
+        let function = self.func_ref(function);
+        self.bcx.ins().func_addr(self.ptr_type, function)
     }
 
     fn get_printf_function(&mut self) -> Self::Function {
@@ -809,19 +1160,11 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         address: Option<usize>,
         linkage: revmc_backend::Linkage,
     ) -> Self::Function {
-        let mut sig = self.module.get().make_signature();
-        if let Some(ret) = ret {
-            sig.returns.push(AbiParam::new(ret));
-        }
-        for param in params {
-            sig.params.push(AbiParam::new(*param));
-        }
+        let sig = make_signature(self.module.get(), params, ret);
         if let Some(address) = address {
             self.symbols.insert(name.to_string(), address as *const u8);
         }
-        let id =
-            self.module.get_mut().declare_function(name, convert_linkage(linkage), &sig).unwrap();
-        self.module.get_mut().declare_func_in_func(id, self.bcx.func)
+        self.module.get_mut().declare_function(name, convert_linkage(linkage), &sig).unwrap()
     }
 
     fn add_function_attribute(
@@ -837,6 +1180,111 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
     }
 }
 
+impl EvmCraneliftBuilder<'_> {
+    fn func_ref(&mut self, id: FuncId) -> FuncRef {
+        *self
+            .func_refs
+            .entry(id)
+            .or_insert_with(|| self.module.get_mut().declare_func_in_func(id, self.bcx.func))
+    }
+
+    fn switch_to_entry_block(&mut self) {
+        let entry = self.bcx.create_block();
+        self.bcx.append_block_params_for_function_params(entry);
+        self.bcx.switch_to_block(entry);
+        self.bcx.ensure_inserted_block();
+    }
+
+    /// Returns the limbs of `value` if it is an `i256` or an `i160`.
+    fn limbs(&self, value: Value) -> Option<i256::Limbs> {
+        self.i256_values.get(&value).map(|&(_, limbs)| limbs)
+    }
+
+    /// Returns the limbs of the operands of a binary operation if they are `i256`s.
+    fn limbs2(&self, lhs: Value, rhs: Value) -> Option<(i256::Limbs, i256::Limbs)> {
+        match (self.limbs(lhs), self.limbs(rhs)) {
+            (Some(a), Some(b)) => Some((a, b)),
+            (None, None) => None,
+            _ => panic!("mismatched operand types: {lhs}, {rhs}"),
+        }
+    }
+
+    /// Creates an `i256` value from its limbs.
+    ///
+    /// The returned value is a placeholder that is never used as an operand, and is removed as
+    /// dead code.
+    fn i256_value(&mut self, limbs: i256::Limbs) -> Value {
+        self.wide_value(i256::I256, limbs)
+    }
+
+    /// Creates a value of a type that is represented with limbs. See
+    /// [`i256_value`](Self::i256_value).
+    fn wide_value(&mut self, ty: Type, limbs: i256::Limbs) -> Value {
+        let value = self.bcx.ins().iconst(types::I8, 0);
+        self.i256_values.insert(value, (ty, limbs));
+        value
+    }
+
+    fn i256_imm(&mut self, imm: i64) -> Value {
+        let limbs = i256::imm(&mut self.bcx, imm);
+        self.i256_value(limbs)
+    }
+
+    /// Splits `i256` values into their limbs.
+    fn expand_values(&self, values: &[Value]) -> Vec<Value> {
+        let mut expanded = Vec::with_capacity(values.len());
+        for &value in values {
+            match self.limbs(value) {
+                Some(limbs) => expanded.extend(limbs),
+                None => expanded.push(value),
+            }
+        }
+        expanded
+    }
+
+    /// Appends `args` to the branches from the terminator of `from` to `to`.
+    ///
+    /// Returns `false` if there are none.
+    fn append_branch_args(&mut self, from: Block, to: Block, args: &[Value]) -> bool {
+        let mut found = false;
+        let last_inst = self.bcx.func.layout.last_inst(from).unwrap();
+        let dfg = &mut self.bcx.func.dfg;
+        for dest in dfg.insts[last_inst].branch_destination_mut(&mut dfg.jump_tables) {
+            if dest.block(&dfg.value_lists) == to {
+                for &arg in args {
+                    dest.append_argument(arg, &mut dfg.value_lists);
+                }
+                found = true;
+            }
+        }
+        found
+    }
+
+    fn call_i256_helper(&mut self, helper: i256::Helper, a: i256::Limbs, b: i256::Limbs) -> Value {
+        let name = helper.name();
+        let f = match self.get_function(name) {
+            Some(f) => f,
+            None => {
+                let ptr = self.ptr_type;
+                let linkage = revmc_backend::Linkage::Import;
+                self.add_function(name, &[ptr, ptr, ptr], None, Some(helper.addr()), linkage)
+            }
+        };
+        let slots = [None, Some(a), Some(b)].map(|value| {
+            let slot = self.new_stack_slot_raw(i256::I256, "");
+            if let Some(value) = value {
+                i256::stack_store(&mut self.bcx, value, slot);
+            }
+            slot
+        });
+        let args = slots.map(|slot| self.bcx.ins().stack_addr(self.ptr_type, slot, 0));
+        let f = self.func_ref(f);
+        self.bcx.ins().call(f, &args);
+        let limbs = i256::stack_load(&mut self.bcx, slots[0]);
+        self.i256_value(limbs)
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 struct Symbols(Arc<RwLock<HashMap<String, usize>>>);
 
@@ -908,6 +1356,7 @@ impl ModuleWrapper {
         }
     }
 
+    #[allow(clippy::result_large_err)]
     fn finalize_definitions(&mut self) -> Result<(), ModuleError> {
         match self {
             Self::Jit(module) => module.finalize_definitions(),
@@ -923,6 +1372,38 @@ impl ModuleWrapper {
     }
 }
 
+fn convert_int_type(bits: u32) -> Type {
+    match bits {
+        // Comparisons produce `i8`s.
+        1 => types::I8,
+        160 => i256::I160,
+        256 => i256::I256,
+        _ => bits
+            .try_into()
+            .ok()
+            .and_then(Type::int)
+            .unwrap_or_else(|| unimplemented!("type: i{bits}")),
+    }
+}
+
+fn convert_array_type(ty: Type, size: u32) -> Type {
+    // Arrays are only used to compute addresses, for which a vector type is enough.
+    // Note that `[4 x i64]` would be indistinguishable from `i256`.
+    ty.by(size).unwrap_or_else(|| unimplemented!("type: [{size} x {ty}]"))
+}
+
+/// Builds a signature, splitting `i256`s into limbs.
+fn make_signature(module: &dyn Module, params: &[Type], ret: Option<Type>) -> Signature {
+    let mut sig = module.make_signature();
+    for &ty in params {
+        sig.params.extend((0..i256::abi_len(ty)).map(|_| AbiParam::new(i256::abi_type(ty))));
+    }
+    if let Some(ty) = ret {
+        sig.returns.extend((0..i256::abi_len(ty)).map(|_| AbiParam::new(i256::abi_type(ty))));
+    }
+    sig
+}
+
 fn convert_intcc(cond: revmc_backend::IntCC) -> IntCC {
     match cond {
         revmc_backend::IntCC::Equal => IntCC::Equal,
@@ -0,0 +1,203 @@
+use crate::{error_msg, orc};
+use inkwell::{llvm_sys::object::*, memory_buffer::MemoryBuffer};
+use revmc_backend::{eyre, Result};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{
+    ffi::{CStr, CString},
+    fmt, ptr,
+    sync::{Arc, Mutex},
+};
+
+/// An ORC JIT that can be shared between multiple [`EvmLlvmBackend`]s, possibly on different
+/// threads.
+///
+/// See [`EvmLlvmBackend::new_orc`] for more information.
+///
+/// [`EvmLlvmBackend`]: crate::EvmLlvmBackend
+/// [`EvmLlvmBackend::new_orc`]: crate::EvmLlvmBackend::new_orc
+#[derive(Clone)]
+pub struct EvmLlvmOrcJit(Arc<Inner>);
+
+struct Inner {
+    jit: orc::LLJIT,
+    /// Symbols that are defined in the main JITDylib.
+    symbols: Mutex<FxHashSet<String>>,
+    /// The sizes of the symbols of the linked objects, by unmangled name.
+    ///
+    /// Only recorded if [`JitEventListeners::perf_map`] is enabled.
+    sizes: Arc<Mutex<FxHashMap<String, usize>>>,
+    /// Records `sizes`. Must be dropped after `jit`.
+    _obj_transform: Option<Box<orc::ObjectTransform>>,
+}
+
+/// JIT event listeners to register with an [`EvmLlvmOrcJit`].
+///
+/// See [`EvmLlvmOrcJit::with_event_listeners`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct JitEventListeners {
+    /// Registers the JIT-compiled code with the GDB JIT interface, which is also supported by
+    /// LLDB.
+    pub gdb: bool,
+    /// Writes `jit-<pid>.dump` files for `perf inject --jit`.
+    ///
+    /// Requires LLVM to be built with `LLVM_USE_PERF`, otherwise it is ignored.
+    pub perf: bool,
+    /// Records the sizes of the JIT-compiled functions, which are required to write the entries
+    /// of `EvmCompiler::perf_map`.
+    ///
+    /// This inspects every object before it is linked, so it should only be enabled along with the
+    /// perf map.
+    pub perf_map: bool,
+}
+
+impl fmt::Debug for EvmLlvmOrcJit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EvmLlvmOrcJit").field("jit", &self.0.jit).finish_non_exhaustive()
+    }
+}
+
+impl EvmLlvmOrcJit {
+    /// Creates a new JIT for the host machine.
+    ///
+    /// Undefined symbols are resolved from the current process.
+    pub fn new() -> Result<Self> {
+        Self::with_event_listeners(JitEventListeners::default())
+    }
+
+    /// Creates a new JIT for the host machine that notifies the given event listeners of the
+    /// JIT-compiled code, so that it can be symbolized by debuggers and profilers.
+    ///
+    /// Undefined symbols are resolved from the current process.
+    pub fn with_event_listeners(listeners: JitEventListeners) -> Result<Self> {
+        crate::init()?;
+        let JitEventListeners { gdb, perf, perf_map } = listeners;
+        let mut builder =
+            orc::LLJIT::builder().set_target_machine_from_host().map_err(error_msg)?;
+        if gdb || perf {
+            builder = builder.set_rtdyld_object_linking_layer(gdb, perf);
+        }
+        let jit = builder.build().map_err(error_msg)?;
+        let prefix = jit.get_global_prefix();
+        let generator = orc::DefinitionGenerator::for_process(prefix).map_err(error_msg)?;
+        jit.get_main_jit_dylib().add_generator(generator);
+
+        let sizes = Arc::<Mutex<FxHashMap<String, usize>>>::default();
+        let obj_transform = perf_map.then(|| {
+            let obj_transform = Box::new(orc::ObjectTransform::new({
+                let sizes = sizes.clone();
+                move |obj| {
+                    // Missing sizes only cause perf map entries to be skipped, so this must not
+                    // fail linking.
+                    match symbol_sizes(obj, prefix as u8) {
+                        Ok(new_sizes) => sizes.lock().unwrap().extend(new_sizes),
+                        Err(e) => warn!(%e, "failed to read symbol sizes"),
+                    }
+                    Ok(())
+                }
+            }));
+            unsafe { jit.get_obj_transform_layer().set_transform(&obj_transform) };
+            obj_transform
+        });
+
+        Ok(Self(Arc::new(Inner {
+            jit,
+            symbols: Mutex::default(),
+            sizes,
+            _obj_transform: obj_transform,
+        })))
+    }
+
+    /// Returns the underlying LLJIT.
+    pub fn lljit(&self) -> &orc::LLJIT {
+        &self.0.jit
+    }
+
+    /// Returns `true` if `name` is defined in the JIT.
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.0.symbols.lock().unwrap().contains(name)
+    }
+
+    /// Reserves `name` for a function that is about to be added to the JIT.
+    pub(crate) fn reserve(&self, name: &str) -> Result<()> {
+        if !self.0.symbols.lock().unwrap().insert(name.to_string()) {
+            return Err(eyre::eyre!("symbol `{name}` is already defined in the JIT"));
+        }
+        Ok(())
+    }
+
+    /// Releases names reserved with [`reserve`](Self::reserve).
+    pub(crate) fn release<'a>(&self, names: impl IntoIterator<Item = &'a str>) {
+        let mut symbols = self.0.symbols.lock().unwrap();
+        let mut sizes = self.0.sizes.lock().unwrap();
+        for name in names {
+            symbols.remove(name);
+            sizes.remove(name);
+        }
+    }
+
+    /// Defines `name` as an absolute symbol at `address`, if it is not already defined.
+    pub(crate) fn define_absolute(&self, name: &str, address: usize) -> Result<()> {
+        let mut symbols = self.0.symbols.lock().unwrap();
+        if symbols.contains(name) {
+            return Ok(());
+        }
+        let jit = &self.0.jit;
+        let flags = orc::SymbolFlags::none().with_exported().callable();
+        let symbol = orc::EvaluatedSymbol::new(address as u64, flags);
+        let pair = orc::SymbolMapPair::new(jit.mangle_and_intern(&CString::new(name)?), symbol);
+        let mu = orc::MaterializationUnit::absolute_symbols(vec![pair]);
+        jit.get_main_jit_dylib().define(mu).map_err(|(e, _)| error_msg(e))?;
+        symbols.insert(name.to_string());
+        Ok(())
+    }
+
+    /// Adds a module to the JIT, tracked by a new resource tracker.
+    pub(crate) fn add_module(&self, tsm: orc::ThreadSafeModule) -> Result<orc::ResourceTracker> {
+        let rt = self.0.jit.get_main_jit_dylib().create_resource_tracker();
+        self.0.jit.add_module_with_rt(tsm, &rt).map_err(error_msg)?;
+        Ok(rt)
+    }
+
+    /// Looks up the address of `name`, compiling it if necessary.
+    pub(crate) fn lookup(&self, name: &str) -> Result<usize> {
+        self.0.jit.lookup_unmangled(&CString::new(name)?).map_err(error_msg)
+    }
+
+    /// Returns the size of the machine code of `name`, if it has been compiled, sizes are
+    /// [recorded](JitEventListeners::perf_map), and the object format records symbol sizes.
+    pub(crate) fn symbol_size(&self, name: &str) -> Option<usize> {
+        self.0.sizes.lock().unwrap().get(name).copied()
+    }
+}
+
+/// Returns the names, without the global `prefix`, and sizes of the sized symbols in `obj`.
+///
+/// Mach-O does not record symbol sizes, so nothing is returned for it.
+fn symbol_sizes(obj: &[u8], prefix: u8) -> Result<Vec<(String, usize)>, String> {
+    let buffer = MemoryBuffer::create_from_memory_range(obj, "obj");
+    let mut err = ptr::null_mut();
+    let binary = unsafe { LLVMCreateBinary(buffer.as_mut_ptr(), ptr::null_mut(), &mut err) };
+    if binary.is_null() {
+        return Err(unsafe { crate::llvm_string(err) }.to_string());
+    }
+
+    let mut sizes = Vec::new();
+    unsafe {
+        let it = LLVMObjectFileCopySymbolIterator(binary);
+        while LLVMObjectFileIsSymbolIteratorAtEnd(binary, it) == 0 {
+            let size = LLVMGetSymbolSize(it) as usize;
+            let name = CStr::from_ptr(LLVMGetSymbolName(it)).to_bytes();
+            let name = match name.split_first() {
+                Some((&first, rest)) if prefix != 0 && first == prefix => rest,
+                _ => name,
+            };
+            if size != 0 && !name.is_empty() {
+                sizes.push((String::from_utf8_lossy(name).into_owned(), size));
+            }
+            LLVMMoveToNextSymbol(it);
+        }
+        LLVMDisposeSymbolIterator(it);
+        LLVMDisposeBinary(binary);
+    }
+    Ok(sizes)
+}
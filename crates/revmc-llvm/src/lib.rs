@@ -8,12 +8,18 @@ extern crate tracing;
 use inkwell::{
     attributes::{Attribute, AttributeLoc},
     basic_block::BasicBlock,
+    debug_info::{
+        debug_metadata_version, AsDIScope, DICompileUnit, DIFlags, DIFlagsConstants,
+        DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder,
+    },
     execution_engine::ExecutionEngine,
+    memory_buffer::MemoryBuffer,
     module::{FlagBehavior, Module},
     passes::PassBuilderOptions,
     support::error_handling::install_fatal_error_handler,
     targets::{
-        CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple,
+        ByteOrdering, CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+        TargetTriple,
     },
     types::{
         AnyType, AnyTypeEnum, BasicType, BasicTypeEnum, FunctionType, IntType, PointerType,
@@ -26,11 +32,13 @@ use inkwell::{
     AddressSpace, IntPredicate, OptimizationLevel,
 };
 use revmc_backend::{
-    eyre, Backend, BackendTypes, Builder, Error, IntCC, Result, TailCallKind, TypeMethods, U256,
+    eyre, Backend, BackendTypes, Builder, Error, IntCC, Result, TailCallKind, TargetDataLayout,
+    TypeMethods, U256,
 };
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::{
     borrow::Cow,
+    ffi::CString,
     iter,
     path::Path,
     sync::{Once, OnceLock},
@@ -41,6 +49,9 @@ pub use inkwell::{self, context::Context};
 mod dh;
 pub mod orc;
 
+mod jit;
+pub use jit::{EvmLlvmOrcJit, JitEventListeners};
+
 mod utils;
 pub(crate) use utils::*;
 
@@ -62,8 +73,18 @@ pub struct EvmLlvmBackend<'ctx> {
     cx: &'ctx Context,
     _dh: dh::DiagnosticHandlerGuard<'ctx>,
     bcx: inkwell::builder::Builder<'ctx>,
+    /// Lazily-initialized debug info of `module`. Declared before it so that it is dropped first.
+    debug_info: Option<DebugInfo<'ctx>>,
     module: Module<'ctx>,
     exec_engine: Option<ExecutionEngine<'ctx>>,
+    /// Lazily-initialized JIT used for loading pre-compiled objects.
+    object_jit: Option<orc::LLJIT>,
+    /// Symbols that have already been defined in `object_jit`.
+    object_jit_symbols: FxHashSet<String>,
+    /// Functions loaded into `object_jit`, by name.
+    object_jit_functions: FxHashMap<String, usize>,
+    /// Set when JIT-compiling with ORC instead of `exec_engine`.
+    orc: Option<OrcState<'ctx>>,
     machine: TargetMachine,
 
     ty_void: VoidType<'ctx>,
@@ -96,12 +117,54 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
     }
 
     /// Creates a new LLVM backend for the given target.
+    ///
+    /// Objects can be emitted for targets other than the host in AOT mode. Rust target triples,
+    /// such as `riscv64gc-unknown-linux-gnu`, are converted to LLVM ones, and their default
+    /// features and ABI are used.
     #[instrument(name = "new_llvm_backend", level = "debug", skip_all)]
     pub fn new_for_target(
         cx: &'ctx Context,
         aot: bool,
         opt_level: revmc_backend::OptimizationLevel,
         target: &revmc_backend::Target,
+    ) -> Result<Self> {
+        Self::new_inner(cx, aot, opt_level, target, None)
+    }
+
+    /// Creates a new LLVM backend for the host machine that JIT-compiles functions with ORC.
+    ///
+    /// Unlike [`new`](Self::new), every call to [`jit_function`](Backend::jit_function) moves the
+    /// current module, and all the functions translated into it, to `jit` in its own
+    /// [`ThreadSafeModule`](orc::ThreadSafeModule). A new module is then created, so more
+    /// functions can be translated without freeing the ones that were already compiled.
+    ///
+    /// Each module is tracked by its own [`ResourceTracker`](orc::ResourceTracker), and can be
+    /// freed individually with [`free_function`](Backend::free_function). All of the modules are
+    /// freed when the backend is dropped.
+    ///
+    /// `jit` can be shared between multiple backends on different threads, each with its own
+    /// `cx`, to compile functions concurrently. Function names must be unique across all of them.
+    pub fn new_orc(
+        cx: &'ctx orc::ThreadSafeContext,
+        jit: EvmLlvmOrcJit,
+        opt_level: revmc_backend::OptimizationLevel,
+    ) -> Result<Self> {
+        let orc = OrcState {
+            tscx: cx,
+            jit,
+            module_counter: 0,
+            modules: FxHashMap::default(),
+            functions: FxHashMap::default(),
+        };
+        Self::new_inner(cx.context(), false, opt_level, &revmc_backend::Target::Native, Some(orc))
+    }
+
+    fn new_inner(
+        cx: &'ctx Context,
+        aot: bool,
+        opt_level: revmc_backend::OptimizationLevel,
+        target: &revmc_backend::Target,
+        orc: Option<OrcState<'ctx>>,
     ) -> Result<Self> {
         init()?;
 
@@ -122,7 +185,7 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
 
         let module = create_module(cx, &machine)?;
 
-        let exec_engine = if aot {
+        let exec_engine = if aot || orc.is_some() {
             None
         } else {
             if !target.has_jit() {
@@ -151,8 +214,13 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
             cx,
             _dh: dh::DiagnosticHandlerGuard::new(cx),
             bcx,
+            debug_info: None,
             module,
             exec_engine,
+            object_jit: None,
+            object_jit_symbols: FxHashSet::default(),
+            object_jit_functions: FxHashMap::default(),
+            orc,
             machine,
             ty_void,
             ty_i1,
@@ -181,6 +249,76 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
         self.exec_engine.as_ref().expect("missing JIT execution engine")
     }
 
+    /// Returns the debug info builder of the current module, creating it if necessary.
+    fn debug_info(&mut self) -> &DebugInfo<'ctx> {
+        if self.debug_info.is_none() {
+            self.debug_info = Some(DebugInfo::new(self.cx, &self.module, self.opt_level));
+        }
+        self.debug_info.as_ref().unwrap()
+    }
+
+    /// Finalizes the debug info of the current module, if any. Must be called before the module is
+    /// verified or compiled.
+    fn finalize_debug_info(&mut self) {
+        if let Some(debug_info) = self.debug_info.take() {
+            debug_info.builder.finalize();
+        }
+    }
+
+    fn orc_jit_function(&mut self, id: u32) -> Result<usize> {
+        if self.functions.contains_key(&id) {
+            self.orc_add_module()?;
+        }
+        let orc = self.orc.as_ref().unwrap();
+        let Some((name, ..)) = orc.functions.get(&id) else {
+            return Err(eyre::eyre!("function {id} was freed"));
+        };
+        orc.jit.lookup(name)
+    }
+
+    /// Moves the current module to the ORC JIT and replaces it with a new one.
+    fn orc_add_module(&mut self) -> Result<()> {
+        self.finalize_debug_info();
+        let new_module = create_module(self.cx, &self.machine)?;
+        let orc = self.orc.as_mut().unwrap();
+        let names = self.functions.values().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        for (i, name) in names.iter().enumerate() {
+            if let Err(e) = orc.jit.reserve(name) {
+                orc.jit.release(names[..i].iter().copied());
+                return Err(e);
+            }
+        }
+
+        let module = std::mem::replace(&mut self.module, new_module);
+        let rt = match orc.jit.add_module(orc.tscx.create_module(module)) {
+            Ok(rt) => rt,
+            Err(e) => {
+                orc.jit.release(names);
+                return Err(e);
+            }
+        };
+        let module_id = orc.module_counter;
+        orc.module_counter += 1;
+        orc.modules.insert(module_id, rt);
+        orc.functions.extend(self.functions.drain().map(|(id, (name, function))| {
+            let exported = function.get_linkage() != inkwell::module::Linkage::Private;
+            (id, (name, module_id, exported))
+        }));
+        Ok(())
+    }
+
+    fn init_object_jit(&mut self) -> Result<()> {
+        assert!(!self.aot, "requested object JIT on AOT");
+        if self.object_jit.is_none() {
+            let jit = orc::LLJIT::new().map_err(error_msg)?;
+            let generator = orc::DefinitionGenerator::for_process(jit.get_global_prefix())
+                .map_err(error_msg)?;
+            jit.get_main_jit_dylib().add_generator(generator);
+            self.object_jit = Some(jit);
+        }
+        Ok(())
+    }
+
     fn fn_type(
         &self,
         ret: Option<BasicTypeEnum<'ctx>>,
@@ -258,6 +396,11 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
         "ll"
     }
 
+    fn codegen_version(&self) -> String {
+        let (major, minor, patch) = inkwell::support::get_llvm_version();
+        format!("LLVM {major}.{minor}.{patch}")
+    }
+
     fn set_module_name(&mut self, name: &str) {
         self.module.set_name(name);
     }
@@ -282,8 +425,31 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
         self.aot
     }
 
+    fn target_triple(&self) -> String {
+        self.machine.get_triple().as_str().to_string_lossy().into_owned()
+    }
+
+    fn target_cpu(&self) -> String {
+        self.machine.get_cpu().to_string_lossy().into_owned()
+    }
+
+    fn target_features(&self) -> String {
+        self.machine.get_feature_string().to_string_lossy().into_owned()
+    }
+
+    fn target_data_layout(&self) -> TargetDataLayout {
+        let td = self.machine.get_target_data();
+        TargetDataLayout {
+            big_endian: matches!(td.get_byte_ordering(), ByteOrdering::BigEndian),
+            pointer_size: td.get_pointer_byte_size(None),
+            pointer_align: td.get_abi_alignment(&self.ty_ptr),
+            i64_align: td.get_abi_alignment(&self.ty_i64),
+        }
+    }
+
     fn function_name_is_unique(&self, name: &str) -> bool {
         self.module.get_function(name).is_none()
+            && !self.orc.as_ref().is_some_and(|orc| orc.jit.contains(name))
     }
 
     fn dump_ir(&mut self, path: &Path) -> Result<()> {
@@ -308,14 +474,24 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
             self.bcx.position_at_end(function.get_first_basic_block().unwrap());
             (id, function)
         } else {
-            let fn_type = self.fn_type(ret, params);
-            let function = self.module.add_function(name, fn_type, Some(convert_linkage(linkage)));
+            // Define functions that were previously declared, e.g. to be called before being built.
+            let function = match self.module.get_function(name) {
+                Some(function) if function.count_basic_blocks() == 0 => {
+                    function.set_linkage(convert_linkage(linkage));
+                    function
+                }
+                _ => {
+                    let fn_type = self.fn_type(ret, params);
+                    self.module.add_function(name, fn_type, Some(convert_linkage(linkage)))
+                }
+            };
             for (i, &name) in param_names.iter().enumerate() {
                 function.get_nth_param(i as u32).expect(name).set_name(name);
             }
 
             let entry = self.cx.append_basic_block(function, "entry");
             self.bcx.position_at_end(entry);
+            self.bcx.unset_current_debug_location();
 
             let id = self.function_counter;
             self.function_counter += 1;
@@ -327,10 +503,12 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
     }
 
     fn verify_module(&mut self) -> Result<()> {
+        self.finalize_debug_info();
         self.module.verify().map_err(error_msg)
     }
 
     fn optimize_module(&mut self) -> Result<()> {
+        self.finalize_debug_info();
         // From `opt --help`, `-passes`.
         let passes = match self.opt_level {
             OptimizationLevel::None => "default<O0>",
@@ -352,12 +530,77 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
     }
 
     fn jit_function(&mut self, id: Self::FuncId) -> Result<usize> {
+        if self.orc.is_some() {
+            return self.orc_jit_function(id);
+        }
         let name = self.id_to_name(id);
         let addr = self.exec_engine().get_function_address(name)?;
         Ok(addr)
     }
 
+    fn jit_function_size(&self, id: Self::FuncId) -> Option<usize> {
+        // MCJIT does not expose the sizes of the functions it compiles.
+        let orc = self.orc.as_ref()?;
+        let (name, ..) = orc.functions.get(&id)?;
+        orc.jit.symbol_size(name)
+    }
+
+    fn jit_object(
+        &mut self,
+        name: &str,
+        object: &[u8],
+        symbols: &[(&str, usize)],
+    ) -> Result<usize> {
+        if let Some(&addr) = self.object_jit_functions.get(name) {
+            return Ok(addr);
+        }
+
+        self.init_object_jit()?;
+        let jit = self.object_jit.as_ref().unwrap();
+        let new_symbols = symbols
+            .iter()
+            .filter(|(name, _)| !self.object_jit_symbols.contains(*name))
+            .map(|&(name, address)| {
+                let name = CString::new(name).unwrap();
+                let flags = orc::SymbolFlags::none().with_exported().callable();
+                let symbol = orc::EvaluatedSymbol::new(address as u64, flags);
+                orc::SymbolMapPair::new(jit.mangle_and_intern(&name), symbol)
+            })
+            .collect::<Vec<_>>();
+        if !new_symbols.is_empty() {
+            let mu = orc::MaterializationUnit::absolute_symbols(new_symbols);
+            jit.get_main_jit_dylib().define(mu).map_err(|(e, _)| error_msg(e))?;
+            self.object_jit_symbols.extend(symbols.iter().map(|(name, _)| name.to_string()));
+        }
+
+        let buffer = MemoryBuffer::create_from_memory_range_copy(object, name);
+        jit.add_object_file(buffer).map_err(error_msg)?;
+        let addr = jit.lookup(&CString::new(name)?).map_err(error_msg)?;
+        self.object_jit_functions.insert(name.to_string(), addr);
+        Ok(addr)
+    }
+
+    fn jit_detaches_module(&self) -> bool {
+        self.orc.is_some()
+    }
+
     unsafe fn free_function(&mut self, id: Self::FuncId) -> Result<()> {
+        if let Some(orc) = &mut self.orc {
+            let Some(&(_, module_id, _)) = orc.functions.get(&id) else {
+                return Err(eyre::eyre!("function {id} was not JIT-compiled"));
+            };
+            // The code is freed by module, which would also free the other functions in it.
+            let shared = orc.functions.iter().find(|&(&other_id, &(_, other_module_id, exported))| {
+                other_id != id && other_module_id == module_id && exported
+            });
+            if let Some((_, (other, ..))) = shared {
+                return Err(eyre::eyre!(
+                    "function {id} was JIT-compiled in the same module as `{other}`, \
+                     so they can only be freed together by freeing all functions"
+                ));
+            }
+            return orc.remove_module(module_id);
+        }
         let name = self.id_to_name(id);
         let function = self.exec_engine().get_function_value(name)?;
         self.exec_engine().free_fn_machine_code(function);
@@ -366,7 +609,11 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
     }
 
     unsafe fn free_all_functions(&mut self) -> Result<()> {
+        self.debug_info = None;
         self.clear_module();
+        if let Some(orc) = &mut self.orc {
+            orc.remove_all_modules()?;
+        }
         if let Some(exec_engine) = &self.exec_engine {
             exec_engine.remove_module(&self.module).map_err(|e| Error::msg(e.to_string()))?;
         }
@@ -377,11 +624,110 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
         }
         Ok(())
     }
+
+    unsafe fn free_all_objects(&mut self) -> Result<()> {
+        self.object_jit = None;
+        self.object_jit_symbols.clear();
+        self.object_jit_functions.clear();
+        Ok(())
+    }
 }
 
 impl Drop for EvmLlvmBackend<'_> {
     fn drop(&mut self) {
+        self.debug_info = None;
         self.clear_module();
+        if let Some(orc) = &mut self.orc {
+            if let Err(e) = orc.remove_all_modules() {
+                error!("failed to remove JIT modules: {e}");
+            }
+        }
+    }
+}
+
+/// The debug info of a module, which only contains line tables.
+#[derive(Debug)]
+struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    cu: DICompileUnit<'ctx>,
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+    fn new(cx: &'ctx Context, module: &Module<'ctx>, opt_level: OptimizationLevel) -> Self {
+        module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            cx.i32_type().const_int(debug_metadata_version() as u64, false),
+        );
+        module.add_basic_value_flag(
+            "Dwarf Version",
+            FlagBehavior::Warning,
+            cx.i32_type().const_int(4, false),
+        );
+        let (builder, cu) = module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            module.get_source_file_name().to_str().unwrap_or("evm"),
+            "",
+            concat!("revmc ", env!("CARGO_PKG_VERSION")),
+            opt_level != OptimizationLevel::None,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::LineTablesOnly,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+        Self { builder, cu }
+    }
+}
+
+/// State of an [`EvmLlvmBackend`] created with [`EvmLlvmBackend::new_orc`].
+struct OrcState<'ctx> {
+    tscx: &'ctx orc::ThreadSafeContext,
+    jit: EvmLlvmOrcJit,
+    /// Separate from `modules` to have always increasing IDs.
+    module_counter: u32,
+    /// Modules that were added to `jit`, by ID.
+    modules: FxHashMap<u32, orc::ResourceTracker>,
+    /// Functions that were added to `jit`, by function ID, with the ID of the module they are in
+    /// and whether they are exported.
+    functions: FxHashMap<u32, (String, u32, bool)>,
+}
+
+impl std::fmt::Debug for OrcState<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OrcState")
+            .field("jit", &self.jit)
+            .field("functions", &self.functions)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OrcState<'_> {
+    /// Frees the code of the given module and all the functions in it, including exported ones
+    /// that were not requested to be freed.
+    fn remove_module(&mut self, module_id: u32) -> Result<()> {
+        let Some(rt) = self.modules.remove(&module_id) else { return Ok(()) };
+        rt.remove().map_err(error_msg)?;
+        let jit = &self.jit;
+        self.functions.retain(|_, (name, id, _)| {
+            let keep = *id != module_id;
+            if !keep {
+                jit.release([name.as_str()]);
+            }
+            keep
+        });
+        Ok(())
+    }
+
+    fn remove_all_modules(&mut self) -> Result<()> {
+        let mut ids = self.modules.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.into_iter().try_for_each(|id| self.remove_module(id))
     }
 }
 
@@ -425,16 +771,50 @@ impl TargetInfo {
                 })))
             }
             revmc_backend::Target::Triple { triple, cpu, features } => {
-                let triple = TargetTriple::create(triple);
+                let (triple, default_features) = normalize_triple(triple);
+                let triple = TargetTriple::create(&triple);
                 let target = Target::from_triple(&triple).map_err(error_msg)?;
                 let cpu = cpu.as_ref().cloned().unwrap_or_default();
-                let features = features.as_ref().cloned().unwrap_or_default();
+                let features = features
+                    .as_ref()
+                    .cloned()
+                    .unwrap_or_else(|| default_features.unwrap_or_default().to_string());
                 Ok(Cow::Owned(Self { target, triple, cpu, features }))
             }
         }
     }
 }
 
+/// Converts a Rust target triple to an LLVM one, returning it along with the default features that
+/// Rust enables for it.
+fn normalize_triple(triple: &str) -> (Cow<'_, str>, Option<&'static str>) {
+    // The `g` and `c` extensions are only part of the Rust triple.
+    if let Some(rest) = triple.strip_prefix("riscv64gc-") {
+        return (format!("riscv64-{rest}").into(), Some("+m,+a,+f,+d,+c"));
+    }
+    (triple.into(), None)
+}
+
+/// Returns the RISC-V ABI implied by the target machine's features, if it is a RISC-V target.
+///
+/// The ABI is selected with a module flag, and must match the one of the objects we are linked
+/// with.
+fn riscv_abi(machine: &TargetMachine) -> Option<&'static str> {
+    let triple = machine.get_triple();
+    if !triple.as_str().to_bytes().starts_with(b"riscv64") {
+        return None;
+    }
+    let features = machine.get_feature_string().to_string_lossy();
+    let has = |feature: &str| features.split(',').any(|f| f == feature);
+    Some(if has("+d") {
+        "lp64d"
+    } else if has("+f") {
+        "lp64f"
+    } else {
+        "lp64"
+    })
+}
+
 /// The LLVM-based EVM bytecode compiler function builder.
 #[derive(Debug)]
 #[must_use]
@@ -639,6 +1019,45 @@ impl<'a, 'ctx> Builder for EvmLlvmBuilder<'a, 'ctx> {
         ins.set_metadata(metadata, self.cx.get_kind_id("annotation")).unwrap();
     }
 
+    fn set_debug_file(&mut self, file: &Path) {
+        let function = self.function;
+        let name = function.get_name().to_string_lossy().into_owned();
+        let is_optimized = self.opt_level != OptimizationLevel::None;
+        let directory = file.parent().map(Path::to_string_lossy).unwrap_or_default();
+        let filename = file.file_name().map(|s| s.to_string_lossy()).unwrap_or_default();
+        let debug_info = self.debug_info();
+        let builder = &debug_info.builder;
+        let file = builder.create_file(&filename, &directory);
+        let ty = builder.create_subroutine_type(file, None, &[], DIFlags::ZERO);
+        let subprogram = builder.create_function(
+            debug_info.cu.as_debug_info_scope(),
+            &name,
+            None,
+            file,
+            0,
+            ty,
+            false,
+            true,
+            0,
+            DIFlags::ZERO,
+            is_optimized,
+        );
+        function.set_subprogram(subprogram);
+    }
+
+    fn set_debug_location(&mut self, line: u32) {
+        let Some(subprogram) = self.function.get_subprogram() else { return };
+        let Some(debug_info) = &self.backend.debug_info else { return };
+        let location = debug_info.builder.create_debug_location(
+            self.backend.cx,
+            line,
+            0,
+            subprogram.as_debug_info_scope(),
+            None,
+        );
+        self.backend.bcx.set_current_debug_location(location);
+    }
+
     fn fn_param(&mut self, index: usize) -> Self::Value {
         self.function.get_nth_param(index as _).unwrap()
     }
@@ -704,6 +1123,17 @@ impl<'a, 'ctx> Builder for EvmLlvmBuilder<'a, 'ctx> {
         self.bcx.build_store(ptr.into_pointer_value(), value).unwrap();
     }
 
+    fn load_unaligned(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value {
+        let value = self.load(ty, ptr, name);
+        value.as_instruction_value().unwrap().set_alignment(1).unwrap();
+        value
+    }
+
+    fn store_unaligned(&mut self, value: Self::Value, ptr: Self::Value) {
+        let inst = self.bcx.build_store(ptr.into_pointer_value(), value).unwrap();
+        inst.set_alignment(1).unwrap();
+    }
+
     fn nop(&mut self) {
         // LLVM doesn't have a NOP instruction.
     }
@@ -1054,6 +1484,9 @@ impl<'a, 'ctx> Builder for EvmLlvmBuilder<'a, 'ctx> {
         }
 
         let before = self.current_block();
+        // Helper functions do not have debug info.
+        let debug_location = self.bcx.get_current_debug_location();
+        self.bcx.unset_current_debug_location();
 
         let func_ty = self.fn_type(ret, params);
         let function = self.module.add_function(name, func_ty, Some(convert_linkage(linkage)));
@@ -1065,6 +1498,9 @@ impl<'a, 'ctx> Builder for EvmLlvmBuilder<'a, 'ctx> {
         if let Some(before) = before {
             self.bcx.position_at_end(before);
         }
+        if let Some(debug_location) = debug_location {
+            self.bcx.set_current_debug_location(debug_location);
+        }
 
         self.function = prev_function;
 
@@ -1075,6 +1511,10 @@ impl<'a, 'ctx> Builder for EvmLlvmBuilder<'a, 'ctx> {
         self.module.get_function(name)
     }
 
+    fn func_addr(&mut self, function: Self::Function) -> Self::Value {
+        function.as_global_value().as_pointer_value().into()
+    }
+
     fn get_printf_function(&mut self) -> Self::Function {
         let name = "printf";
         if let Some(function) = self.module.get_function(name) {
@@ -1097,6 +1537,11 @@ impl<'a, 'ctx> Builder for EvmLlvmBuilder<'a, 'ctx> {
         let function = self.module.add_function(name, func_ty, Some(convert_linkage(linkage)));
         if let (Some(address), Some(exec_engine)) = (address, &self.exec_engine) {
             exec_engine.add_global_mapping(&function, address);
+        } else if let (Some(address), Some(orc)) = (address, &self.orc) {
+            // Lookups of functions that use this symbol will fail if it cannot be defined.
+            if let Err(e) = orc.jit.define_absolute(name, address) {
+                error!("failed to define symbol `{name}`: {e}");
+            }
         }
         function
     }
@@ -1174,6 +1619,9 @@ fn create_module<'ctx>(cx: &'ctx Context, machine: &TargetMachine) -> Result<Mod
         FlagBehavior::Warning,
         cx.i32_type().const_int(1, false),
     );
+    if let Some(abi) = riscv_abi(machine) {
+        module.add_metadata_flag("target-abi", FlagBehavior::Error, cx.metadata_string(abi));
+    }
     Ok(module)
 }
 
@@ -15,12 +15,15 @@
 
 use crate::llvm_string;
 use inkwell::{
-    context::ContextRef,
+    context::{Context, ContextRef},
     llvm_sys::{
+        core::{LLVMGetBufferSize, LLVMGetBufferStart},
         error::*,
-        orc2::{lljit::*, *},
+        execution_engine::{LLVMCreateGDBRegistrationListener, LLVMCreatePerfJITEventListener},
+        orc2::{ee::*, lljit::*, *},
         prelude::*,
     },
+    memory_buffer::MemoryBuffer,
     module::Module,
     support::LLVMString,
     targets::TargetMachine,
@@ -41,6 +44,8 @@ use std::{
 /// See [the ORCv2 docs](https://releases.llvm.org/17.0.1/docs/ORCv2.html).
 pub struct ThreadSafeContext {
     ctx: LLVMOrcThreadSafeContextRef,
+    /// The wrapped LLVMContext, which is owned by `ctx`.
+    cx: mem::ManuallyDrop<Context>,
 }
 
 impl ThreadSafeContext {
@@ -51,7 +56,8 @@ impl ThreadSafeContext {
 
     /// Wraps a raw pointer.
     pub unsafe fn from_inner(ctx: LLVMOrcThreadSafeContextRef) -> Self {
-        Self { ctx }
+        let cx = mem::ManuallyDrop::new(Context::new(LLVMOrcThreadSafeContextGetContext(ctx)));
+        Self { ctx, cx }
     }
 
     /// Unwraps the raw pointer.
@@ -66,6 +72,14 @@ impl ThreadSafeContext {
         unsafe { std::mem::transmute(ptr) }
     }
 
+    /// Returns the wrapped LLVMContext.
+    ///
+    /// Unlike [`get_context`](Self::get_context), the returned context lives as long as `self`, so
+    /// it can be used to create an [`EvmLlvmBackend`](crate::EvmLlvmBackend).
+    pub fn context(&self) -> &Context {
+        &self.cx
+    }
+
     /// Create a ThreadSafeModule wrapper around the given LLVM module.
     pub fn create_module<'ctx>(&'ctx self, module: Module<'ctx>) -> ThreadSafeModule {
         ThreadSafeModule::create_in_context(module, self)
@@ -769,6 +783,10 @@ impl ResourceTracker {
     }
 }
 
+// SAFETY: Resource trackers are reference-counted and can be used from any thread.
+unsafe impl Send for ResourceTracker {}
+unsafe impl Sync for ResourceTracker {}
+
 impl Drop for ResourceTracker {
     fn drop(&mut self) {
         unsafe { LLVMOrcReleaseResourceTracker(self.as_inner()) };
@@ -992,6 +1010,23 @@ impl DefinitionGenerator {
         Self::from_inner(LLVMOrcCreateCustomCAPIDefinitionGenerator(f, ctx, dispose))
     }
 
+    /// Creates a DefinitionGenerator that resolves symbols from the current process.
+    ///
+    /// `global_prefix` is the global prefix character of the JIT's data layout, see
+    /// [`LLJIT::get_global_prefix`].
+    pub fn for_process(global_prefix: c_char) -> Result<Self, LLVMString> {
+        let mut res = MaybeUninit::uninit();
+        cvt(unsafe {
+            LLVMOrcCreateDynamicLibrarySearchGeneratorForProcess(
+                res.as_mut_ptr(),
+                global_prefix,
+                None,
+                ptr::null_mut(),
+            )
+        })?;
+        Ok(unsafe { Self::from_inner(res.assume_init()) })
+    }
+
     /// Wraps a raw pointer.
     pub unsafe fn from_inner(dg: LLVMOrcDefinitionGeneratorRef) -> Self {
         Self { dg }
@@ -1173,24 +1208,39 @@ impl LLJITBuilder {
         self
     }
 
-    /*
-    pub fn set_object_linking_layer_creator(self, mut f: &mut dyn FnMut(*const (), &CStr)) -> Self {
+    /// Uses an `RTDyldObjectLinkingLayer` with a section memory manager as the object linking
+    /// layer, and registers the given JIT event listeners with it.
+    ///
+    /// `gdb` registers the code with the GDB JIT interface, and `perf` writes `jit-<pid>.dump`
+    /// files for `perf inject --jit`. Listeners that are not available in the linked LLVM, such as
+    /// `perf` when LLVM is built without `LLVM_USE_PERF`, are ignored.
+    pub fn set_rtdyld_object_linking_layer(mut self, gdb: bool, perf: bool) -> Self {
         extern "C" fn shim(
             ctx: *mut c_void,
             es: LLVMOrcExecutionSessionRef,
-            triple: *const c_char,
+            _triple: *const c_char,
         ) -> LLVMOrcObjectLayerRef {
-            let f = ctx.cast::<&mut dyn FnMut(*const (), &CStr)>();
-            let name = unsafe { CStr::from_ptr(triple) };
-            unsafe { (*f)(es.cast(), name) };
-            ptr::null_mut()
+            let flags = ctx as usize;
+            let layer =
+                unsafe { LLVMOrcCreateRTDyldObjectLinkingLayerWithSectionMemoryManager(es) };
+            let mut listeners = [ptr::null_mut(); 2];
+            if flags & 1 != 0 {
+                listeners[0] = unsafe { LLVMCreateGDBRegistrationListener() };
+            }
+            if flags & 2 != 0 {
+                listeners[1] = unsafe { LLVMCreatePerfJITEventListener() };
+            }
+            for listener in listeners.into_iter().filter(|l| !l.is_null()) {
+                unsafe { LLVMOrcRTDyldObjectLinkingLayerRegisterJITEventListener(layer, listener) };
+            }
+            layer
         }
 
-        let ctx = &mut f as *mut _ as *mut c_void;
+        // The listeners are process-wide singletons, so we only need to pass the flags.
+        let ctx = ((gdb as usize) | ((perf as usize) << 1)) as *mut c_void;
         unsafe { LLVMOrcLLJITBuilderSetObjectLinkingLayerCreator(self.as_inner_init(), shim, ctx) };
         self
     }
-    */
 
     /// Builds the JIT.
     pub fn build(self) -> Result<LLJIT, LLVMString> {
@@ -1282,11 +1332,40 @@ impl LLJIT {
     pub fn add_module_with_rt(
         &self,
         tsm: ThreadSafeModule,
-        jd: ResourceTracker,
+        rt: &ResourceTracker,
     ) -> Result<(), LLVMString> {
         let tsm = mem::ManuallyDrop::new(tsm);
         cvt(unsafe {
-            LLVMOrcLLJITAddLLVMIRModuleWithRT(self.as_inner(), jd.as_inner(), tsm.as_inner())
+            LLVMOrcLLJITAddLLVMIRModuleWithRT(self.as_inner(), rt.as_inner(), tsm.as_inner())
+        })
+    }
+
+    /// Add an object file to the main JITDylib.
+    pub fn add_object_file(&self, obj: MemoryBuffer) -> Result<(), LLVMString> {
+        let jd = self.get_main_jit_dylib();
+        self.add_object_file_with_dylib(obj, jd)
+    }
+
+    /// Add an object file to the given JITDylib.
+    pub fn add_object_file_with_dylib(
+        &self,
+        obj: MemoryBuffer,
+        jd: JITDylibRef,
+    ) -> Result<(), LLVMString> {
+        // This operation transfers ownership of the buffer to the LLJIT instance.
+        let obj = mem::ManuallyDrop::new(obj);
+        cvt(unsafe { LLVMOrcLLJITAddObjectFile(self.as_inner(), jd.as_inner(), obj.as_mut_ptr()) })
+    }
+
+    /// Add an object file to the given ResourceTracker's JITDylib.
+    pub fn add_object_file_with_rt(
+        &self,
+        obj: MemoryBuffer,
+        rt: &ResourceTracker,
+    ) -> Result<(), LLVMString> {
+        let obj = mem::ManuallyDrop::new(obj);
+        cvt(unsafe {
+            LLVMOrcLLJITAddObjectFileWithRT(self.as_inner(), rt.as_inner(), obj.as_mut_ptr())
         })
     }
 
@@ -1332,6 +1411,13 @@ impl LLJIT {
         unsafe { IRTransformLayerRef::from_inner(LLVMOrcLLJITGetIRTransformLayer(self.as_inner())) }
     }
 
+    /// Returns a non-owning reference to the LLJIT instance's object transform layer.
+    pub fn get_obj_transform_layer(&self) -> ObjectTransformLayerRef {
+        unsafe {
+            ObjectTransformLayerRef::from_inner(LLVMOrcLLJITGetObjTransformLayer(self.as_inner()))
+        }
+    }
+
     // get_*_layer...
 
     // Experimental interface for `libLLVMOrcDebugging.a`.
@@ -1344,6 +1430,16 @@ impl LLJIT {
     */
 }
 
+// SAFETY: LLJIT is thread-safe; modules can be added and symbols looked up from multiple threads.
+unsafe impl Send for LLJIT {}
+unsafe impl Sync for LLJIT {}
+
+impl fmt::Debug for LLJIT {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LLJIT").field("triple", &self.get_triple_string()).finish_non_exhaustive()
+    }
+}
+
 impl Drop for LLJIT {
     fn drop(&mut self) {
         if let Err(e) = cvt(unsafe { LLVMOrcDisposeLLJIT(self.jit) }) {
@@ -1352,23 +1448,7 @@ impl Drop for LLJIT {
     }
 }
 
-/*
-pub struct ObjectLayerRef {
-    ptr: LLVMOrcObjectLayerRef,
-}
-
-impl ObjectLayerRef {
-    /// Wraps a raw pointer.
-    pub unsafe fn from_inner(ptr: LLVMOrcObjectLayerRef) -> Self {
-        Self { ptr }
-    }
-
-    /// Unwraps the raw pointer.
-    pub fn as_inner(&self) -> LLVMOrcObjectLayerRef {
-        self.ptr
-    }
-}
-
+/// A reference to an object transform layer.
 pub struct ObjectTransformLayerRef {
     ptr: LLVMOrcObjectTransformLayerRef,
 }
@@ -1383,8 +1463,44 @@ impl ObjectTransformLayerRef {
     pub fn as_inner(&self) -> LLVMOrcObjectTransformLayerRef {
         self.ptr
     }
+
+    /// Set the transform function of this transform layer.
+    ///
+    /// The function is called with the contents of every object before it is linked.
+    ///
+    /// # Safety
+    ///
+    /// `f` must outlive the layer.
+    pub unsafe fn set_transform(&self, f: &ObjectTransform) {
+        extern "C" fn shim(ctx: *mut c_void, obj: *mut LLVMMemoryBufferRef) -> LLVMErrorRef {
+            let f = ctx.cast_const().cast::<ObjectTransform>();
+            let obj = unsafe { *obj };
+            let bytes = unsafe {
+                std::slice::from_raw_parts(
+                    LLVMGetBufferStart(obj).cast::<u8>(),
+                    LLVMGetBufferSize(obj),
+                )
+            };
+            let res = std::panic::catch_unwind(AssertUnwindSafe(|| unsafe { ((*f).0)(bytes) }));
+            cvt_cb_res(res)
+        }
+
+        let ctx = f as *const ObjectTransform as *mut c_void;
+        LLVMOrcObjectTransformLayerSetTransform(self.as_inner(), shim, ctx);
+    }
+}
+
+/// A function that inspects objects before they are linked.
+///
+/// See [`ObjectTransformLayerRef::set_transform`].
+pub struct ObjectTransform(Box<dyn Fn(&[u8]) -> Result<(), String> + Send + Sync>);
+
+impl ObjectTransform {
+    /// Creates a new object transform function.
+    pub fn new(f: impl Fn(&[u8]) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        Self(Box::new(f))
+    }
 }
-*/
 
 /// A reference to an IR transform layer.
 pub struct IRTransformLayerRef {
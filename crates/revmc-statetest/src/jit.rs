@@ -0,0 +1,94 @@
+//! Routing of all revm frames to JIT-compiled functions.
+
+use revm::{
+    handler::register::EvmHandler,
+    primitives::{keccak256, EVMError, SpecId, B256},
+    Database,
+};
+use revmc::{
+    llvm::{orc::ThreadSafeContext, EvmLlvmOrcJit},
+    EvmCompiler, EvmCompilerFn, EvmLlvmBackend, OptimizationLevel, Result,
+};
+use rustc_hash::FxHashMap;
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+/// Compiles every bytecode the first time it is executed.
+///
+/// Functions are keyed by code hash and spec ID, and kept until [`clear`](Self::clear) is called.
+pub struct JitCompiler {
+    functions: RefCell<FxHashMap<(B256, SpecId), EvmCompilerFn>>,
+    /// Borrows `_cx`, so it must be declared, and therefore dropped, before it.
+    compiler: RefCell<EvmCompiler<EvmLlvmBackend<'static>>>,
+    _cx: Box<ThreadSafeContext>,
+}
+
+impl JitCompiler {
+    /// Creates a new compiler with the given optimization level.
+    pub fn new(opt_level: OptimizationLevel) -> Result<Self> {
+        // The compiler is stored in the external context of the EVM, which must be `'static`.
+        let cx = Box::new(ThreadSafeContext::new());
+        // SAFETY: The context is boxed, so it does not move, and it outlives the compiler, which is
+        // dropped first.
+        let cx_ref = unsafe { &*std::ptr::from_ref::<ThreadSafeContext>(&cx) };
+        let backend = EvmLlvmBackend::new_orc(cx_ref, EvmLlvmOrcJit::new()?, opt_level)?;
+        let compiler = EvmCompiler::new(backend);
+        Ok(Self { functions: RefCell::default(), compiler: RefCell::new(compiler), _cx: cx })
+    }
+
+    /// Returns the number of compiled functions.
+    pub fn len(&self) -> usize {
+        self.functions.borrow().len()
+    }
+
+    /// Returns `true` if no functions have been compiled.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the function for the given bytecode, compiling it if necessary.
+    fn get_or_compile(
+        &self,
+        code_hash: B256,
+        bytecode: &[u8],
+        spec_id: SpecId,
+    ) -> Result<EvmCompilerFn> {
+        let key = (code_hash, spec_id);
+        if let Some(&f) = self.functions.borrow().get(&key) {
+            return Ok(f);
+        }
+        let name = format!("{code_hash}_{spec_id:?}");
+        let f = unsafe { self.compiler.borrow_mut().jit(&name, bytecode, spec_id) }?;
+        trace!(%code_hash, ?spec_id, "compiled");
+        self.functions.borrow_mut().insert(key, f);
+        Ok(f)
+    }
+
+    /// Frees all the compiled functions.
+    ///
+    /// # Safety
+    ///
+    /// The functions must not be executing, which is the case when no transaction is in progress.
+    pub unsafe fn clear(&self) -> Result<()> {
+        self.functions.borrow_mut().clear();
+        Ok(unsafe { self.compiler.borrow_mut().clear() }?)
+    }
+}
+
+/// Registers an `execute_frame` handler that executes every frame with the function compiled by the
+/// [`JitCompiler`] of the external context, never falling back to the interpreter.
+///
+/// Compilation errors are returned as [`EVMError::Custom`].
+// The `+ 'static` bound is only necessary because of an internal cfg feature.
+pub fn register_handler<DB: Database + 'static>(handler: &mut EvmHandler<'_, Rc<JitCompiler>, DB>) {
+    handler.execution.execute_frame = Arc::new(|frame, memory, _tables, context| {
+        let interpreter = frame.interpreter_mut();
+        let bytecode = interpreter.contract.bytecode.original_byte_slice();
+        let code_hash = interpreter.contract.hash.unwrap_or_else(|| keccak256(bytecode));
+        let spec_id = context.evm.spec_id();
+        let f = context
+            .external
+            .get_or_compile(code_hash, bytecode, spec_id)
+            .map_err(|e| EVMError::Custom(format!("failed to compile {code_hash}: {e:#}")))?;
+        Ok(unsafe { f.call_with_interpreter_and_memory(interpreter, memory, context) })
+    });
+}
@@ -0,0 +1,20 @@
+//! Ethereum state test runner for compiled EVM bytecode.
+
+#![allow(missing_docs)]
+#![cfg_attr(not(test), warn(unused_extern_crates))]
+
+#[macro_use]
+extern crate tracing;
+
+// For features.
+use alloy_primitives as _;
+
+mod jit;
+pub use jit::{register_handler, JitCompiler};
+
+mod merkle_trie;
+
+pub mod models;
+
+mod runner;
+pub use runner::{find_all_json_tests, run, TestFailure, TestFailureKind, TestReport};
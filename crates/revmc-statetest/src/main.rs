@@ -0,0 +1,51 @@
+#![allow(missing_docs)]
+
+use clap::Parser;
+use color_eyre::{eyre::ensure, Result};
+use revmc::OptimizationLevel;
+use revmc_statetest::{find_all_json_tests, run, JitCompiler};
+use std::{path::PathBuf, rc::Rc};
+
+/// Runs Ethereum state tests with all frames executed by JIT-compiled functions.
+#[derive(Parser)]
+struct Cli {
+    /// Paths to `GeneralStateTests` JSON fixture files or directories.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    #[arg(short = 'O', long, default_value = "1")]
+    opt_level: OptimizationLevel,
+}
+
+fn main() -> Result<()> {
+    if std::env::var_os("RUST_BACKTRACE").is_none() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+    }
+    let _ = color_eyre::install();
+    let _ = init_tracing_subscriber();
+
+    let cli = Cli::parse();
+
+    let files = find_all_json_tests(&cli.paths);
+    ensure!(!files.is_empty(), "no JSON fixtures found");
+    eprintln!("Running {} test files", files.len());
+
+    let jit = Rc::new(JitCompiler::new(cli.opt_level)?);
+    let report = run(&files, &jit)?;
+    for failure in &report.failures {
+        eprintln!("FAIL {failure}");
+    }
+    eprintln!("\n{report}");
+    if !report.is_success() {
+        eprintln!("{} tests failed", report.failures.len());
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn init_tracing_subscriber() -> Result<(), tracing_subscriber::util::TryInitError> {
+    use tracing_subscriber::prelude::*;
+    tracing_subscriber::Registry::default()
+        .with(tracing_subscriber::EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer())
+        .try_init()
+}
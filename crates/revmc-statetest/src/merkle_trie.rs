@@ -0,0 +1,77 @@
+//! State root and logs hash computation.
+
+use alloy_rlp::RlpEncodable;
+use hash_db::Hasher;
+use plain_hasher::PlainHasher;
+use revm::{
+    db::PlainAccount,
+    primitives::{keccak256, Address, Log, B256, U256},
+};
+use triehash::sec_trie_root;
+
+/// Returns the keccak256 hash of the RLP-encoded logs.
+pub(crate) fn log_rlp_hash(logs: &[Log]) -> B256 {
+    let mut out = Vec::with_capacity(alloy_rlp::list_length(logs));
+    alloy_rlp::encode_list(logs, &mut out);
+    keccak256(&out)
+}
+
+/// Returns the state root of the given accounts.
+pub(crate) fn state_merkle_trie_root<'a>(
+    accounts: impl IntoIterator<Item = (Address, &'a PlainAccount)>,
+) -> B256 {
+    trie_root(
+        accounts
+            .into_iter()
+            .map(|(address, account)| (address, alloy_rlp::encode(TrieAccount::new(account)))),
+    )
+}
+
+/// An account as it is encoded in the state trie.
+#[derive(RlpEncodable)]
+struct TrieAccount {
+    nonce: u64,
+    balance: U256,
+    storage_root: B256,
+    code_hash: B256,
+}
+
+impl TrieAccount {
+    fn new(account: &PlainAccount) -> Self {
+        let storage = account
+            .storage
+            .iter()
+            .filter(|(_, value)| !value.is_zero())
+            .map(|(slot, value)| (slot.to_be_bytes::<32>(), alloy_rlp::encode(value)));
+        Self {
+            nonce: account.info.nonce,
+            balance: account.info.balance,
+            storage_root: trie_root(storage),
+            code_hash: account.info.code_hash,
+        }
+    }
+}
+
+fn trie_root<I, K, V>(input: I) -> B256
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    sec_trie_root::<KeccakHasher, _, _, _>(input)
+}
+
+/// [`Hasher`] implementation for keccak256.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct KeccakHasher;
+
+impl Hasher for KeccakHasher {
+    type Out = B256;
+    type StdHasher = PlainHasher;
+    const LENGTH: usize = 32;
+
+    #[inline]
+    fn hash(x: &[u8]) -> Self::Out {
+        keccak256(x)
+    }
+}
@@ -0,0 +1,176 @@
+//! `GeneralStateTests` JSON fixture models.
+
+use revm::primitives::{Address, Bytes, SpecId, B256, U256};
+use serde::{de, Deserialize, Deserializer};
+use std::collections::{BTreeMap, HashMap};
+
+/// A fixture file: a map of test names to test units.
+pub type TestSuite = BTreeMap<String, TestUnit>;
+
+/// A single state test, which may contain multiple transactions for multiple forks.
+#[derive(Debug, Deserialize)]
+pub struct TestUnit {
+    /// The block environment.
+    pub env: Env,
+    /// The accounts before the transaction.
+    pub pre: HashMap<Address, AccountInfo>,
+    /// The expected results for each fork.
+    pub post: BTreeMap<SpecName, Vec<Test>>,
+    /// The transaction parts, which are combined using [`Test::indexes`].
+    pub transaction: TransactionParts,
+}
+
+/// An expected result of a transaction.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Test {
+    /// The exception that the transaction is expected to fail validation with, if any.
+    pub expect_exception: Option<String>,
+    /// The indexes of the transaction parts.
+    pub indexes: TxPartIndices,
+    /// The expected post-state root.
+    pub hash: B256,
+    /// The expected hash of the RLP-encoded logs.
+    pub logs: B256,
+}
+
+/// The indexes into [`TransactionParts`].
+#[derive(Debug, Deserialize)]
+pub struct TxPartIndices {
+    /// The index into [`TransactionParts::data`] and [`TransactionParts::access_lists`].
+    pub data: usize,
+    /// The index into [`TransactionParts::gas_limit`].
+    pub gas: usize,
+    /// The index into [`TransactionParts::value`].
+    pub value: usize,
+}
+
+/// An account in the pre-state.
+#[derive(Debug, Deserialize)]
+pub struct AccountInfo {
+    /// The balance.
+    pub balance: U256,
+    /// The bytecode.
+    pub code: Bytes,
+    /// The nonce.
+    #[serde(deserialize_with = "deserialize_str_as_u64")]
+    pub nonce: u64,
+    /// The storage.
+    pub storage: HashMap<U256, U256>,
+}
+
+/// The block environment.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Env {
+    pub current_coinbase: Address,
+    pub current_difficulty: U256,
+    pub current_gas_limit: U256,
+    pub current_number: U256,
+    pub current_timestamp: U256,
+    pub current_base_fee: Option<U256>,
+    pub current_random: Option<B256>,
+    pub current_excess_blob_gas: Option<U256>,
+    pub parent_blob_gas_used: Option<U256>,
+    pub parent_excess_blob_gas: Option<U256>,
+}
+
+/// The parts that the transactions of a [`TestUnit`] are made of.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionParts {
+    pub data: Vec<Bytes>,
+    pub gas_limit: Vec<U256>,
+    pub gas_price: Option<U256>,
+    pub nonce: U256,
+    pub secret_key: B256,
+    /// The sender. Recovered from [`secret_key`](Self::secret_key) if not set.
+    pub sender: Option<Address>,
+    /// The recipient, or `None` for contract creation.
+    #[serde(deserialize_with = "deserialize_maybe_empty")]
+    pub to: Option<Address>,
+    pub value: Vec<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    #[serde(default)]
+    pub access_lists: Vec<Option<Vec<AccessListItem>>>,
+    #[serde(default)]
+    pub blob_versioned_hashes: Vec<B256>,
+    pub max_fee_per_blob_gas: Option<U256>,
+}
+
+/// An EIP-2930 access list item.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<B256>,
+}
+
+/// The name of a fork in the fixtures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum SpecName {
+    Frontier,
+    Homestead,
+    EIP150,
+    EIP158,
+    Byzantium,
+    Constantinople,
+    ConstantinopleFix,
+    Istanbul,
+    Berlin,
+    London,
+    #[serde(alias = "Merge")]
+    Paris,
+    Shanghai,
+    Cancun,
+    Prague,
+    PragueEOF,
+    /// Transition forks and forks that are not supported by revm.
+    #[serde(other)]
+    Unknown,
+}
+
+impl SpecName {
+    /// Returns the spec ID of the fork, or `None` if the fork is not supported.
+    pub fn to_spec_id(self) -> Option<SpecId> {
+        Some(match self {
+            Self::Frontier => SpecId::FRONTIER,
+            Self::Homestead => SpecId::HOMESTEAD,
+            Self::EIP150 => SpecId::TANGERINE,
+            Self::EIP158 => SpecId::SPURIOUS_DRAGON,
+            Self::Byzantium => SpecId::BYZANTIUM,
+            Self::ConstantinopleFix => SpecId::PETERSBURG,
+            Self::Istanbul => SpecId::ISTANBUL,
+            Self::Berlin => SpecId::BERLIN,
+            Self::London => SpecId::LONDON,
+            Self::Paris => SpecId::MERGE,
+            Self::Shanghai => SpecId::SHANGHAI,
+            Self::Cancun => SpecId::CANCUN,
+            Self::Prague => SpecId::PRAGUE,
+            Self::PragueEOF => SpecId::PRAGUE_EOF,
+            // `Constantinople` was never activated on mainnet and was replaced by
+            // `ConstantinopleFix`.
+            Self::Constantinople | Self::Unknown => return None,
+        })
+    }
+}
+
+fn deserialize_str_as_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    let r = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    r.map_err(de::Error::custom)
+}
+
+fn deserialize_maybe_empty<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Address>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    if s.is_empty() {
+        return Ok(None);
+    }
+    s.parse().map(Some).map_err(de::Error::custom)
+}
@@ -0,0 +1,441 @@
+use crate::{
+    jit::{register_handler, JitCompiler},
+    merkle_trie::{log_rlp_hash, state_merkle_trie_root},
+    models::{SpecName, Test, TestSuite, TestUnit},
+};
+use revm::{
+    db::{CacheState, State},
+    interpreter::analysis::to_analysed,
+    primitives::{
+        calc_excess_blob_gas, keccak256, AccessListItem, Address, Bytecode, EVMError, Env,
+        ExecutionResult, SpecId, TransactTo, B256,
+    },
+    Evm,
+};
+use revmc::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use walkdir::{DirEntry, WalkDir};
+
+/// Returns all the JSON fixture files in the given paths.
+pub fn find_all_json_tests(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = paths
+        .iter()
+        .flat_map(WalkDir::new)
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(DirEntry::into_path)
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+/// Returns `true` if the given fixture file should be skipped.
+fn skip_test(path: &Path) -> bool {
+    let name = path.file_name().unwrap().to_str().unwrap();
+    matches!(
+        name,
+        // Contains values that do not fit in the fixture models.
+        "ValueOverflow.json" | "ValueOverflowParis.json"
+        // Take too long.
+        | "loopExp.json"
+        | "loopMul.json"
+        | "Call50000_sha256.json"
+        | "static_Call50000_sha256.json"
+        | "CALLBlake2f_MaxRounds.json"
+    )
+}
+
+/// A failed test.
+#[derive(Debug)]
+pub struct TestFailure {
+    /// The fixture file.
+    pub path: PathBuf,
+    /// The name of the test unit.
+    pub name: String,
+    /// The fork.
+    pub spec_name: SpecName,
+    /// The index of the expected result in the fork's list.
+    pub index: usize,
+    /// The reason of the failure.
+    pub kind: TestFailureKind,
+}
+
+impl fmt::Display for TestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { path, name, spec_name, index, kind } = self;
+        write!(f, "{}: {name} [{spec_name:?}/{index}]: {kind}", path.display())
+    }
+}
+
+/// The reason of a [`TestFailure`].
+#[derive(Debug)]
+pub enum TestFailureKind {
+    /// The fixture file could not be read or parsed.
+    Parse(String),
+    /// A frame could not be compiled.
+    Jit(String),
+    /// The transaction was expected to fail validation but did not, or vice versa.
+    UnexpectedException { expected: Option<String>, got: Option<String> },
+    /// The logs hash does not match.
+    LogsRootMismatch { expected: B256, got: B256 },
+    /// The post-state root does not match.
+    StateRootMismatch { expected: B256, got: B256 },
+}
+
+impl fmt::Display for TestFailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "failed to parse: {e}"),
+            Self::Jit(e) => write!(f, "JIT error: {e}"),
+            Self::UnexpectedException { expected, got } => {
+                write!(f, "unexpected exception: expected {expected:?}, got {got:?}")
+            }
+            Self::LogsRootMismatch { expected, got } => {
+                write!(f, "logs root mismatch: expected {expected}, got {got}")
+            }
+            Self::StateRootMismatch { expected, got } => {
+                write!(f, "state root mismatch: expected {expected}, got {got}")
+            }
+        }
+    }
+}
+
+/// The results of running state tests, per fork.
+#[derive(Debug, Default)]
+pub struct TestReport {
+    /// The number of passed tests per fork.
+    pub passed: BTreeMap<SpecName, usize>,
+    /// The number of skipped test files.
+    pub skipped: usize,
+    /// The failed tests.
+    pub failures: Vec<TestFailure>,
+}
+
+impl TestReport {
+    /// Returns `true` if no test failed.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut failed = BTreeMap::<SpecName, usize>::new();
+        for failure in &self.failures {
+            *failed.entry(failure.spec_name).or_default() += 1;
+        }
+        let mut forks = self.passed.keys().chain(failed.keys()).copied().collect::<Vec<_>>();
+        forks.sort_unstable();
+        forks.dedup();
+        for fork in forks {
+            let passed = self.passed.get(&fork).copied().unwrap_or_default();
+            let failed = failed.get(&fork).copied().unwrap_or_default();
+            writeln!(f, "{:<20} {passed:>6} passed, {failed:>6} failed", format!("{fork:?}:"))?;
+        }
+        write!(f, "{} skipped files", self.skipped)
+    }
+}
+
+/// Runs all the state tests in the given fixture files, executing all frames with JIT-compiled
+/// functions.
+pub fn run(files: &[PathBuf], jit: &Rc<JitCompiler>) -> Result<TestReport> {
+    let mut report = TestReport::default();
+    for path in files {
+        if skip_test(path) {
+            report.skipped += 1;
+            continue;
+        }
+        execute_test_suite(path, jit, &mut report)?;
+        // Functions are keyed by code hash, so they could be reused by other test suites, but
+        // clearing them keeps memory usage bounded.
+        unsafe { jit.clear() }?;
+    }
+    Ok(report)
+}
+
+fn execute_test_suite(path: &Path, jit: &Rc<JitCompiler>, report: &mut TestReport) -> Result<()> {
+    let suite = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str::<TestSuite>(&s).map_err(|e| e.to_string()));
+    let suite = match suite {
+        Ok(suite) => suite,
+        Err(e) => {
+            report.failures.push(TestFailure {
+                path: path.to_path_buf(),
+                name: String::new(),
+                spec_name: SpecName::Unknown,
+                index: 0,
+                kind: TestFailureKind::Parse(e),
+            });
+            return Ok(());
+        }
+    };
+
+    for (name, unit) in suite {
+        execute_test_unit(path, &name, &unit, jit, report)
+            .wrap_err_with(|| format!("{}: {name}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn execute_test_unit(
+    path: &Path,
+    name: &str,
+    unit: &TestUnit,
+    jit: &Rc<JitCompiler>,
+    report: &mut TestReport,
+) -> Result<()> {
+    let mut cache_state = CacheState::new(false);
+    for (&address, account) in &unit.pre {
+        let info = revm::primitives::AccountInfo {
+            balance: account.balance,
+            code_hash: keccak256(&account.code),
+            code: Some(to_analysed(Bytecode::new_raw(account.code.clone()))),
+            nonce: account.nonce,
+        };
+        let storage = account.storage.iter().map(|(&k, &v)| (k, v)).collect();
+        cache_state.insert_account_with_storage(address, info, storage);
+    }
+
+    let mut env = Box::<Env>::default();
+    env.cfg.chain_id = 1;
+    env.block.number = unit.env.current_number;
+    env.block.coinbase = unit.env.current_coinbase;
+    env.block.timestamp = unit.env.current_timestamp;
+    env.block.gas_limit = unit.env.current_gas_limit;
+    env.block.basefee = unit.env.current_base_fee.unwrap_or_default();
+    env.block.difficulty = unit.env.current_difficulty;
+    env.block.prevrandao = unit.env.current_random;
+    if let Some(excess_blob_gas) = unit.env.current_excess_blob_gas {
+        env.block.set_blob_excess_gas_and_price(excess_blob_gas.saturating_to());
+    } else if let (Some(parent_blob_gas_used), Some(parent_excess_blob_gas)) =
+        (unit.env.parent_blob_gas_used, unit.env.parent_excess_blob_gas)
+    {
+        env.block.set_blob_excess_gas_and_price(calc_excess_blob_gas(
+            parent_blob_gas_used.saturating_to(),
+            parent_excess_blob_gas.saturating_to(),
+        ));
+    }
+
+    let tx = &unit.transaction;
+    env.tx.caller = match tx.sender {
+        Some(sender) => sender,
+        None => recover_address(tx.secret_key.as_slice())
+            .ok_or_else(|| eyre!("invalid secret key: {}", tx.secret_key))?,
+    };
+    env.tx.gas_price = tx.gas_price.or(tx.max_fee_per_gas).unwrap_or_default();
+    env.tx.gas_priority_fee = tx.max_priority_fee_per_gas;
+    env.tx.blob_hashes.clone_from(&tx.blob_versioned_hashes);
+    env.tx.max_fee_per_blob_gas = tx.max_fee_per_blob_gas;
+    env.tx.nonce = Some(tx.nonce.saturating_to());
+    env.tx.transact_to = match tx.to {
+        Some(to) => TransactTo::Call(to),
+        None => TransactTo::Create,
+    };
+
+    for (&spec_name, tests) in &unit.post {
+        let Some(spec_id) = spec_name.to_spec_id() else { continue };
+        for (index, test) in tests.iter().enumerate() {
+            env.tx.gas_limit = tx.gas_limit[test.indexes.gas].saturating_to();
+            env.tx.data = tx.data[test.indexes.data].clone();
+            env.tx.value = tx.value[test.indexes.value];
+            env.tx.access_list = tx
+                .access_lists
+                .get(test.indexes.data)
+                .and_then(Option::as_deref)
+                .unwrap_or_default()
+                .iter()
+                .map(|item| AccessListItem {
+                    address: item.address,
+                    storage_keys: item.storage_keys.clone(),
+                })
+                .collect();
+
+            let mut cache = cache_state.clone();
+            cache.set_state_clear_flag(spec_id.is_enabled_in(SpecId::SPURIOUS_DRAGON));
+            let state = State::builder().with_cached_prestate(cache).with_bundle_update().build();
+            let mut evm = Evm::builder()
+                .with_db(state)
+                .with_external_context(jit.clone())
+                .modify_env(|e| e.clone_from(&env))
+                .with_spec_id(spec_id)
+                .append_handler_register(register_handler)
+                .build();
+            let result = evm.transact_commit();
+            let kind = compare_result(test, &result, || {
+                state_merkle_trie_root(evm.db_mut().cache.trie_account())
+            });
+
+            match kind {
+                Some(kind) => report.failures.push(TestFailure {
+                    path: path.to_path_buf(),
+                    name: name.to_string(),
+                    spec_name,
+                    index,
+                    kind,
+                }),
+                None => *report.passed.entry(spec_name).or_default() += 1,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compares the result of a transaction, and the state root that `state_root` computes, against
+/// the expected result.
+fn compare_result<E: fmt::Display>(
+    test: &Test,
+    result: &Result<ExecutionResult, EVMError<E>>,
+    state_root: impl FnOnce() -> B256,
+) -> Option<TestFailureKind> {
+    match result {
+        // Only returned by our `execute_frame` handler.
+        Err(EVMError::Custom(e)) => return Some(TestFailureKind::Jit(e.clone())),
+        Err(e) if test.expect_exception.is_none() => {
+            return Some(TestFailureKind::UnexpectedException {
+                expected: None,
+                got: Some(e.to_string()),
+            });
+        }
+        Ok(_) if test.expect_exception.is_some() => {
+            return Some(TestFailureKind::UnexpectedException {
+                expected: test.expect_exception.clone(),
+                got: None,
+            });
+        }
+        _ => {}
+    }
+
+    let logs = result.as_ref().map(|r| r.logs()).unwrap_or_default();
+    let logs_root = log_rlp_hash(logs);
+    if logs_root != test.logs {
+        return Some(TestFailureKind::LogsRootMismatch { expected: test.logs, got: logs_root });
+    }
+    let state_root = state_root();
+    if state_root != test.hash {
+        return Some(TestFailureKind::StateRootMismatch { expected: test.hash, got: state_root });
+    }
+    None
+}
+
+/// Recovers the address of the given secp256k1 secret key.
+fn recover_address(secret_key: &[u8]) -> Option<Address> {
+    let key = k256::ecdsa::SigningKey::from_slice(secret_key).ok()?;
+    let public_key = key.verifying_key().to_encoded_point(false);
+    Some(Address::from_raw_public_key(&public_key.as_bytes()[1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TxPartIndices;
+    use revm::primitives::{Bytes, InvalidHeader, Log, Output, SuccessReason};
+    use revmc::OptimizationLevel;
+    use std::convert::Infallible;
+
+    const STATE_ROOT: B256 = B256::repeat_byte(0x01);
+
+    fn test(expect_exception: Option<&str>, logs: &[Log]) -> Test {
+        Test {
+            expect_exception: expect_exception.map(Into::into),
+            indexes: TxPartIndices { data: 0, gas: 0, value: 0 },
+            hash: STATE_ROOT,
+            logs: log_rlp_hash(logs),
+        }
+    }
+
+    fn success(logs: Vec<Log>) -> Result<ExecutionResult, EVMError<Infallible>> {
+        Ok(ExecutionResult::Success {
+            reason: SuccessReason::Stop,
+            gas_used: 21000,
+            gas_refunded: 0,
+            logs,
+            output: Output::Call(Bytes::new()),
+        })
+    }
+
+    fn error() -> Result<ExecutionResult, EVMError<Infallible>> {
+        Err(EVMError::Header(InvalidHeader::PrevrandaoNotSet))
+    }
+
+    #[test]
+    fn compare_success() {
+        let log = Log::new_unchecked(Address::ZERO, vec![], Bytes::from_static(&[42]));
+        let test = test(None, std::slice::from_ref(&log));
+        assert!(compare_result(&test, &success(vec![log]), || STATE_ROOT).is_none());
+    }
+
+    #[test]
+    fn compare_expected_exception() {
+        let test = test(Some("TransactionException.INTRINSIC_GAS_TOO_LOW"), &[]);
+        assert!(compare_result(&test, &error(), || STATE_ROOT).is_none());
+        let kind = compare_result(&test, &error(), || B256::ZERO);
+        assert!(matches!(kind, Some(TestFailureKind::StateRootMismatch { .. })), "{kind:?}");
+    }
+
+    #[test]
+    fn compare_jit_error() {
+        let result = Err(EVMError::<Infallible>::Custom("failed".into()));
+        let kind = compare_result(&test(None, &[]), &result, || STATE_ROOT);
+        assert!(matches!(kind, Some(TestFailureKind::Jit(ref e)) if e == "failed"), "{kind:?}");
+    }
+
+    #[test]
+    fn compare_unexpected_exception() {
+        let kind = compare_result(&test(None, &[]), &error(), || STATE_ROOT);
+        assert!(
+            matches!(
+                kind,
+                Some(TestFailureKind::UnexpectedException { expected: None, got: Some(_) })
+            ),
+            "{kind:?}"
+        );
+
+        let test = test(Some("TransactionException.INTRINSIC_GAS_TOO_LOW"), &[]);
+        let kind = compare_result(&test, &success(vec![]), || STATE_ROOT);
+        assert!(
+            matches!(
+                kind,
+                Some(TestFailureKind::UnexpectedException { expected: Some(_), got: None })
+            ),
+            "{kind:?}"
+        );
+    }
+
+    #[test]
+    fn compare_mismatch() {
+        let log = Log::new_unchecked(Address::ZERO, vec![], Bytes::new());
+        let kind = compare_result(&test(None, &[]), &success(vec![log]), || STATE_ROOT);
+        assert!(matches!(kind, Some(TestFailureKind::LogsRootMismatch { .. })), "{kind:?}");
+
+        let kind = compare_result(&test(None, &[]), &success(vec![]), || B256::ZERO);
+        assert!(
+            matches!(
+                kind,
+                Some(TestFailureKind::StateRootMismatch { expected, got })
+                    if expected == STATE_ROOT && got == B256::ZERO
+            ),
+            "{kind:?}"
+        );
+    }
+
+    #[test]
+    fn fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let files = find_all_json_tests(&[dir]);
+        assert!(!files.is_empty());
+        let jit = Rc::new(JitCompiler::new(OptimizationLevel::None).unwrap());
+        let report = run(&files, &jit).unwrap();
+        assert!(report.is_success(), "{:#?}", report.failures);
+        assert_eq!(report.passed.values().sum::<usize>(), 2);
+    }
+}
@@ -0,0 +1,62 @@
+use revmc::{ObjectCache, OptimizationLevel};
+
+/// [`JitWorker`](crate::JitWorker) configuration.
+#[derive(Clone, Debug)]
+pub struct JitConfig {
+    /// The number of times a bytecode has to be executed before it is queued for compilation.
+    ///
+    /// Defaults to `8`.
+    pub hot_threshold: u64,
+    /// The maximum number of bytecodes that can be waiting for compilation at the same time.
+    ///
+    /// Bytecodes that become hot while the queue is full keep being interpreted, and are queued
+    /// again on their next execution. Must be greater than zero.
+    ///
+    /// Defaults to `64`.
+    pub max_queue_size: usize,
+    /// The maximum number of compiled functions that are kept alive at the same time.
+    ///
+    /// Defaults to `1024`.
+    pub max_functions: usize,
+    /// What to do when `max_functions` is reached.
+    ///
+    /// Defaults to [`EvictionPolicy::Lru`].
+    pub eviction_policy: EvictionPolicy,
+    /// The optimization level used to compile bytecodes.
+    ///
+    /// Defaults to [`OptimizationLevel::Default`].
+    pub opt_level: OptimizationLevel,
+    /// An on-disk cache of compiled objects, shared between runs.
+    ///
+    /// Defaults to `None`.
+    pub object_cache: Option<ObjectCache>,
+}
+
+impl Default for JitConfig {
+    fn default() -> Self {
+        Self {
+            hot_threshold: 8,
+            max_queue_size: 64,
+            max_functions: 1024,
+            eviction_policy: EvictionPolicy::Lru,
+            opt_level: OptimizationLevel::Default,
+            object_cache: None,
+        }
+    }
+}
+
+/// Policy used to choose which compiled function to free when
+/// [`max_functions`](JitConfig::max_functions) is reached.
+///
+/// Functions that are currently executing are never evicted. An evicted bytecode goes back to
+/// being interpreted, and its execution count is reset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum EvictionPolicy {
+    /// Evict the least recently executed function.
+    #[default]
+    Lru,
+    /// Evict the least frequently executed function.
+    Lfu,
+    /// Never evict; stop compiling new bytecodes instead.
+    Never,
+}
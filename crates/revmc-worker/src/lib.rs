@@ -0,0 +1,144 @@
+#![doc = include_str!("../README.md")]
+#![cfg_attr(not(test), warn(unused_extern_crates))]
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+#[macro_use]
+extern crate tracing;
+
+use revm::{
+    handler::register::EvmHandler,
+    interpreter::{Interpreter, InterpreterAction},
+    Database,
+};
+use std::sync::Arc;
+
+mod config;
+pub use config::{EvictionPolicy, JitConfig};
+
+mod worker;
+pub use worker::{JitStats, JitWorker};
+
+/// Registers an `execute_frame` handler that calls the functions compiled by the [`JitWorker`] of
+/// the external context.
+///
+/// Frames of bytecodes that have not been compiled yet are executed by the previous handler, which
+/// is usually the interpreter.
+// The `+ 'static` bounds are only necessary because of an internal cfg feature.
+pub fn register_handler<EXT, DB>(handler: &mut EvmHandler<'_, EXT, DB>)
+where
+    EXT: AsRef<JitWorker> + 'static,
+    DB: Database + 'static,
+{
+    let prev = handler.execution.execute_frame.clone();
+    handler.execution.execute_frame = Arc::new(move |frame, memory, tables, context| {
+        let interpreter = frame.interpreter_mut();
+        let Some(code_hash) = interpreter.contract.hash else {
+            return prev(frame, memory, tables, context);
+        };
+        let key = (code_hash, context.evm.spec_id());
+        let worker = context.external.as_ref();
+        let f = match FrameState::of(interpreter) {
+            FrameState::New => worker.enter(key, || interpreter.contract.bytecode.original_bytes()),
+            FrameState::Compiled => Some(worker.resume(key)),
+            FrameState::Interpreted => None,
+        };
+        let Some(f) = f else {
+            return prev(frame, memory, tables, context);
+        };
+
+        let action = unsafe { f.call_with_interpreter_and_memory(interpreter, memory, context) };
+        if !matches!(
+            action,
+            InterpreterAction::Call { .. }
+                | InterpreterAction::Create { .. }
+                | InterpreterAction::EOFCreate { .. }
+        ) {
+            context.external.as_ref().exit(key);
+        }
+        Ok(action)
+    });
+}
+
+/// The state of a frame when it is passed to `execute_frame`.
+///
+/// A frame that was suspended to perform a call must be resumed by whoever started it: the
+/// interpreter cannot resume a compiled function, and vice versa.
+enum FrameState {
+    /// The frame has not been executed yet.
+    New,
+    /// The frame was suspended by the interpreter.
+    Interpreted,
+    /// The frame was suspended by a compiled function.
+    Compiled,
+}
+
+impl FrameState {
+    fn of(interpreter: &Interpreter) -> Self {
+        let ip = interpreter.instruction_pointer;
+        let code = interpreter.bytecode.as_ptr_range();
+        if ip == code.start {
+            Self::New
+        } else if (code.start..=code.end).contains(&ip) {
+            Self::Interpreted
+        } else {
+            // Compiled functions store their resume index in the instruction pointer.
+            Self::Compiled
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::{
+        db::{CacheDB, EmptyDB},
+        primitives::{address, hex, keccak256, AccountInfo, Bytecode, TransactTo, U256},
+        Evm,
+    };
+    use std::time::{Duration, Instant};
+
+    // fib(input + 1), see `examples/runner`.
+    const FIBONACCI_CODE: &[u8] =
+        &hex!("5f355f60015b8215601a578181019150909160019003916005565b9150505f5260205ff3");
+
+    #[test]
+    fn compiles_hot_bytecode() {
+        let config = JitConfig { hot_threshold: 2, ..Default::default() };
+        let mut evm = Evm::builder()
+            .with_db(CacheDB::new(EmptyDB::new()))
+            .with_external_context(JitWorker::new(config))
+            .append_handler_register(register_handler)
+            .build();
+        let code_hash = keccak256(FIBONACCI_CODE);
+        let address = address!("0000000000000000000000000000000000001234");
+        evm.db_mut().insert_account_info(
+            address,
+            AccountInfo {
+                code_hash,
+                code: Some(Bytecode::new_raw(FIBONACCI_CODE.into())),
+                ..Default::default()
+            },
+        );
+        evm.context.evm.env.tx.transact_to = TransactTo::Call(address);
+        evm.context.evm.env.tx.data = U256::from(69).to_be_bytes_vec().into();
+        let spec_id = evm.spec_id();
+
+        let expected = U256::from(190392490709135_u64);
+        assert_eq!(run(&mut evm), expected);
+        assert_eq!(run(&mut evm), expected);
+
+        let start = Instant::now();
+        while !evm.context.external.is_compiled(code_hash, spec_id) {
+            assert!(start.elapsed() < Duration::from_secs(30), "timed out waiting for compilation");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(run(&mut evm), expected);
+        assert_eq!(evm.context.external.stats().compiled, 1);
+    }
+
+    fn run(evm: &mut Evm<'_, JitWorker, CacheDB<EmptyDB>>) -> U256 {
+        let result = evm.transact().unwrap().result;
+        assert!(result.is_success(), "{result:?}");
+        U256::from_be_slice(result.output().unwrap())
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{EvictionPolicy, JitConfig};
+use revm::primitives::{Bytes, SpecId, B256};
+use revmc::{
+    llvm::{with_llvm_context, Context},
+    EvmCompiler, EvmCompilerFn, EvmLlvmBackend, Result,
+};
+use rustc_hash::FxHashMap;
+use std::{
+    fmt,
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+};
+
+/// A bytecode is compiled separately for each spec.
+pub(crate) type Key = (B256, SpecId);
+
+/// Compiles hot bytecodes on a background thread.
+///
+/// Install it as the external context of a [`revm::Evm`] with
+/// [`register_handler`](crate::register_handler).
+///
+/// Dropping the worker waits for the current compilation to finish, and frees all the compiled
+/// functions.
+pub struct JitWorker {
+    shared: Arc<Shared>,
+    sender: Option<SyncSender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for JitWorker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JitWorker")
+            .field("config", &self.shared.config)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsRef<Self> for JitWorker {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl Default for JitWorker {
+    fn default() -> Self {
+        Self::new(JitConfig::default())
+    }
+}
+
+impl JitWorker {
+    /// Spawns a new worker thread with the given configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_queue_size` is zero, or if the thread cannot be spawned.
+    pub fn new(config: JitConfig) -> Self {
+        assert!(config.max_queue_size > 0, "max_queue_size must be greater than zero");
+        let (sender, receiver) = mpsc::sync_channel(config.max_queue_size);
+        let shared = Arc::new(Shared { config, state: Mutex::default() });
+        let thread = std::thread::Builder::new()
+            .name("revmc-worker".into())
+            .spawn({
+                let shared = shared.clone();
+                move || run(&shared, receiver)
+            })
+            .expect("failed to spawn worker thread");
+        Self { shared, sender: Some(sender), thread: Some(thread) }
+    }
+
+    /// Returns the configuration of the worker.
+    pub fn config(&self) -> &JitConfig {
+        &self.shared.config
+    }
+
+    /// Returns `true` if the given bytecode hash and spec is currently compiled.
+    ///
+    /// The compiled function may be evicted at any time afterwards.
+    pub fn is_compiled(&self, code_hash: B256, spec_id: SpecId) -> bool {
+        self.shared
+            .state()
+            .entries
+            .get(&(code_hash, spec_id))
+            .is_some_and(|entry| matches!(entry.status, Status::Compiled(_)))
+    }
+
+    /// Returns statistics about the worker.
+    pub fn stats(&self) -> JitStats {
+        self.shared.state().stats
+    }
+
+    /// Records a new frame of the given bytecode.
+    ///
+    /// Returns the compiled function if there is one, in which case [`exit`](Self::exit) must be
+    /// called once the frame has returned. Otherwise, the bytecode is queued for compilation if it
+    /// became hot.
+    pub(crate) fn enter(
+        &self,
+        key: Key,
+        bytecode: impl FnOnce() -> Bytes,
+    ) -> Option<EvmCompilerFn> {
+        let config = &self.shared.config;
+        let mut state = self.shared.state();
+        let state = &mut *state;
+        state.clock += 1;
+        let entry = state.entries.entry(key).or_default();
+        entry.executions += 1;
+        entry.last_used = state.clock;
+        match entry.status {
+            Status::Compiled(f) => {
+                entry.active += 1;
+                return Some(f);
+            }
+            Status::Interpreted if entry.executions >= config.hot_threshold => {}
+            _ => return None,
+        }
+
+        if config.eviction_policy == EvictionPolicy::Never
+            && state.compiled + state.queued >= config.max_functions
+        {
+            return None;
+        }
+        let job = Job { key, bytecode: bytecode() };
+        match self.sender.as_ref().unwrap().try_send(job) {
+            Ok(()) => {
+                trace!(code_hash=%key.0, spec_id=?key.1, "queued");
+                entry.status = Status::Queued;
+                state.queued += 1;
+            }
+            Err(TrySendError::Full(_)) => state.stats.dropped += 1,
+            Err(TrySendError::Disconnected(_)) => unreachable!("worker thread exited"),
+        }
+        None
+    }
+
+    /// Returns the compiled function of a frame that was suspended in [`enter`](Self::enter).
+    pub(crate) fn resume(&self, key: Key) -> EvmCompilerFn {
+        match self.shared.state().entries.get(&key).map(|entry| entry.status) {
+            Some(Status::Compiled(f)) => f,
+            _ => unreachable!("compiled function of an active frame was evicted"),
+        }
+    }
+
+    /// Marks a frame started in [`enter`](Self::enter) as returned.
+    pub(crate) fn exit(&self, key: Key) {
+        if let Some(entry) = self.shared.state().entries.get_mut(&key) {
+            entry.active -= 1;
+        }
+    }
+}
+
+impl Drop for JitWorker {
+    fn drop(&mut self) {
+        // Disconnect the channel so that the thread exits once it has finished the current job.
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("worker thread panicked");
+            }
+        }
+    }
+}
+
+/// [`JitWorker`] statistics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct JitStats {
+    /// The number of bytecodes that were compiled successfully.
+    pub compiled: u64,
+    /// The number of bytecodes that failed to compile.
+    pub failed: u64,
+    /// The number of compiled functions that were evicted.
+    pub evicted: u64,
+    /// The number of times a hot bytecode could not be queued because the queue was full.
+    pub dropped: u64,
+}
+
+struct Shared {
+    config: JitConfig,
+    state: Mutex<State>,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Records the result of a compilation. Returns the keys of the functions that must be freed.
+    fn finish(&self, key: Key, f: Option<EvmCompilerFn>) -> Vec<Key> {
+        let mut state = self.state();
+        let state = &mut *state;
+        state.queued -= 1;
+        let entry = state.entries.get_mut(&key).unwrap();
+        if let Some(f) = f {
+            entry.status = Status::Compiled(f);
+            state.compiled += 1;
+            state.stats.compiled += 1;
+        } else {
+            entry.status = Status::Failed;
+            state.stats.failed += 1;
+        }
+
+        let mut evicted = Vec::new();
+        while state.compiled > self.config.max_functions {
+            let victim = state
+                .entries
+                .iter()
+                .filter(|&(k, e)| {
+                    *k != key && matches!(e.status, Status::Compiled(_)) && e.active == 0
+                })
+                .min_by_key(|(_, e)| match self.config.eviction_policy {
+                    EvictionPolicy::Lru => e.last_used,
+                    EvictionPolicy::Lfu => e.executions,
+                    EvictionPolicy::Never => unreachable!(),
+                })
+                .map(|(k, _)| *k);
+            // All the other functions are executing; try again after the next compilation.
+            let Some(victim) = victim else { break };
+            let entry = state.entries.get_mut(&victim).unwrap();
+            entry.status = Status::Interpreted;
+            entry.executions = 0;
+            state.compiled -= 1;
+            state.stats.evicted += 1;
+            evicted.push(victim);
+        }
+        evicted
+    }
+}
+
+#[derive(Default)]
+struct State {
+    entries: FxHashMap<Key, Entry>,
+    /// Incremented on every frame; used for LRU eviction.
+    clock: u64,
+    /// The number of compiled functions that are alive.
+    compiled: usize,
+    /// The number of jobs in the queue.
+    queued: usize,
+    stats: JitStats,
+}
+
+#[derive(Default)]
+struct Entry {
+    status: Status,
+    executions: u64,
+    last_used: u64,
+    /// The number of frames that started executing the compiled function and have not returned.
+    active: usize,
+}
+
+#[derive(Clone, Copy, Default)]
+enum Status {
+    #[default]
+    Interpreted,
+    Queued,
+    Compiled(EvmCompilerFn),
+    Failed,
+}
+
+struct Job {
+    key: Key,
+    bytecode: Bytes,
+}
+
+fn run(shared: &Shared, receiver: Receiver<Job>) {
+    with_llvm_context(|cx| {
+        // Each function is compiled in its own module, so that it can be freed independently.
+        let mut compilers = FxHashMap::default();
+        while let Ok(Job { key, bytecode }) = receiver.recv() {
+            let (code_hash, spec_id) = key;
+            let f = match compile(cx, &shared.config, key, &bytecode) {
+                Ok((compiler, f)) => {
+                    debug!(%code_hash, ?spec_id, "compiled");
+                    compilers.insert(key, compiler);
+                    Some(f)
+                }
+                Err(err) => {
+                    warn!(%code_hash, ?spec_id, %err, "failed to compile");
+                    None
+                }
+            };
+            for key in shared.finish(key, f) {
+                debug!(code_hash=%key.0, spec_id=?key.1, "evicted");
+                drop(compilers.remove(&key));
+            }
+        }
+    })
+}
+
+#[instrument(level = "debug", skip_all)]
+fn compile<'ctx>(
+    cx: &'ctx Context,
+    config: &JitConfig,
+    (code_hash, spec_id): Key,
+    bytecode: &[u8],
+) -> Result<(EvmCompiler<EvmLlvmBackend<'ctx>>, EvmCompilerFn)> {
+    let backend = EvmLlvmBackend::new(cx, false, config.opt_level)?;
+    let mut compiler = EvmCompiler::new(backend);
+    let f = if let Some(cache) = &config.object_cache {
+        unsafe { compiler.jit_cached(cache, bytecode, spec_id) }
+    } else {
+        unsafe { compiler.jit(&code_hash.to_string(), bytecode, spec_id) }
+    }?;
+    Ok((compiler, f))
+}
@@ -0,0 +1,73 @@
+//! Computes a fingerprint of the sources that affect code generation, which namespaces the object
+//! cache. See `src/cache.rs`.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// The crates whose sources affect the generated code, relative to this crate's directory.
+///
+/// Only this crate is available when building from a package, in which case the other crates are
+/// covered by the package version.
+const CODEGEN_CRATES: &[&str] = &[
+    ".",
+    "../revmc-backend",
+    "../revmc-builtins",
+    "../revmc-context",
+    "../revmc-cranelift",
+    "../revmc-llvm",
+];
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let mut hasher = Fnv64::default();
+    hasher.write(env::var("CARGO_PKG_VERSION").unwrap().as_bytes());
+    for krate in CODEGEN_CRATES {
+        let src = manifest_dir.join(krate).join("src");
+        if !src.is_dir() {
+            continue;
+        }
+        println!("cargo:rerun-if-changed={}", src.display());
+        let mut files = Vec::new();
+        collect_files(&src, &mut files);
+        files.sort();
+        for file in files {
+            hasher.write(
+                file.strip_prefix(&manifest_dir).unwrap_or(&file).as_os_str().as_encoded_bytes(),
+            );
+            hasher.write(&fs::read(&file).unwrap());
+        }
+    }
+    println!("cargo:rustc-env=REVMC_CODEGEN_FINGERPRINT={:016x}", hasher.0);
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// 64-bit FNV-1a, which is stable across Rust versions, unlike `DefaultHasher`.
+struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv64 {
+    fn write(&mut self, bytes: &[u8]) {
+        // Length-prefix to separate consecutive writes.
+        for &byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Bytecode analysis.
+//!
+//! This module exposes the results of the analysis passes that [`EvmCompiler`] runs on the
+//! bytecode before translating it, so that other tools can reuse them.
+//!
+//! # Examples
+//!
+//! ```
+//! use revmc::{
+//!     analysis::{BytecodeAnalysis, JumpTarget},
+//!     interpreter::opcode as op,
+//!     primitives::SpecId,
+//! };
+//!
+//! let code = [op::PUSH1, 0x05, op::JUMP, op::STOP, op::ADD, op::JUMPDEST, op::STOP];
+//! let analysis = BytecodeAnalysis::new(&code[..], SpecId::CANCUN)?;
+//! assert_eq!(analysis.inst(1).jump_target(), Some(JumpTarget::Static(4)));
+//! assert!(analysis.inst(3).is_dead_code());
+//! assert!(analysis.inst(4).is_reachable_jumpdest());
+//! # Ok::<(), revmc::Error>(())
+//! ```
+//!
+//! [`EvmCompiler`]: crate::EvmCompiler
+
+use super::{Bytecode, InstData, InstFlags, Opcode};
+use crate::{compiler::validate_eof, EvmCompilerInput, Result};
+use revm_interpreter::opcode as op;
+use revm_primitives::SpecId;
+use std::fmt;
+
+pub use super::sections::Section;
+
+/// The analyzed EVM bytecode.
+///
+/// Created with [`BytecodeAnalysis::new`] or [`EvmCompiler::analyze`](crate::EvmCompiler::analyze).
+pub struct BytecodeAnalysis<'a> {
+    bytecode: Bytecode<'a>,
+}
+
+impl fmt::Debug for BytecodeAnalysis<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.bytecode, f)
+    }
+}
+
+impl fmt::Display for BytecodeAnalysis<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.bytecode, f)
+    }
+}
+
+impl<'a> BytecodeAnalysis<'a> {
+    /// Parses and analyzes the given EVM bytecode.
+    ///
+    /// EOF containers are always validated.
+    pub fn new(input: impl Into<EvmCompilerInput<'a>>, spec_id: SpecId) -> Result<Self> {
+        let (code, eof) = input.into().decode(spec_id)?;
+        if let Some(eof) = &eof {
+            validate_eof(eof)?;
+        }
+        let mut bytecode = Bytecode::new(code, eof, spec_id);
+        bytecode.analyze()?;
+        Ok(Self::from_bytecode(bytecode))
+    }
+
+    pub(crate) fn from_bytecode(bytecode: Bytecode<'a>) -> Self {
+        Self { bytecode }
+    }
+
+    /// Returns the raw bytecode.
+    ///
+    /// For EOF, this is the concatenation of all code sections.
+    #[inline]
+    pub fn code(&self) -> &[u8] {
+        self.bytecode.code
+    }
+
+    /// Returns the spec ID that the bytecode was analyzed with.
+    #[inline]
+    pub fn spec_id(&self) -> SpecId {
+        self.bytecode.spec_id
+    }
+
+    /// Returns `true` if the bytecode is EOF.
+    #[inline]
+    pub fn is_eof(&self) -> bool {
+        self.bytecode.is_eof()
+    }
+
+    /// Returns the number of instructions.
+    ///
+    /// This may include a `STOP` instruction that is appended to legacy bytecode that does not end
+    /// with a diverging instruction.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytecode.insts.len()
+    }
+
+    /// Returns `true` if there are no instructions.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the instruction at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    #[track_caller]
+    pub fn inst(&self, index: usize) -> Instruction<'_> {
+        assert!(index < self.len(), "instruction index out of bounds: {index}");
+        Instruction { bytecode: &self.bytecode, index }
+    }
+
+    /// Returns an iterator over all the instructions, including dead code.
+    pub fn insts(&self) -> impl ExactSizeIterator<Item = Instruction<'_>> + Clone + '_ {
+        (0..self.len()).map(|index| Instruction { bytecode: &self.bytecode, index })
+    }
+
+    /// Returns the index of the instruction at the given program counter, if any.
+    pub fn pc_to_inst(&self, pc: usize) -> Option<usize> {
+        let pc = u32::try_from(pc).ok()?;
+        self.bytecode.pc_to_inst.get(&pc).map(|&inst| inst as usize)
+    }
+
+    /// Returns `true` if the given program counter is a `JUMPDEST`, meaning it is a valid target
+    /// for legacy `JUMP` and `JUMPI`.
+    ///
+    /// Always `false` in EOF.
+    #[inline]
+    pub fn is_valid_jump(&self, pc: usize) -> bool {
+        self.bytecode.is_valid_jump(pc)
+    }
+
+    /// Returns an iterator over the program counters of all the `JUMPDEST`s, including unreachable
+    /// ones.
+    ///
+    /// Always empty in EOF.
+    pub fn jumpdests(&self) -> impl Iterator<Item = usize> + '_ {
+        self.bytecode.jumpdests.iter_ones()
+    }
+
+    /// Returns `true` if the bytecode contains jumps whose target could not be resolved at compile
+    /// time. Always `false` in EOF.
+    #[inline]
+    pub fn has_dynamic_jumps(&self) -> bool {
+        self.bytecode.has_dynamic_jumps()
+    }
+
+    /// Returns `true` if the bytecode may suspend execution, meaning it contains reachable calls or
+    /// creates.
+    #[inline]
+    pub fn may_suspend(&self) -> bool {
+        self.bytecode.may_suspend()
+    }
+
+    /// Returns an iterator over the reachable instructions that may suspend execution.
+    pub fn suspend_points(&self) -> impl Iterator<Item = Instruction<'_>> + '_ {
+        self.insts().filter(|inst| !inst.is_dead_code() && inst.may_suspend())
+    }
+
+    /// Returns an iterator over the sections, and the index of the instruction that each one starts
+    /// at.
+    ///
+    /// A section is a sequence of instructions that are executed without any branches, whose gas
+    /// and stack height checks are done once at its start. Empty sections are skipped.
+    pub fn sections(&self) -> impl Iterator<Item = (usize, Section)> + '_ {
+        self.insts().filter_map(|inst| Some((inst.index(), inst.section()?)))
+    }
+
+    /// Returns the number of EOF code sections. Always `0` in legacy bytecode.
+    pub fn eof_code_sections(&self) -> usize {
+        if self.is_eof() {
+            self.bytecode.expect_eof().body.code_section.len()
+        } else {
+            0
+        }
+    }
+
+    /// Returns the index of the first instruction of the given EOF code section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytecode is not EOF, or if `section` is out of bounds.
+    #[track_caller]
+    pub fn eof_code_section_start(&self, section: usize) -> usize {
+        self.bytecode.eof_section_inst(section)
+    }
+
+    /// Returns the EOF code section that the given instruction belongs to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytecode is not EOF.
+    #[track_caller]
+    pub fn eof_code_section_of(&self, inst: usize) -> usize {
+        self.bytecode.pc_to_eof_section(self.bytecode.inst(inst).pc as usize)
+    }
+
+    /// Returns the indices of the `CALLF` instructions that may call the given EOF code section,
+    /// either directly or through `JUMPF`. Always empty in legacy bytecode.
+    pub fn eof_callers(&self, section: usize) -> &[usize] {
+        self.bytecode.eof_called_by.get(section).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the sorted list of EOF code sections that the given code section calls with `CALLF`
+    /// or jumps to with `JUMPF`. Always empty in legacy bytecode.
+    pub fn eof_callees(&self, section: usize) -> Vec<usize> {
+        if !self.is_eof() {
+            return Vec::new();
+        }
+        let mut callees = self
+            .insts()
+            .filter(|inst| matches!(inst.data().opcode, op::CALLF | op::JUMPF))
+            .filter(|inst| self.eof_code_section_of(inst.index()) == section)
+            .map(|inst| {
+                let imm = inst.opcode().immediate.unwrap();
+                u16::from_be_bytes(imm.try_into().unwrap()) as usize
+            })
+            .collect::<Vec<_>>();
+        callees.sort_unstable();
+        callees.dedup();
+        callees
+    }
+}
+
+/// An analyzed instruction in a [`BytecodeAnalysis`].
+#[derive(Clone, Copy)]
+pub struct Instruction<'a> {
+    bytecode: &'a Bytecode<'a>,
+    index: usize,
+}
+
+impl fmt::Debug for Instruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instruction")
+            .field("index", &self.index)
+            .field("pc", &self.pc())
+            .field("opcode", &format_args!("{}", self.opcode()))
+            .field("data", self.data())
+            .finish()
+    }
+}
+
+impl<'a> Instruction<'a> {
+    #[inline]
+    fn data(&self) -> &'a InstData {
+        self.bytecode.inst(self.index)
+    }
+
+    /// Returns the index of the instruction.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the program counter of the instruction.
+    #[inline]
+    pub fn pc(&self) -> usize {
+        self.data().pc as usize
+    }
+
+    /// Returns the opcode and its immediate data.
+    #[inline]
+    pub fn opcode(&self) -> Opcode<'a> {
+        self.data().to_op_in(self.bytecode)
+    }
+
+    /// Returns the base gas cost of the instruction.
+    ///
+    /// This includes the parts of the dynamic cost that are known at compile time, such as the word
+    /// cost of a `KECCAK256` with a constant length. It may still not be the full gas cost of the
+    /// instruction, as the rest of its dynamic cost is only known at runtime.
+    #[inline]
+    pub fn base_gas(&self) -> u16 {
+        self.data().base_gas
+    }
+
+    /// Returns the number of stack inputs and outputs of the instruction.
+    ///
+    /// This does not count the targets of jumps resolved from a preceding `PUSH`, which are
+    /// never pushed onto the stack.
+    #[inline]
+    pub fn stack_io(&self) -> (u8, u8) {
+        self.data().stack_io()
+    }
+
+    /// Returns `true` if the instruction is unreachable.
+    #[inline]
+    pub fn is_dead_code(&self) -> bool {
+        self.data().is_dead_code()
+    }
+
+    /// Returns `true` if the instruction is a `JUMPDEST` in legacy bytecode.
+    #[inline]
+    pub fn is_jumpdest(&self) -> bool {
+        self.data().is_jumpdest()
+    }
+
+    /// Returns `true` if the instruction may be jumped to: a `JUMPDEST` that is the target of a
+    /// jump in legacy bytecode, or the target of an `RJUMP*` in EOF.
+    #[inline]
+    pub fn is_reachable_jumpdest(&self) -> bool {
+        self.data().is_reachable_jumpdest(self.bytecode.is_eof())
+    }
+
+    /// Returns `true` if the instruction may be the target of a dynamic jump.
+    #[inline]
+    pub fn is_dynamic_jump_target(&self) -> bool {
+        self.data().flags.contains(InstFlags::DYNAMIC_JUMPDEST)
+    }
+
+    /// Returns the target of the instruction if it is a jump, or `None` otherwise.
+    pub fn jump_target(&self) -> Option<JumpTarget> {
+        let data = self.data();
+        if data.is_legacy_jump() {
+            return Some(if data.flags.contains(InstFlags::INVALID_JUMP) {
+                JumpTarget::Invalid
+            } else if data.flags.contains(InstFlags::STATIC_JUMP) {
+                JumpTarget::Static(data.data as usize)
+            } else {
+                JumpTarget::Dynamic
+            });
+        }
+        if self.bytecode.is_eof() && matches!(data.opcode, op::RJUMP | op::RJUMPI | op::RJUMPV) {
+            let targets = self.bytecode.iter_rjump_target_insts(data).map(|(_, target)| target);
+            return Some(if data.opcode == op::RJUMPV {
+                JumpTarget::Table(targets.collect())
+            } else {
+                JumpTarget::Static(targets.last().unwrap())
+            });
+        }
+        None
+    }
+
+    /// Returns `true` if the instruction is known to stop execution, or to leave the current code
+    /// section in EOF.
+    #[inline]
+    pub fn is_diverging(&self) -> bool {
+        self.data().is_diverging(self.bytecode.is_eof())
+    }
+
+    /// Returns `true` if the instruction may suspend execution, such as calls and creates.
+    #[inline]
+    pub fn may_suspend(&self) -> bool {
+        self.data().may_suspend(self.bytecode.is_eof())
+    }
+
+    /// Returns the section that starts at this instruction, if any.
+    #[inline]
+    pub fn section(&self) -> Option<Section> {
+        let section = self.data().section;
+        (!section.is_empty()).then_some(section)
+    }
+}
+
+/// The target of a jump [`Instruction`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JumpTarget {
+    /// The target instruction index is known at compile time.
+    Static(usize),
+    /// The target instruction indices of an `RJUMPV`, by case.
+    Table(Vec<usize>),
+    /// The target is only known at runtime. It is one of the instructions that are
+    /// [dynamic jump targets](Instruction::is_dynamic_jump_target), or invalid.
+    Dynamic,
+    /// The target is known to be invalid. The jump always fails at runtime.
+    Invalid,
+}
@@ -0,0 +1,232 @@
+use super::{Bytecode, Inst, InstFlags, Section};
+use revm_interpreter::opcode as op;
+use std::io;
+
+/// The control-flow graph of an analyzed bytecode.
+///
+/// The basic blocks are the [sections](Section), which also end at `CALLF` so that the edges of
+/// internal calls can be represented. Dead code is not part of any block.
+pub(crate) struct Cfg {
+    /// The basic blocks, sorted by their first instruction.
+    pub(crate) blocks: Vec<CfgBlock>,
+}
+
+/// A basic block in the [`Cfg`].
+pub(crate) struct CfgBlock {
+    /// The first instruction.
+    pub(crate) start: Inst,
+    /// The last instruction, inclusive.
+    pub(crate) end: Inst,
+    /// The section that starts at this block.
+    ///
+    /// This is empty if the section does not have any gas cost or stack requirements.
+    pub(crate) section: Section,
+    /// The outgoing edges.
+    pub(crate) succs: Vec<CfgEdge>,
+}
+
+/// An edge in the [`Cfg`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CfgEdge {
+    /// The index of the target block.
+    pub(crate) target: usize,
+    /// The kind of the edge.
+    pub(crate) kind: CfgEdgeKind,
+}
+
+/// The kind of a [`CfgEdge`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CfgEdgeKind {
+    /// Execution continues with the next instruction.
+    Fallthrough,
+    /// A jump with a known target: static `JUMP{,I}`, `RJUMP*` and `JUMPF`.
+    Jump,
+    /// A possible target of a dynamic `JUMP{,I}`.
+    DynamicJump,
+    /// `CALLF` to the first block of the called code section.
+    Call,
+    /// `RETF` to the block after one of the `CALLF`s that may have called the code section.
+    Return,
+}
+
+impl CfgEdgeKind {
+    /// Returns the name of the edge kind.
+    pub(crate) const fn name(self) -> &'static str {
+        match self {
+            Self::Fallthrough => "fallthrough",
+            Self::Jump => "jump",
+            Self::DynamicJump => "dynamic_jump",
+            Self::Call => "call",
+            Self::Return => "return",
+        }
+    }
+}
+
+impl Cfg {
+    /// Builds the control-flow graph of the given bytecode.
+    ///
+    /// The bytecode must have been analyzed.
+    pub(crate) fn new(bytecode: &Bytecode<'_>) -> Self {
+        let is_eof = bytecode.is_eof();
+        let mut blocks = Vec::<CfgBlock>::new();
+        let mut ends_block = true;
+        for (inst, data) in bytecode.iter_insts() {
+            if ends_block || !data.section.is_empty() || data.is_reachable_jumpdest(is_eof) {
+                let section = data.section;
+                blocks.push(CfgBlock { start: inst, end: inst, section, succs: Vec::new() });
+            }
+            blocks.last_mut().unwrap().end = inst;
+            ends_block = data.is_branching(is_eof)
+                || data.may_suspend(is_eof)
+                || data.opcode == op::CALLF
+                || (!is_eof && data.requires_gasleft(bytecode.spec_id));
+        }
+
+        let mut cfg = Self { blocks };
+        for block in 0..cfg.blocks.len() {
+            cfg.blocks[block].succs = cfg.successors(bytecode, block);
+        }
+        cfg
+    }
+
+    /// Returns the index of the block that starts at the given instruction.
+    fn block_at(&self, inst: Inst) -> Option<usize> {
+        self.blocks.binary_search_by_key(&inst, |block| block.start).ok()
+    }
+
+    /// Computes the outgoing edges of the given block.
+    fn successors(&self, bytecode: &Bytecode<'_>, block: usize) -> Vec<CfgEdge> {
+        let is_eof = bytecode.is_eof();
+        let data = bytecode.inst(self.blocks[block].end);
+
+        let mut succs = Vec::new();
+        let mut add_edge = |inst: Inst, kind: CfgEdgeKind| {
+            if let Some(target) = self.block_at(inst) {
+                succs.push(CfgEdge { target, kind });
+            }
+        };
+
+        let is_unconditional_jump = if is_eof {
+            matches!(data.opcode, op::RJUMP | op::CALLF)
+        } else {
+            data.opcode == op::JUMP
+        };
+        if !data.is_diverging(is_eof) && !is_unconditional_jump {
+            if let Some(next) = self.blocks.get(block + 1) {
+                add_edge(next.start, CfgEdgeKind::Fallthrough);
+            }
+        }
+
+        match data.opcode {
+            op::JUMP | op::JUMPI if !is_eof => {
+                if data.flags.contains(InstFlags::INVALID_JUMP) {
+                    // No target.
+                } else if data.flags.contains(InstFlags::STATIC_JUMP) {
+                    add_edge(data.data as Inst, CfgEdgeKind::Jump);
+                } else {
+                    for (inst, _) in bytecode
+                        .iter_insts()
+                        .filter(|(_, data)| data.flags.contains(InstFlags::DYNAMIC_JUMPDEST))
+                    {
+                        add_edge(inst, CfgEdgeKind::DynamicJump);
+                    }
+                }
+            }
+            op::RJUMP | op::RJUMPI | op::RJUMPV if is_eof => {
+                for (_, target) in bytecode.iter_rjump_target_insts(data) {
+                    add_edge(target, CfgEdgeKind::Jump);
+                }
+            }
+            op::CALLF | op::JUMPF if is_eof => {
+                let imm = bytecode.get_imm(data).unwrap();
+                let section = u16::from_be_bytes(imm.try_into().unwrap()) as usize;
+                let kind =
+                    if data.opcode == op::CALLF { CfgEdgeKind::Call } else { CfgEdgeKind::Jump };
+                add_edge(bytecode.eof_section_inst(section), kind);
+            }
+            op::RETF if is_eof => {
+                let section = bytecode.pc_to_eof_section(data.pc as usize);
+                for &caller in bytecode.eof_section_called_by(section) {
+                    add_edge(caller + 1, CfgEdgeKind::Return);
+                }
+            }
+            _ => {}
+        }
+
+        succs
+    }
+
+    /// Writes the graph in the Graphviz DOT format.
+    pub(crate) fn write_dot(
+        &self,
+        bytecode: &Bytecode<'_>,
+        w: &mut impl io::Write,
+    ) -> io::Result<()> {
+        writeln!(w, "digraph cfg {{")?;
+        writeln!(w, "    node [shape=box, fontname=monospace];")?;
+        for (i, block) in self.blocks.iter().enumerate() {
+            let Section { gas_cost, inputs, max_growth } = block.section;
+            write!(w, "    bb{i} [label=\"bb{i} (gas: {gas_cost}, inputs: {inputs}, ")?;
+            write!(w, "max_growth: {max_growth})\\l")?;
+            for inst in block.start..=block.end {
+                let pc = bytecode.inst(inst).pc;
+                write!(w, "{pc:>5}: {}\\l", bytecode.opcode(inst))?;
+            }
+            writeln!(w, "\"];")?;
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            for edge in &block.succs {
+                let style = match edge.kind {
+                    CfgEdgeKind::Fallthrough | CfgEdgeKind::Jump => "solid",
+                    CfgEdgeKind::DynamicJump | CfgEdgeKind::Call | CfgEdgeKind::Return => "dashed",
+                };
+                writeln!(
+                    w,
+                    "    bb{i} -> bb{} [label=\"{}\", style={style}];",
+                    edge.target,
+                    edge.kind.name()
+                )?;
+            }
+        }
+        writeln!(w, "}}")
+    }
+
+    /// Writes the graph as JSON.
+    ///
+    /// The output is an object with a `blocks` array, where each block has:
+    /// - `id`: the index of the block;
+    /// - `start`, `end`: the first and last (inclusive) instruction indices;
+    /// - `start_pc`, `end_pc`: the program counters of the first and last instructions;
+    /// - `gas_cost`, `inputs`, `max_growth`: the [`Section`] that starts at the block;
+    /// - `succs`: the outgoing edges, as objects with a `target` block index and a `kind`.
+    pub(crate) fn write_json(
+        &self,
+        bytecode: &Bytecode<'_>,
+        w: &mut impl io::Write,
+    ) -> io::Result<()> {
+        writeln!(w, "{{")?;
+        writeln!(w, "  \"blocks\": [")?;
+        for (i, block) in self.blocks.iter().enumerate() {
+            let Section { gas_cost, inputs, max_growth } = block.section;
+            let start_pc = bytecode.inst(block.start).pc;
+            let end_pc = bytecode.inst(block.end).pc;
+            write!(
+                w,
+                "    {{\"id\": {i}, \"start\": {}, \"end\": {}, \"start_pc\": {start_pc}, \
+                 \"end_pc\": {end_pc}, \"gas_cost\": {gas_cost}, \"inputs\": {inputs}, \
+                 \"max_growth\": {max_growth}, \"succs\": [",
+                block.start, block.end,
+            )?;
+            for (j, edge) in block.succs.iter().enumerate() {
+                if j > 0 {
+                    write!(w, ", ")?;
+                }
+                write!(w, "{{\"target\": {}, \"kind\": \"{}\"}}", edge.target, edge.kind.name())?;
+            }
+            let comma = if i + 1 < self.blocks.len() { "," } else { "" };
+            writeln!(w, "]}}{comma}")?;
+        }
+        writeln!(w, "  ]")?;
+        writeln!(w, "}}")
+    }
+}
@@ -0,0 +1,101 @@
+use super::{Bytecode, Inst, InstFlags};
+use revm_interpreter::opcode as op;
+
+/// The number of instructions in a dispatcher arm.
+const ARM_LEN: usize = 5;
+
+/// The minimum number of arms for a chain to be recognized as a dispatcher.
+const MIN_ARMS: usize = 2;
+
+/// A Solidity function dispatcher.
+///
+/// This is a chain of arms that each compare the function selector at the top of the stack with a
+/// constant, and jump to the function if they are equal, or fall through to the next arm:
+/// - `DUP1 PUSH4 selector EQ PUSH2 dest JUMPI`, or
+/// - `PUSH4 selector DUP2 EQ PUSH2 dest JUMPI`.
+///
+/// The first arm is translated as usual, except that its `JUMPI`, which is marked with
+/// [`InstFlags::DISPATCH`], branches directly to the destination of the first matching arm, or to
+/// the instruction after the chain. The static gas of the arms that are skipped this way is charged
+/// on each branch, so that the gas used is the same as walking the chain.
+#[derive(Clone, Debug)]
+pub(crate) struct Dispatcher {
+    /// The arms, in the order in which they are compared.
+    pub(crate) arms: Vec<DispatcherArm>,
+    /// The instruction after the last arm, which is executed if no selector matches.
+    pub(crate) fallthrough: Inst,
+}
+
+/// An arm of a [`Dispatcher`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DispatcherArm {
+    /// The function selector.
+    pub(crate) selector: u32,
+    /// The `JUMPDEST` instruction to branch to if the selector matches.
+    pub(crate) target: Inst,
+    /// The static gas of the instructions of the arm.
+    pub(crate) gas: u64,
+}
+
+impl Dispatcher {
+    /// Finds all the dispatchers in the bytecode, returning them with the `JUMPI` of their first
+    /// arm.
+    pub(crate) fn find_all(bytecode: &Bytecode<'_>) -> Vec<(Inst, Self)> {
+        let mut dispatchers = Vec::new();
+        let mut inst = 0;
+        while inst < bytecode.insts.len() {
+            match Self::parse(bytecode, inst) {
+                Some(dispatcher) => {
+                    let next = dispatcher.fallthrough;
+                    dispatchers.push((inst + ARM_LEN - 1, dispatcher));
+                    inst = next;
+                }
+                None => inst += 1,
+            }
+        }
+        dispatchers
+    }
+
+    /// Parses the dispatcher whose first arm starts at `inst`, if any.
+    fn parse(bytecode: &Bytecode<'_>, inst: Inst) -> Option<Self> {
+        let arms = (inst..)
+            .step_by(ARM_LEN)
+            .map_while(|inst| DispatcherArm::parse(bytecode, inst))
+            .collect::<Vec<_>>();
+        if arms.len() < MIN_ARMS {
+            return None;
+        }
+        let fallthrough = inst + arms.len() * ARM_LEN;
+        Some(Self { arms, fallthrough })
+    }
+}
+
+impl DispatcherArm {
+    /// Parses the arm starting at `inst`, if any.
+    fn parse(bytecode: &Bytecode<'_>, inst: Inst) -> Option<Self> {
+        let insts = bytecode.insts.get(inst..inst + ARM_LEN)?;
+        let [a, b, eq, _push, jump] = insts else { unreachable!() };
+        if insts.iter().any(|data| data.is_dead_code()) {
+            return None;
+        }
+
+        let selector = match (a.opcode, b.opcode) {
+            (op::DUP1, op::PUSH1..=op::PUSH4) => b,
+            (op::PUSH1..=op::PUSH4, op::DUP2) => a,
+            _ => return None,
+        };
+        // The target is pushed by the previous instruction unless the jump was resolved.
+        if eq.opcode != op::EQ
+            || jump.opcode != op::JUMPI
+            || !jump.flags.contains(InstFlags::STATIC_JUMP)
+            || jump.flags.intersects(InstFlags::INVALID_JUMP | InstFlags::RESOLVED_JUMP)
+        {
+            return None;
+        }
+
+        let selector =
+            bytecode.get_imm(selector)?.iter().fold(0, |acc, &byte| acc << 8 | byte as u32);
+        let gas = insts.iter().map(|data| data.base_gas as u64).sum();
+        Some(Self { selector, target: jump.data as Inst, gas })
+    }
+}
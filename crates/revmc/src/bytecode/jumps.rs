@@ -0,0 +1,341 @@
+use super::{stack_io, Bytecode, Inst, InstFlags};
+use revm_interpreter::opcode as op;
+use rustc_hash::FxHashMap;
+
+/// The maximum number of constants an abstract value can be before it becomes unknown.
+const MAX_CONSTS: usize = 16;
+
+/// The maximum average number of times each block can be visited before the analysis gives up.
+const MAX_VISITS_PER_BLOCK: usize = 64;
+
+/// Dynamic jump resolution by abstract interpretation.
+///
+/// Propagates the constants pushed onto the stack through the control flow graph, including across
+/// `DUP*`, `SWAP*` and `POP`, to find the possible targets of the jumps that are not immediately
+/// preceded by a `PUSH`, such as the return addresses of Solidity internal functions.
+///
+/// After the analysis:
+/// - jumps with a single possible target are marked as [`STATIC_JUMP`] and
+///   [`RESOLVED_JUMP`](InstFlags::RESOLVED_JUMP);
+/// - the possible targets of the remaining dynamic jumps are marked as
+///   [`DYNAMIC_JUMPDEST`](InstFlags::DYNAMIC_JUMPDEST). If any of the targets is unknown, this is
+///   all `JUMPDEST`s.
+///
+/// [`STATIC_JUMP`]: InstFlags::STATIC_JUMP
+pub(crate) struct JumpAnalysis {
+    /// The basic blocks, as inclusive ranges of instructions.
+    blocks: Vec<(Inst, Inst)>,
+    /// Mapping from `JUMPDEST` instruction to the block that starts with it.
+    jumpdest_blocks: FxHashMap<Inst, usize>,
+    /// The abstract stack at the start of each block, or `None` if it has not been reached.
+    states: Vec<Option<AbsStack>>,
+}
+
+impl JumpAnalysis {
+    /// Splits the bytecode into basic blocks.
+    pub(crate) fn new(bytecode: &Bytecode<'_>) -> Self {
+        let mut blocks = Vec::new();
+        let mut jumpdest_blocks = FxHashMap::default();
+        let mut start = 0;
+        for (inst, data) in bytecode.iter_all_insts() {
+            if data.is_jumpdest() && inst != start {
+                blocks.push((start, inst - 1));
+                start = inst;
+            }
+            if data.is_jumpdest() {
+                jumpdest_blocks.insert(inst, blocks.len());
+            }
+            if data.is_branching(false) {
+                blocks.push((start, inst));
+                start = inst + 1;
+            }
+        }
+        if start < bytecode.insts.len() {
+            blocks.push((start, bytecode.insts.len() - 1));
+        }
+        let states = vec![None; blocks.len()];
+        Self { blocks, jumpdest_blocks, states }
+    }
+
+    /// Runs the analysis to a fixed point and applies the results to the bytecode.
+    pub(crate) fn run(mut self, bytecode: &mut Bytecode<'_>) {
+        if !self.solve(bytecode) {
+            debug!(blocks = self.blocks.len(), "jump analysis did not converge");
+            for data in &mut bytecode.insts {
+                if data.is_jumpdest() {
+                    data.flags |= InstFlags::DYNAMIC_JUMPDEST;
+                }
+            }
+            return;
+        }
+
+        // Collect the targets of all the reachable jumps.
+        let mut targets = FxHashMap::default();
+        for block in 0..self.blocks.len() {
+            if let Some(mut stack) = self.states[block].clone() {
+                self.simulate(bytecode, block, &mut stack, |inst, target| {
+                    targets.insert(inst, target.clone());
+                });
+            }
+        }
+
+        let mut resolved = 0usize;
+        let mut any_unknown = false;
+        for inst in 0..bytecode.insts.len() {
+            let data = &bytecode.insts[inst];
+            if !data.is_legacy_jump() || data.flags.contains(InstFlags::STATIC_JUMP) {
+                continue;
+            }
+            match targets.get(&inst) {
+                // Unreachable.
+                None => {}
+                Some(AbsValue::Consts(consts)) if consts.len() == 1 => {
+                    let target_pc = consts[0] as usize;
+                    let flags = InstFlags::STATIC_JUMP | InstFlags::RESOLVED_JUMP;
+                    bytecode.insts[inst].flags |= flags;
+                    if bytecode.is_valid_jump(target_pc) {
+                        let target = bytecode.pc_to_inst(target_pc);
+                        bytecode.insts[target].data = 1;
+                        bytecode.insts[inst].data = target as u32;
+                        trace!(inst, target, "resolved jump");
+                    } else {
+                        bytecode.insts[inst].flags |= InstFlags::INVALID_JUMP;
+                        trace!(inst, target_pc, "resolved invalid jump");
+                    }
+                    resolved += 1;
+                }
+                Some(AbsValue::Consts(consts)) => {
+                    for &target_pc in consts {
+                        if bytecode.is_valid_jump(target_pc as usize) {
+                            let target = bytecode.pc_to_inst(target_pc as usize);
+                            bytecode.insts[target].flags |= InstFlags::DYNAMIC_JUMPDEST;
+                        }
+                    }
+                    trace!(inst, ?consts, "found dynamic jump");
+                }
+                Some(AbsValue::Unknown) => {
+                    trace!(inst, "found unknown jump");
+                    any_unknown = true;
+                }
+            }
+        }
+        if any_unknown {
+            for data in &mut bytecode.insts {
+                if data.is_jumpdest() {
+                    data.flags |= InstFlags::DYNAMIC_JUMPDEST;
+                }
+            }
+        }
+
+        bytecode.has_dynamic_jumps = bytecode
+            .insts
+            .iter()
+            .any(|data| data.is_legacy_jump() && !data.flags.contains(InstFlags::STATIC_JUMP));
+        debug!(resolved, any_unknown, has_dynamic_jumps = bytecode.has_dynamic_jumps, "jumps");
+    }
+
+    /// Propagates the abstract stacks until a fixed point is reached.
+    ///
+    /// Returns `false` if the analysis gave up.
+    fn solve(&mut self, bytecode: &Bytecode<'_>) -> bool {
+        let mut budget = self.blocks.len() * MAX_VISITS_PER_BLOCK;
+        let mut in_worklist = vec![false; self.blocks.len()];
+        let mut worklist = vec![0];
+        in_worklist[0] = true;
+        self.states[0] = Some(AbsStack::default());
+        while let Some(block) = worklist.pop() {
+            in_worklist[block] = false;
+            if budget == 0 {
+                return false;
+            }
+            budget -= 1;
+
+            let mut stack = self.states[block].clone().unwrap();
+            let mut jump_targets = Vec::new();
+            let mut fallthrough = true;
+            self.simulate(bytecode, block, &mut stack, |inst, target| {
+                jump_targets.push(target.clone());
+                fallthrough = bytecode.inst(inst).opcode == op::JUMPI;
+            });
+
+            let (_, end) = self.blocks[block];
+            let mut successors = Vec::new();
+            if fallthrough
+                && !bytecode.inst(end).is_diverging(false)
+                && block + 1 < self.blocks.len()
+            {
+                successors.push(block + 1);
+            }
+            for target in &jump_targets {
+                match target {
+                    AbsValue::Consts(consts) => {
+                        successors.extend(consts.iter().filter_map(|&pc| {
+                            if !bytecode.is_valid_jump(pc as usize) {
+                                return None;
+                            }
+                            self.jumpdest_blocks.get(&bytecode.pc_to_inst(pc as usize)).copied()
+                        }));
+                    }
+                    AbsValue::Unknown => successors.extend(self.jumpdest_blocks.values().copied()),
+                }
+            }
+
+            for successor in successors {
+                let changed = match &mut self.states[successor] {
+                    Some(state) => state.join(&stack),
+                    state @ None => {
+                        *state = Some(stack.clone());
+                        true
+                    }
+                };
+                if changed && !in_worklist[successor] {
+                    in_worklist[successor] = true;
+                    worklist.push(successor);
+                }
+            }
+        }
+        true
+    }
+
+    /// Simulates the instructions of `block` on `stack`, calling `on_jump` with the target of the
+    /// `JUMP` or `JUMPI` that ends it, if any.
+    fn simulate(
+        &self,
+        bytecode: &Bytecode<'_>,
+        block: usize,
+        stack: &mut AbsStack,
+        mut on_jump: impl FnMut(Inst, &AbsValue),
+    ) {
+        let (start, end) = self.blocks[block];
+        for inst in start..=end {
+            let data = bytecode.inst(inst);
+            let opcode = data.opcode;
+            match opcode {
+                op::PUSH0..=op::PUSH32 => {
+                    let value = match bytecode.get_imm(data) {
+                        _ if opcode == op::PUSH0 => AbsValue::Consts(vec![0]),
+                        Some(imm) => AbsValue::from_be_bytes(imm),
+                        None => AbsValue::Unknown,
+                    };
+                    stack.push(value);
+                }
+                op::DUP1..=op::DUP16 => {
+                    let value = stack.peek((opcode - op::DUP1) as usize);
+                    stack.push(value);
+                }
+                op::SWAP1..=op::SWAP16 => stack.swap((opcode - op::SWAP1 + 1) as usize),
+                op::JUMP | op::JUMPI => {
+                    let target = stack.pop();
+                    on_jump(inst, &target);
+                    if opcode == op::JUMPI {
+                        stack.pop();
+                    }
+                }
+                _ => {
+                    let (inp, out) = stack_io(opcode);
+                    for _ in 0..inp {
+                        stack.pop();
+                    }
+                    for _ in 0..out {
+                        stack.push(AbsValue::Unknown);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The abstract stack.
+///
+/// Only the topmost values are tracked; the values below are unknown.
+#[derive(Clone, Default)]
+struct AbsStack(Vec<AbsValue>);
+
+impl AbsStack {
+    fn push(&mut self, value: AbsValue) {
+        if self.0.len() == revmc_context::EvmStack::CAPACITY {
+            self.0.remove(0);
+        }
+        self.0.push(value);
+    }
+
+    fn pop(&mut self) -> AbsValue {
+        self.0.pop().unwrap_or(AbsValue::Unknown)
+    }
+
+    /// Returns the `n`th value from the top.
+    fn peek(&self, n: usize) -> AbsValue {
+        self.0.iter().rev().nth(n).cloned().unwrap_or(AbsValue::Unknown)
+    }
+
+    /// Swaps the top value with the `n`th value from the top.
+    fn swap(&mut self, n: usize) {
+        let len = self.0.len();
+        if n >= len {
+            // Track the unknown values that are swapped to the top.
+            self.0.splice(0..0, std::iter::repeat(AbsValue::Unknown).take(n + 1 - len));
+        }
+        let len = self.0.len();
+        self.0.swap(len - 1, len - 1 - n);
+    }
+
+    /// Joins `other` into `self`, aligning the stacks at the top.
+    ///
+    /// Returns `true` if `self` changed.
+    fn join(&mut self, other: &Self) -> bool {
+        let mut changed = false;
+        if self.0.len() > other.0.len() {
+            self.0.drain(..self.0.len() - other.0.len());
+            changed = true;
+        }
+        let other = &other.0[other.0.len() - self.0.len()..];
+        for (a, b) in self.0.iter_mut().zip(other) {
+            changed |= a.join(b);
+        }
+        changed
+    }
+}
+
+/// A value on the abstract stack.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum AbsValue {
+    /// Any value.
+    Unknown,
+    /// One of the given sorted constants.
+    ///
+    /// Only values that fit in a `u32` are tracked, as they are only used as jump targets.
+    Consts(Vec<u32>),
+}
+
+impl AbsValue {
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+        let bytes = &bytes[start..];
+        if bytes.len() > 4 {
+            return Self::Unknown;
+        }
+        let mut padded = [0; 4];
+        padded[4 - bytes.len()..].copy_from_slice(bytes);
+        Self::Consts(vec![u32::from_be_bytes(padded)])
+    }
+
+    /// Joins `other` into `self`. Returns `true` if `self` changed.
+    fn join(&mut self, other: &Self) -> bool {
+        let Self::Consts(a) = self else { return false };
+        let Self::Consts(b) = other else {
+            *self = Self::Unknown;
+            return true;
+        };
+        let len = a.len();
+        for &value in b {
+            if let Err(i) = a.binary_search(&value) {
+                a.insert(i, value);
+            }
+        }
+        if a.len() > MAX_CONSTS {
+            *self = Self::Unknown;
+            return true;
+        }
+        a.len() != len
+    }
+}
@@ -6,14 +6,31 @@ use revm_interpreter::opcode as op;
 use revm_primitives::{hex, Eof, SpecId};
 use revmc_backend::{eyre::ensure, Result};
 use rustc_hash::FxHashMap;
-use std::{borrow::Cow, fmt};
+use std::{borrow::Cow, fmt, io, ops::Range};
 
 mod sections;
 use sections::{Section, SectionAnalysis};
 
+mod cfg;
+use cfg::Cfg;
+
+pub mod analysis;
+
+mod stack;
+use stack::StackAnalysis;
+
+mod range;
+use range::RangeAnalysis;
+
 mod info;
 pub use info::*;
 
+mod jumps;
+use jumps::JumpAnalysis;
+
+mod dispatch;
+pub(crate) use dispatch::Dispatcher;
+
 mod opcode;
 pub use opcode::*;
 
@@ -50,18 +67,34 @@ pub struct Bytecode<'a> {
     pc_to_inst: FxHashMap<u32, u32>,
     /// Mapping from EOF code section index to the list of instructions that call it.
     eof_called_by: Vec<Vec<Inst>>,
+    /// The static gas of the instructions of each instruction's section that come after it.
+    /// See [`unspent_section_gas`](Self::unspent_section_gas).
+    unspent_section_gas: Vec<u32>,
+    /// Mapping from the `JUMPI` of the first arm of each function dispatcher to the dispatcher.
+    dispatchers: FxHashMap<Inst, Dispatcher>,
 }
 
 impl<'a> Bytecode<'a> {
     #[instrument(name = "new_bytecode", level = "debug", skip_all)]
     pub(crate) fn new(mut code: &'a [u8], eof: Option<Cow<'a, Eof>>, spec_id: SpecId) -> Self {
-        if let Some(eof) = &eof {
-            code = unsafe {
-                std::slice::from_raw_parts(
-                    eof.body.code_section.first().unwrap().as_ptr(),
-                    eof.header.sum_code_sizes,
-                )
-            };
+        // The code sections are contiguous in the raw container, but not necessarily in
+        // `eof.body.code_section` if the container was not decoded from it.
+        let code_sections = |eof: &Eof| {
+            let start = eof.header.size() + eof.header.types_size as usize;
+            start..start + eof.header.sum_code_sizes
+        };
+        match &eof {
+            Some(Cow::Borrowed(eof)) => code = &eof.raw[code_sections(eof)],
+            Some(Cow::Owned(eof)) => {
+                let code_sections = &eof.raw[code_sections(eof)];
+                // SAFETY: The container is owned by `self.eof`, which is never modified, and
+                // moving it does not move the bytes of `raw`. `code` is never exposed outside of
+                // this crate with a lifetime longer than `&self`.
+                code = unsafe {
+                    std::slice::from_raw_parts(code_sections.as_ptr(), code_sections.len())
+                };
+            }
+            None => {}
         }
 
         let is_eof = eof.is_some();
@@ -110,6 +143,8 @@ impl<'a> Bytecode<'a> {
             may_suspend: false,
             pc_to_inst,
             eof_called_by: vec![],
+            unspent_section_gas: vec![],
+            dispatchers: FxHashMap::default(),
         };
 
         // Pad code to ensure there is at least one diverging instruction.
@@ -144,7 +179,6 @@ impl<'a> Bytecode<'a> {
 
     /// Returns the opcode at the given instruction counter.
     #[inline]
-    #[allow(dead_code)]
     pub(crate) fn opcode(&self, inst: Inst) -> Opcode<'_> {
         self.inst(inst).to_op_in(self)
     }
@@ -188,9 +222,13 @@ impl<'a> Bytecode<'a> {
     pub(crate) fn analyze(&mut self) -> Result<()> {
         if !self.is_eof() {
             self.static_jump_analysis();
-            // NOTE: `mark_dead_code` must run after `static_jump_analysis` as it can mark
-            // unreachable `JUMPDEST`s as dead code.
+            if self.has_dynamic_jumps {
+                self.dynamic_jump_analysis();
+            }
+            // NOTE: `mark_dead_code` must run after the jump analyses as it can mark unreachable
+            // `JUMPDEST`s as dead code.
             self.mark_dead_code();
+            self.dispatcher_analysis();
         }
 
         self.calc_may_suspend();
@@ -200,7 +238,12 @@ impl<'a> Bytecode<'a> {
             self.eof_mark_jumpdests();
         }
 
+        // NOTE: `range_analysis` must run before `construct_sections` as it can change the base
+        // gas of instructions, and before `stack_analysis` as narrowed instructions don't use the
+        // stack pointer.
+        self.range_analysis();
         self.construct_sections();
+        self.stack_analysis();
 
         Ok(())
     }
@@ -270,6 +313,27 @@ impl<'a> Bytecode<'a> {
         }
     }
 
+    /// Resolve the targets of the remaining dynamic jumps. See [`JumpAnalysis`].
+    #[instrument(name = "dj", level = "debug", skip_all)]
+    fn dynamic_jump_analysis(&mut self) {
+        debug_assert!(!self.is_eof());
+        JumpAnalysis::new(self).run(self);
+    }
+
+    /// Recognizes Solidity function dispatchers, which are translated to a `switch` over the
+    /// selector. See [`Dispatcher`].
+    #[instrument(name = "dispatch", level = "debug", skip_all)]
+    fn dispatcher_analysis(&mut self) {
+        debug_assert!(!self.is_eof());
+
+        for (jump_inst, dispatcher) in Dispatcher::find_all(self) {
+            trace!(jump_inst, arms = dispatcher.arms.len(), "found dispatcher");
+            self.insts[jump_inst].flags |= InstFlags::DISPATCH;
+            self.dispatchers.insert(jump_inst, dispatcher);
+        }
+        debug!(dispatchers = self.dispatchers.len(), "dispatch");
+    }
+
     /// Mark `RJUMP*` targets with `EOF_JUMPDEST` flag.
     #[instrument(name = "eof_sj", level = "debug", skip_all)]
     fn eof_mark_jumpdests(&mut self) {
@@ -304,7 +368,7 @@ impl<'a> Bytecode<'a> {
                 let mut end = i;
                 for (j, data) in &mut iter {
                     end = j;
-                    if data.is_reachable_jumpdest(false, self.has_dynamic_jumps) {
+                    if data.is_reachable_jumpdest(false) {
                         break;
                     }
                     data.flags |= InstFlags::DEAD_CODE;
@@ -330,6 +394,7 @@ impl<'a> Bytecode<'a> {
     /// Constructs the sections in the bytecode.
     #[instrument(name = "sections", level = "debug", skip_all)]
     fn construct_sections(&mut self) {
+        self.unspent_section_gas = vec![0; self.insts.len()];
         let mut analysis = SectionAnalysis::default();
         for inst in 0..self.insts.len() {
             if !self.inst(inst).is_dead_code() {
@@ -339,6 +404,29 @@ impl<'a> Bytecode<'a> {
         analysis.finish(self);
     }
 
+    /// Marks the instructions that can be computed in integer types narrower than a word, and the
+    /// `KECCAK256` instructions with a constant length.
+    #[instrument(name = "range", level = "debug", skip_all)]
+    fn range_analysis(&mut self) {
+        let mut analysis = RangeAnalysis::default();
+        let insts = self.iter_insts().map(|(inst, _)| inst).collect::<Vec<_>>();
+        for (i, &inst) in insts.iter().enumerate() {
+            analysis.process(self, inst, insts.get(i + 1).copied());
+        }
+        analysis.finish();
+    }
+
+    /// Marks the instructions that spill the stack values kept in registers.
+    #[instrument(name = "stack", level = "debug", skip_all)]
+    fn stack_analysis(&mut self) {
+        let mut analysis = StackAnalysis::default();
+        let insts = self.iter_insts().map(|(inst, _)| inst).collect::<Vec<_>>();
+        for (i, &inst) in insts.iter().enumerate() {
+            analysis.process(self, inst, insts.get(i + 1).copied());
+        }
+        analysis.finish();
+    }
+
     /// Constructs the sections in the bytecode.
     #[instrument(name = "sections", level = "debug", skip_all)]
     #[cfg(any())]
@@ -386,6 +474,9 @@ impl<'a> Bytecode<'a> {
 
                     let imm = self.get_imm(data).unwrap();
                     let target_section = u16::from_be_bytes(imm.try_into().unwrap()) as usize;
+                    if source_section == target_section {
+                        continue;
+                    }
 
                     let (source_section, target_section) =
                         get_two_mut(&mut eof_called_by, source_section, target_section);
@@ -409,6 +500,30 @@ impl<'a> Bytecode<'a> {
         Ok(())
     }
 
+    /// Builds the control-flow graph of the analyzed bytecode.
+    pub(crate) fn cfg(&self) -> Cfg {
+        Cfg::new(self)
+    }
+
+    /// Writes the source file used for debug info: line `pc + 1` is the instruction at `pc`, and
+    /// the lines of immediate bytes are left empty.
+    pub(crate) fn write_debug_source(&self, w: &mut impl io::Write) -> io::Result<()> {
+        let mut line = 0;
+        for (pc, opcode) in self.opcodes().with_pc() {
+            for _ in line..pc {
+                writeln!(w)?;
+            }
+            writeln!(w, "{pc:>6}: {opcode}")?;
+            line = pc + 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the function dispatcher whose first arm ends with the given `JUMPI` instruction.
+    pub(crate) fn dispatcher(&self, inst: Inst) -> Option<&Dispatcher> {
+        self.dispatchers.get(&inst)
+    }
+
     /// Returns the list of instructions that call the given EOF section.
     pub(crate) fn eof_section_called_by(&self, section: usize) -> &[Inst] {
         &self.eof_called_by[section]
@@ -438,6 +553,30 @@ impl<'a> Bytecode<'a> {
         self.has_dynamic_jumps
     }
 
+    /// Returns the number of instructions, including dead code.
+    pub(crate) fn num_insts(&self) -> usize {
+        self.insts.len()
+    }
+
+    /// Returns the number of `JUMPDEST`s that dynamic jumps may branch to, which is the size of
+    /// the dynamic jump table.
+    pub(crate) fn num_dynamic_jump_targets(&self) -> usize {
+        if !self.has_dynamic_jumps {
+            return 0;
+        }
+        self.iter_insts()
+            .filter(|(_, data)| data.flags.contains(InstFlags::DYNAMIC_JUMPDEST))
+            .count()
+    }
+
+    /// Returns the static gas of the instructions in the section of `inst` that come after it.
+    ///
+    /// This is the amount of gas that was paid in advance at the start of the section but not
+    /// spent if execution stops at `inst`.
+    pub(crate) fn unspent_section_gas(&self, inst: Inst) -> u64 {
+        self.unspent_section_gas[inst] as u64
+    }
+
     /// Returns `true` if the bytecode may suspend execution, to be resumed later.
     pub(crate) fn may_suspend(&self) -> bool {
         self.may_suspend
@@ -478,10 +617,8 @@ impl<'a> Bytecode<'a> {
 
     /// Returns the program counter of the given EOF section index.
     pub(crate) fn eof_section_pc(&self, section: usize) -> Inst {
-        let code = &self.expect_eof().body.code_section;
-        let first = code.first().unwrap().as_ptr();
-        let section_ptr = code[section].as_ptr();
-        section_ptr as usize - first as usize
+        let code_sizes = &self.expect_eof().header.code_sizes;
+        code_sizes[..section].iter().map(|&size| size as usize).sum()
     }
 
     /// Returns the first instruction of the given EOF section index.
@@ -489,6 +626,82 @@ impl<'a> Bytecode<'a> {
         self.pc_to_inst(self.eof_section_pc(section))
     }
 
+    /// Returns the instructions of the given EOF section index.
+    pub(crate) fn eof_section_insts(&self, section: usize) -> Range<Inst> {
+        let end = if section + 1 < self.expect_eof().body.code_section.len() {
+            self.eof_section_inst(section + 1)
+        } else {
+            self.insts.len()
+        };
+        self.eof_section_inst(section)..end
+    }
+
+    /// Returns `true` if each EOF code section can be translated into its own function.
+    ///
+    /// This requires more than one code section, none of which except the first may suspend
+    /// execution, as resuming is only supported in the entry function, and no `JUMPF` to the first
+    /// code section, as it is the entry function.
+    ///
+    /// `JUMPF`s between different code sections must also not form a cycle, as each of them is a
+    /// call that is not guaranteed to be a tail call, and would overflow the native stack.
+    /// `JUMPF` to the current code section is a branch instead.
+    pub(crate) fn supports_eof_section_functions(&self) -> bool {
+        let Some(eof) = self.eof.as_deref() else { return false };
+        if eof.body.code_section.len() <= 1 {
+            return false;
+        }
+        let supported = self.insts[self.eof_section_inst(1)..].iter().all(|data| {
+            let jumpf_to_entry =
+                data.opcode == op::JUMPF && self.get_imm(data).is_some_and(|imm| imm == [0, 0]);
+            !data.may_suspend(true) && !jumpf_to_entry
+        });
+        supported && !self.has_eof_jumpf_cycle()
+    }
+
+    /// Returns `true` if the `JUMPF`s between different EOF code sections form a cycle.
+    fn has_eof_jumpf_cycle(&self) -> bool {
+        let code_sections_len = self.expect_eof().body.code_section.len();
+        let mut jumpf_targets = vec![Vec::new(); code_sections_len];
+        for (_, data) in self.iter_all_insts() {
+            if data.opcode != op::JUMPF {
+                continue;
+            }
+            let imm = self.get_imm(data).unwrap();
+            let source_section = self.pc_to_eof_section(data.pc as usize);
+            let target_section = u16::from_be_bytes(imm.try_into().unwrap()) as usize;
+            if source_section != target_section {
+                jumpf_targets[source_section].push(target_section);
+            }
+        }
+
+        // Depth-first search, where `on_path` contains the sections of the current path.
+        fn visit(
+            section: usize,
+            jumpf_targets: &[Vec<usize>],
+            visited: &mut BitVec,
+            on_path: &mut BitVec,
+        ) -> bool {
+            if on_path[section] {
+                return true;
+            }
+            if visited[section] {
+                return false;
+            }
+            visited.set(section, true);
+            on_path.set(section, true);
+            let has_cycle = jumpf_targets[section]
+                .iter()
+                .any(|&target| visit(target, jumpf_targets, visited, on_path));
+            on_path.set(section, false);
+            has_cycle
+        }
+
+        let mut visited = BitVec::repeat(false, code_sections_len);
+        let mut on_path = BitVec::repeat(false, code_sections_len);
+        (0..code_sections_len)
+            .any(|section| visit(section, &jumpf_targets, &mut visited, &mut on_path))
+    }
+
     pub(crate) fn pc_to_eof_section(&self, pc: usize) -> usize {
         (0..self.expect_eof().body.code_section.len())
             .rev()
@@ -617,11 +830,15 @@ pub(crate) struct InstData {
     /// The base gas cost of the opcode.
     ///
     /// This may not be the final/full gas cost of the opcode as it may also have a dynamic cost.
-    base_gas: u16,
+    /// Dynamic costs that are known statically are included, see `RangeAnalysis`.
+    pub(crate) base_gas: u16,
     /// Instruction-specific data:
     /// - if the instruction has immediate data, this is a packed offset+length into the bytecode;
     /// - `JUMP{,I} && STATIC_JUMP in kind`: the jump target, `Instr`;
-    /// - `JUMPDEST`: `1` if the jump destination is reachable, `0` otherwise;
+    /// - `JUMPDEST`: `1` if the jump destination is the target of a static jump, `0` otherwise;
+    /// - `NARROW in flags`: the bit width bounds of the inputs, see
+    ///   [`input_bits`](Self::input_bits);
+    /// - `KECCAK256 && CONST_LEN in flags`: the constant length, `32` or `64`;
     /// - otherwise: no meaning.
     pub(crate) data: u32,
     /// The program counter, meaning `code[pc]` is this instruction's opcode.
@@ -675,6 +892,7 @@ impl InstData {
     pub(crate) fn stack_io(&self) -> (u8, u8) {
         let (mut inp, out) = stack_io(self.opcode);
         if self.is_legacy_static_jump()
+            && !self.flags.contains(InstFlags::RESOLVED_JUMP)
             && !(self.opcode == op::JUMPI && self.flags.contains(InstFlags::INVALID_JUMP))
         {
             inp -= 1;
@@ -682,6 +900,15 @@ impl InstData {
         (inp, out)
     }
 
+    /// Returns the upper bounds on the bit widths of the inputs of a `NARROW` instruction, in pop
+    /// order. Missing inputs are `256`.
+    #[inline]
+    pub(crate) fn input_bits(&self) -> [u16; 3] {
+        debug_assert!(self.flags.contains(InstFlags::NARROW));
+        let [a, b, c, _] = self.data.to_le_bytes();
+        [a, b, c].map(|bits| bits as u16 + 1)
+    }
+
     /// Converts this instruction to a raw opcode. Note that the immediate data is not resolved.
     #[inline]
     pub(crate) const fn to_op(&self) -> Opcode<'static> {
@@ -738,15 +965,12 @@ impl InstData {
 
     /// Returns `true` if this instruction is a reachable `JUMPDEST`.
     #[inline]
-    pub(crate) const fn is_reachable_jumpdest(
-        &self,
-        is_eof: bool,
-        has_dynamic_jumps: bool,
-    ) -> bool {
+    pub(crate) const fn is_reachable_jumpdest(&self, is_eof: bool) -> bool {
         if is_eof {
             self.flags.contains(InstFlags::EOF_JUMPDEST)
         } else {
-            self.is_jumpdest() && (has_dynamic_jumps || self.data == 1)
+            self.is_jumpdest()
+                && (self.data == 1 || self.flags.contains(InstFlags::DYNAMIC_JUMPDEST))
         }
     }
 
@@ -786,6 +1010,50 @@ impl InstData {
             || (is_eof && matches!(self.opcode, op::JUMPF | op::RETF | op::RETURNCONTRACT))
     }
 
+    /// Returns `true` if this instruction passes the stack pointer to a builtin, which reads the
+    /// inputs from and writes the outputs to the stack in memory.
+    #[inline]
+    pub(crate) const fn uses_stack_pointer(&self, is_eof: bool) -> bool {
+        if self.flags.intersects(InstFlags::NARROW.union(InstFlags::CONST_LEN)) {
+            return false;
+        }
+        matches!(
+            self.opcode,
+            op::ADDMOD
+                | op::MULMOD
+                | op::EXP
+                | op::KECCAK256
+                | op::BALANCE
+                | op::CALLDATACOPY
+                | op::CODECOPY
+                | op::GASPRICE
+                | op::EXTCODESIZE
+                | op::EXTCODECOPY
+                | op::RETURNDATACOPY
+                | op::EXTCODEHASH
+                | op::BLOCKHASH
+                | op::DIFFICULTY
+                | op::SELFBALANCE
+                | op::BLOBHASH
+                | op::BLOBBASEFEE
+                | op::SLOAD
+                | op::SSTORE
+                | op::TLOAD
+                | op::TSTORE
+                | op::MCOPY
+                | op::LOG0
+                ..=op::LOG4
+                    | op::DATALOAD
+                    | op::DATACOPY
+                    | op::EOFCREATE
+                    | op::RETURNCONTRACT
+                    | op::RETURN
+                    | op::REVERT
+                    | op::RETURNDATALOAD
+                    | op::SELFDESTRUCT
+        ) || self.may_suspend(is_eof)
+    }
+
     /// Returns `true` if this instruction may suspend execution.
     #[inline]
     pub(crate) const fn may_suspend(&self, is_eof: bool) -> bool {
@@ -816,7 +1084,7 @@ impl InstData {
 bitflags::bitflags! {
     /// [`InstrData`] flags.
     #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-    pub(crate) struct InstFlags: u8 {
+    pub(crate) struct InstFlags: u16 {
         /// The `JUMP`/`JUMPI` target is known at compile time.
         /// This is implied for other jump instructions which are always static.
         const STATIC_JUMP = 1 << 0;
@@ -840,6 +1108,27 @@ bitflags::bitflags! {
         const SKIP_LOGIC = 1 << 6;
         /// Don't generate any code.
         const DEAD_CODE = 1 << 7;
+
+        /// The stack values that are kept in registers must be spilled to the stack in memory.
+        /// See `StackAnalysis`.
+        const SPILL_STACK = 1 << 8;
+
+        /// The `JUMP`/`JUMPI` target was resolved by [`JumpAnalysis`] rather than being pushed by
+        /// the previous instruction. Unlike other static jumps, the target is still popped from the
+        /// stack.
+        const RESOLVED_JUMP = 1 << 9;
+        /// The `JUMPDEST` may be the target of a dynamic jump.
+        const DYNAMIC_JUMPDEST = 1 << 10;
+
+        /// The inputs of the instruction are small enough for it to be computed in a narrower
+        /// integer type than a word. See `RangeAnalysis`.
+        const NARROW = 1 << 11;
+        /// The `KECCAK256` length is a known constant, which is small enough for the hash to be
+        /// computed without the generic builtin. See `RangeAnalysis`.
+        const CONST_LEN = 1 << 12;
+
+        /// The `JUMPI` ends the first arm of a function dispatcher. See [`Dispatcher`].
+        const DISPATCH = 1 << 13;
     }
 }
 
@@ -861,10 +1150,198 @@ fn get_two_mut<T>(sl: &mut [T], idx_1: usize, idx_2: usize) -> (&mut T, &mut T)
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use super::{cfg::CfgEdgeKind, *};
 
     #[test]
     fn test_suspend_is_free() {
         assert_eq!(op::OPCODE_INFO_JUMPTABLE[TEST_SUSPEND as usize], None);
     }
+
+    fn analyze(code: &[u8]) -> Bytecode<'_> {
+        let mut bytecode = Bytecode::new(code, None, SpecId::CANCUN);
+        bytecode.analyze().unwrap();
+        bytecode
+    }
+
+    #[test]
+    fn eof_code() {
+        use revm_primitives::{
+            eof::{EofBody, TypesSection},
+            Bytes,
+        };
+
+        // The code sections are not contiguous in memory.
+        let eof = EofBody {
+            types_section: vec![
+                TypesSection { inputs: 0, outputs: 0x80, max_stack_size: 0 },
+                TypesSection { inputs: 0, outputs: 0, max_stack_size: 0 },
+            ],
+            code_section: vec![
+                Bytes::from_static(&[op::CALLF, 0x00, 0x01, op::STOP]),
+                Bytes::from_static(&[op::RETF]),
+            ],
+            container_section: vec![],
+            data_section: Bytes::new(),
+            is_data_filled: true,
+        }
+        .into_eof();
+        for eof in [Cow::Borrowed(&eof), Cow::Owned(eof.clone())] {
+            let bytecode = Bytecode::new(&[], Some(eof), SpecId::PRAGUE_EOF);
+            assert_eq!(bytecode.code, [op::CALLF, 0x00, 0x01, op::STOP, op::RETF]);
+            assert_eq!(bytecode.eof_section_pc(1), 4);
+        }
+    }
+
+    #[test]
+    fn resolve_internal_function_return() {
+        #[rustfmt::skip]
+        let code = [
+            op::PUSH1, 5, op::PUSH1, 9, op::JUMP,
+            op::JUMPDEST, op::PUSH1, 69, op::STOP,
+            op::JUMPDEST, op::JUMP,
+        ];
+        let bytecode = analyze(&code);
+        assert!(!bytecode.has_dynamic_jumps());
+        let ret = bytecode.inst(7);
+        assert!(ret.flags.contains(InstFlags::STATIC_JUMP | InstFlags::RESOLVED_JUMP));
+        assert_eq!(ret.data, 3);
+        assert_eq!(ret.stack_io(), (1, 0));
+    }
+
+    #[test]
+    fn range_analysis() {
+        #[rustfmt::skip]
+        let code = [
+            op::PUSH1, 0xff, op::CALLDATASIZE, op::ADD,
+            op::PUSH0, op::CALLDATALOAD, op::ADD,
+            op::PUSH1, 2, op::DUP2, op::EXP,
+            op::STOP,
+        ];
+        let bytecode = analyze(&code);
+        let add = bytecode.inst(2);
+        assert!(add.flags.contains(InstFlags::NARROW));
+        assert_eq!(add.input_bits(), [64, 8, 256]);
+        assert!(!bytecode.inst(5).flags.contains(InstFlags::NARROW));
+        let exp = bytecode.inst(8);
+        assert!(exp.flags.contains(InstFlags::NARROW));
+        assert_eq!(exp.input_bits(), [256, 2, 256]);
+        assert!(!exp.uses_stack_pointer(false));
+    }
+
+    #[test]
+    fn dispatcher() {
+        #[rustfmt::skip]
+        let code = [
+            op::PUSH0, op::CALLDATALOAD, op::PUSH1, 0xe0, op::SHR,
+            op::DUP1, op::PUSH4, 0x06, 0xfd, 0xde, 0x03, op::EQ, op::PUSH1, 35, op::JUMPI,
+            op::PUSH4, 0x09, 0x5e, 0xa7, 0xb3, op::DUP2, op::EQ, op::PUSH1, 37, op::JUMPI,
+            op::DUP1, op::PUSH3, 0x18, 0x16, 0x0d, op::EQ, op::PUSH1, 39, op::JUMPI,
+            op::STOP,
+            op::JUMPDEST, op::STOP,
+            op::JUMPDEST, op::STOP,
+            op::JUMPDEST, op::STOP,
+        ];
+        let bytecode = analyze(&code);
+        assert!(bytecode.inst(8).flags.contains(InstFlags::DISPATCH));
+        assert!(!bytecode.inst(13).flags.contains(InstFlags::DISPATCH));
+        let dispatcher = bytecode.dispatcher(8).unwrap();
+        let arms = dispatcher
+            .arms
+            .iter()
+            .map(|arm| (arm.selector, arm.target, arm.gas))
+            .collect::<Vec<_>>();
+        assert_eq!(arms, [(0x06fdde03, 20, 22), (0x095ea7b3, 22, 22), (0x0018160d, 24, 22)]);
+        assert_eq!(dispatcher.fallthrough, 19);
+    }
+
+    #[test]
+    fn limit_dynamic_jump_targets() {
+        #[rustfmt::skip]
+        let code = [
+            op::PUSH1, 7, op::PUSH1, 1, op::PUSH1, 17, op::JUMP,
+            op::JUMPDEST, op::PUSH1, 15, op::PUSH1, 2, op::PUSH1, 17, op::JUMP,
+            op::JUMPDEST, op::STOP,
+            op::JUMPDEST, op::SWAP1, op::JUMP,
+        ];
+        let bytecode = analyze(&code);
+        assert!(bytecode.has_dynamic_jumps());
+        assert!(!bytecode.inst(13).flags.contains(InstFlags::STATIC_JUMP));
+        let targets = bytecode
+            .iter_insts()
+            .filter(|(_, data)| data.flags.contains(InstFlags::DYNAMIC_JUMPDEST))
+            .map(|(inst, _)| inst)
+            .collect::<Vec<_>>();
+        assert_eq!(targets, [4, 9]);
+        assert_eq!(bytecode.num_dynamic_jump_targets(), 2);
+    }
+
+    #[test]
+    fn cfg_edges() {
+        #[rustfmt::skip]
+        let code = [
+            op::PUSH1, 5, op::PUSH1, 9, op::JUMP,
+            op::JUMPDEST, op::PUSH1, 69, op::STOP,
+            op::JUMPDEST, op::JUMP,
+        ];
+        let bytecode = analyze(&code);
+        let cfg = bytecode.cfg();
+        let blocks = cfg
+            .blocks
+            .iter()
+            .map(|block| {
+                let succs = block.succs.iter().map(|edge| (edge.target, edge.kind));
+                (block.start, block.end, succs.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            [
+                (0, 2, vec![(2, CfgEdgeKind::Jump)]),
+                (3, 5, vec![]),
+                (6, 7, vec![(1, CfgEdgeKind::Jump)]),
+                (8, 8, vec![]),
+            ]
+        );
+        assert_eq!(cfg.blocks[0].section.gas_cost, 14);
+
+        let mut dot = Vec::new();
+        cfg.write_dot(&bytecode, &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("bb2 -> bb1 [label=\"jump\""), "{dot}");
+
+        let mut json = Vec::new();
+        cfg.write_json(&bytecode, &mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains("\"succs\": [{\"target\": 1, \"kind\": \"jump\"}]"), "{json}");
+    }
+
+    #[test]
+    fn debug_source_lines() {
+        let code = [op::PUSH1, 0x42, op::PUSH2, 0x01, 0x02, op::ADD, op::STOP];
+        let bytecode = analyze(&code);
+        let mut source = Vec::new();
+        bytecode.write_debug_source(&mut source).unwrap();
+        let source = String::from_utf8(source).unwrap();
+        let lines = source.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "     0: PUSH1 0x42",
+                "",
+                "     2: PUSH2 0x0102",
+                "",
+                "",
+                "     5: ADD",
+                "     6: STOP",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_jump_targets() {
+        let code = [op::PUSH0, op::CALLDATALOAD, op::JUMP, op::JUMPDEST, op::STOP];
+        let bytecode = analyze(&code);
+        assert!(bytecode.has_dynamic_jumps());
+        assert!(bytecode.inst(3).flags.contains(InstFlags::DYNAMIC_JUMPDEST));
+    }
 }
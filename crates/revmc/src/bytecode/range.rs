@@ -0,0 +1,190 @@
+use super::{Bytecode, Inst, InstData, InstFlags};
+use revm_interpreter::{gas, opcode as op};
+
+/// The number of bits of a word.
+const WORD_BITS: u16 = 256;
+
+/// Value range analysis.
+///
+/// Every value is bounded by `0..2^bits`, where `bits` is an upper bound on its number of
+/// significant bits, at least `1`. This pass simulates the stack symbolically within each section
+/// to compute these bounds from the instructions that push the values, such as `PUSH`, comparisons,
+/// masks, and environment fields that are narrower than a word.
+///
+/// Arithmetic and comparison instructions whose inputs are small enough to be computed in a
+/// narrower integer type are marked with [`InstFlags::NARROW`], and the bounds of their inputs
+/// are stored in their `data`. See [`InstData::input_bits`].
+///
+/// `KECCAK256` instructions that hash a constant 32 or 64 bytes are marked with
+/// [`InstFlags::CONST_LEN`], and the length is stored in their `data`. Their dynamic gas cost is
+/// known, so it is added to their base gas.
+///
+/// Values that are on the stack at the start of a section are not known. The stack is reset
+/// where `StackAnalysis` ends a section, so the bounds never flow across control flow joins.
+#[derive(Default)]
+pub(crate) struct RangeAnalysis {
+    /// The ranges of the values at the top of the stack, with the topmost value last.
+    /// The values below are not known.
+    stack: Vec<Range>,
+
+    /// The number of instructions that were narrowed.
+    narrowed: usize,
+}
+
+/// The known range of a value.
+#[derive(Clone, Copy, Debug)]
+struct Range {
+    /// An upper bound on the number of significant bits, at least `1`.
+    bits: u16,
+    /// The value, if it is a constant that fits in 64 bits.
+    value: Option<u64>,
+}
+
+impl Range {
+    const UNKNOWN: Self = Self { bits: WORD_BITS, value: None };
+
+    fn bits(bits: u16) -> Self {
+        Self { bits: bits.clamp(1, WORD_BITS), value: None }
+    }
+}
+
+impl RangeAnalysis {
+    /// Process a single instruction. `next` is the next instruction that is not dead code, if any.
+    pub(crate) fn process(&mut self, bytecode: &mut Bytecode<'_>, inst: Inst, next: Option<Inst>) {
+        let is_eof = bytecode.is_eof();
+        let data = bytecode.inst(inst);
+        let opcode = data.opcode;
+
+        if data.flags.contains(InstFlags::SKIP_LOGIC) {
+            // No stack operations are generated.
+        } else if let op::DUP1..=op::DUP16 = opcode {
+            let range = self.peek((opcode - op::DUP1) as usize);
+            self.stack.push(range);
+        } else if let op::SWAP1..=op::SWAP16 = opcode {
+            let n = (opcode - op::SWAP1) as usize + 1;
+            self.reserve(n + 1);
+            let len = self.stack.len();
+            self.stack.swap(len - 1, len - 1 - n);
+        } else if let op::DUPN | op::SWAPN | op::EXCHANGE = opcode {
+            // Not worth tracking.
+            self.stack.clear();
+        } else {
+            let (inp, out) = data.stack_io();
+            let mut inputs = [Range::UNKNOWN; 3];
+            for i in 0..inp as usize {
+                let range = self.stack.pop().unwrap_or(Range::UNKNOWN);
+                if let Some(input) = inputs.get_mut(i) {
+                    *input = range;
+                }
+            }
+            if out > 0 {
+                let range = output_range(bytecode, data, inputs.map(|range| range.bits));
+                self.stack.extend(std::iter::repeat(Range::UNKNOWN).take(out as usize - 1));
+                self.stack.push(range);
+            }
+
+            let bits = inputs.map(|range| range.bits);
+            if is_narrow(opcode, bits) {
+                trace!(inst, ?bits, "narrow");
+                let data = bytecode.inst_mut(inst);
+                data.flags |= InstFlags::NARROW;
+                let [a, b, c] = bits.map(|bits| (bits - 1) as u8);
+                data.data = u32::from_le_bytes([a, b, c, 0]);
+                self.narrowed += 1;
+            }
+
+            // `KECCAK256(offset, len)`.
+            if let (op::KECCAK256, Some(len @ (32 | 64))) = (opcode, inputs[1].value) {
+                trace!(inst, len, "const len");
+                let data = bytecode.inst_mut(inst);
+                data.flags |= InstFlags::CONST_LEN;
+                data.data = len as u32;
+                data.base_gas += gas::KECCAK256WORD as u16 * (len / 32) as u16;
+            }
+        }
+
+        let data = bytecode.inst(inst);
+        let ends_section = data.is_branching(is_eof)
+            || data.may_suspend(is_eof)
+            || data.opcode == op::CALLF
+            || next.map_or(true, |next| bytecode.inst(next).is_reachable_jumpdest(is_eof));
+        if ends_section {
+            self.stack.clear();
+        }
+    }
+
+    /// Finishes the analysis.
+    pub(crate) fn finish(self) {
+        debug!(narrowed = self.narrowed, "range");
+    }
+
+    /// Returns the range of the `n`th value from the top of the stack.
+    fn peek(&self, n: usize) -> Range {
+        self.stack.len().checked_sub(n + 1).map_or(Range::UNKNOWN, |i| self.stack[i])
+    }
+
+    /// Makes sure that the topmost `n` values are tracked, filling in unknown values.
+    fn reserve(&mut self, n: usize) {
+        if let Some(missing) = n.checked_sub(self.stack.len()) {
+            self.stack.splice(0..0, std::iter::repeat(Range::UNKNOWN).take(missing));
+        }
+    }
+}
+
+/// Returns the range of the value pushed by `data`, given the bounds of its inputs in pop order.
+fn output_range(bytecode: &Bytecode<'_>, data: &InstData, [a, b, c]: [u16; 3]) -> Range {
+    let bits = match data.opcode {
+        op::PUSH0..=op::PUSH32 => {
+            let imm = bytecode.get_imm(data).unwrap_or_default();
+            let imm = &imm[imm.iter().position(|&byte| byte != 0).unwrap_or(imm.len())..];
+            let mut range = Range::bits((imm.len() * 8) as u16);
+            if let Some(&first) = imm.first() {
+                range.bits -= first.leading_zeros() as u16;
+            }
+            if imm.len() <= 8 {
+                range.value = Some(imm.iter().fold(0, |acc, &byte| acc << 8 | byte as u64));
+            }
+            return range;
+        }
+        op::LT | op::GT | op::SLT | op::SGT | op::EQ | op::ISZERO => 1,
+        op::AND => a.min(b),
+        op::OR | op::XOR => a.max(b),
+        op::ADD => a.max(b) + 1,
+        op::MUL => a + b,
+        op::DIV => a,
+        op::MOD => a.min(b),
+        op::ADDMOD | op::MULMOD => c,
+        // `SHR(shift, value)`.
+        op::SHR => b,
+        op::BYTE => 8,
+        op::ADDRESS | op::ORIGIN | op::CALLER | op::COINBASE => 160,
+        op::CALLDATASIZE
+        | op::RETURNDATASIZE
+        | op::CODESIZE
+        | op::MSIZE
+        | op::PC
+        | op::GAS
+        | op::CHAINID => 64,
+        _ => WORD_BITS,
+    };
+    Range::bits(bits)
+}
+
+/// Returns `true` if the instruction can be computed in a narrower integer type than a word, given
+/// the bounds of its inputs in pop order.
+///
+/// See `FunctionCx::build_narrow_op`.
+fn is_narrow(opcode: u8, [a, b, _]: [u16; 3]) -> bool {
+    match opcode {
+        op::ADD => a.max(b) < 128,
+        op::MUL => a + b <= 128,
+        op::LT | op::GT | op::EQ => a.max(b) <= 128,
+        op::DIV | op::MOD => a.max(b) <= 64,
+        // The sum or product of the first two inputs fits in a word.
+        op::ADDMOD => a.max(b) < WORD_BITS,
+        op::MULMOD => a + b <= WORD_BITS,
+        // `EXP(base, exponent)`.
+        op::EXP => b <= 8,
+        _ => false,
+    }
+}
@@ -1,5 +1,6 @@
 use super::Bytecode;
 use core::fmt;
+use revm_interpreter::opcode as op;
 
 // TODO: Separate gas sections from stack length sections.
 // E.g. `GAS` should stop only a gas section because it requires `gasleft`, and execution will
@@ -11,13 +12,14 @@ use core::fmt;
 /// This would be better named "block" but it's already used in the context of the basic block
 /// analysis.
 #[derive(Clone, Copy, Default, PartialEq, Eq)]
-pub(crate) struct Section {
+#[non_exhaustive]
+pub struct Section {
     /// The total base gas cost of all instructions in the section.
-    pub(crate) gas_cost: u32,
+    pub gas_cost: u32,
     /// The stack height required to execute the section.
-    pub(crate) inputs: u16,
+    pub inputs: u16,
     /// The maximum stack height growth relative to the stack height at section start.
-    pub(crate) max_growth: i16,
+    pub max_growth: i16,
 }
 
 impl fmt::Debug for Section {
@@ -37,7 +39,7 @@ impl fmt::Debug for Section {
 impl Section {
     /// Returns `true` if the section is empty.
     #[inline]
-    pub(crate) fn is_empty(self) -> bool {
+    pub fn is_empty(self) -> bool {
         self == Self::default()
     }
 }
@@ -59,7 +61,7 @@ impl SectionAnalysis {
         let is_eof = bytecode.is_eof();
 
         // JUMPDEST starts a section.
-        if bytecode.inst(inst).is_reachable_jumpdest(is_eof, bytecode.has_dynamic_jumps()) {
+        if bytecode.inst(inst).is_reachable_jumpdest(is_eof) {
             self.save_to(bytecode, inst);
             self.reset(inst);
         }
@@ -73,11 +75,12 @@ impl SectionAnalysis {
 
         self.gas_cost += data.base_gas as u64;
 
-        // Instructions that require `gasleft` and branching instructions end a section, starting a
-        // new one on the next instruction, if any.
+        // Instructions that require `gasleft`, branching instructions, and `CALLF` end a section,
+        // starting a new one on the next instruction, if any.
         if (!is_eof && data.requires_gasleft(bytecode.spec_id))
             || data.may_suspend(is_eof)
             || data.is_branching(is_eof)
+            || (is_eof && data.opcode == op::CALLF)
         {
             let next = inst + 1;
             self.save_to(bytecode, next);
@@ -87,7 +90,7 @@ impl SectionAnalysis {
 
     /// Finishes the analysis.
     pub(crate) fn finish(self, bytecode: &mut Bytecode<'_>) {
-        self.save_to(bytecode, bytecode.insts.len() - 1);
+        self.save_to(bytecode, bytecode.insts.len());
         if enabled!(tracing::Level::DEBUG) {
             let mut max_len = 0;
             let mut current = 0;
@@ -122,6 +125,20 @@ impl SectionAnalysis {
             if let Some(inst) = insts.find(|inst| !inst.is_dead_code()) {
                 inst.section = section;
             }
+
+            // The gas left unspent by stopping at an instruction is the section's total minus the
+            // prefix sum up to and including that instruction.
+            let range = self.start_inst..next_section_inst.min(bytecode.insts.len());
+            let mut prefix = 0u64;
+            for inst in range {
+                let data = &bytecode.insts[inst];
+                if data.is_dead_code() {
+                    continue;
+                }
+                prefix += data.base_gas as u64;
+                let unspent = self.gas_cost.saturating_sub(prefix);
+                bytecode.unspent_section_gas[inst] = unspent.try_into().unwrap_or(u32::MAX);
+            }
         }
     }
 
@@ -0,0 +1,69 @@
+use super::{Bytecode, Inst, InstFlags};
+use revm_interpreter::opcode as op;
+
+/// Stack-to-SSA promotion analysis.
+///
+/// The translator keeps the values that instructions push in SSA registers instead of storing them
+/// to the EVM stack, and only loads from the EVM stack the values that are not in registers.
+///
+/// This pass simulates the stack symbolically to find the instructions that must spill the values
+/// kept in registers to the EVM stack, marking them with [`InstFlags::SPILL_STACK`]:
+/// - instructions that pass the stack pointer to a builtin, as the builtin reads its inputs from,
+///   and writes its outputs to, the EVM stack;
+/// - instructions that end a section where control flow may join with other paths: jumps, diverging
+///   instructions, suspends, `CALLF`, and instructions that fall through into a jump destination.
+///
+/// Since values only become registers when pushed, and builtins that use the stack pointer write
+/// their outputs to the EVM stack, the registers are always at the top of the stack. The symbolic
+/// stack is therefore just the number of topmost values that may be in registers.
+#[derive(Default)]
+pub(crate) struct StackAnalysis {
+    /// The number of values at the top of the stack that may be kept in registers.
+    registers: usize,
+
+    /// The number of instructions that spill.
+    spills: usize,
+    /// The number of values that were pushed into registers.
+    pushed: usize,
+}
+
+impl StackAnalysis {
+    /// Process a single instruction. `next` is the next instruction that is not dead code, if any.
+    pub(crate) fn process(&mut self, bytecode: &mut Bytecode<'_>, inst: Inst, next: Option<Inst>) {
+        let is_eof = bytecode.is_eof();
+        let data = bytecode.inst(inst);
+
+        let mut spill = false;
+        if data.flags.contains(InstFlags::SKIP_LOGIC) {
+            // No stack operations are generated.
+        } else if data.uses_stack_pointer(is_eof) {
+            spill = self.registers > 0;
+            self.registers = 0;
+        } else {
+            let (inp, out) = data.stack_io();
+            self.registers = self.registers.saturating_sub(inp as usize) + out as usize;
+            self.pushed += out as usize;
+        }
+
+        let ends_section = data.is_branching(is_eof)
+            || data.may_suspend(is_eof)
+            || data.opcode == op::CALLF
+            || next.map_or(true, |next| bytecode.inst(next).is_reachable_jumpdest(is_eof));
+        if ends_section {
+            spill |= self.registers > 0;
+            self.registers = 0;
+        }
+
+        if spill {
+            trace!(inst, "spill");
+            bytecode.inst_mut(inst).flags |= InstFlags::SPILL_STACK;
+            self.spills += 1;
+        }
+    }
+
+    /// Finishes the analysis.
+    pub(crate) fn finish(self) {
+        debug_assert_eq!(self.registers, 0, "stack values left in registers");
+        debug!(spills = self.spills, pushed = self.pushed, "stack");
+    }
+}
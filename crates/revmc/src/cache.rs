@@ -0,0 +1,216 @@
+//! Persistent on-disk cache for compiled objects.
+
+use revm_primitives::{hex, keccak256, B256};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// The compiler version that is used to namespace the cache.
+///
+/// Includes a fingerprint of the compiler sources computed in `build.rs`, so that objects compiled
+/// by a different build of the compiler are never loaded, even if the version was not bumped.
+const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "-", env!("REVMC_CODEGEN_FINGERPRINT"));
+
+/// The file extension of cached objects.
+const EXTENSION: &str = "o";
+
+/// Key of a compiled object in an [`ObjectCache`].
+///
+/// Use [`EvmCompiler::cache_key`](crate::EvmCompiler::cache_key) to create a key that accounts for
+/// all the inputs that affect code generation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey(B256);
+
+impl fmt::Debug for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CacheKey({self})")
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl CacheKey {
+    /// Creates a new key by hashing the given parts together with the compiler version.
+    ///
+    /// Each part is length-prefixed, so that different partitions of the same bytes produce
+    /// different keys.
+    pub fn from_parts<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut buf = Vec::with_capacity(256);
+        for part in std::iter::once(VERSION.as_bytes()).chain(parts) {
+            buf.extend_from_slice(&(part.len() as u64).to_le_bytes());
+            buf.extend_from_slice(part);
+        }
+        Self(keccak256(buf))
+    }
+
+    /// Returns the key as a hash.
+    #[inline]
+    pub const fn as_b256(&self) -> &B256 {
+        &self.0
+    }
+
+    /// Returns the name of the function symbol that is defined by the object with this key.
+    pub fn symbol_name(&self) -> String {
+        format!("revmc_cached_{self}")
+    }
+
+    fn file_name(&self) -> String {
+        format!("{self}.{EXTENSION}")
+    }
+}
+
+/// A persistent on-disk cache for compiled objects.
+///
+/// Objects are stored in a subdirectory of the cache root named after the compiler version and
+/// build, so upgrading or modifying the compiler automatically invalidates all previously cached
+/// objects. Stale
+/// directories can be removed with [`remove_stale`](Self::remove_stale).
+///
+/// Writes are atomic, so multiple processes can share the same cache directory.
+///
+/// See [`EvmCompiler::jit_cached`](crate::EvmCompiler::jit_cached) for loading cached objects into
+/// a JIT session.
+#[derive(Clone, Debug)]
+pub struct ObjectCache {
+    root: PathBuf,
+    dir: PathBuf,
+}
+
+impl ObjectCache {
+    /// Opens the cache at the given root directory, creating it if it does not exist.
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        let dir = root.join(VERSION);
+        fs::create_dir_all(&dir)?;
+        Ok(Self { root, dir })
+    }
+
+    /// Returns the root directory of the cache.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the directory in which objects of the current compiler version are stored.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the path of the object with the given key.
+    ///
+    /// The file may not exist.
+    pub fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(key.file_name())
+    }
+
+    /// Returns `true` if an object with the given key is cached.
+    pub fn contains(&self, key: &CacheKey) -> bool {
+        self.path(key).is_file()
+    }
+
+    /// Reads the object with the given key, if it exists.
+    pub fn get(&self, key: &CacheKey) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)) {
+            Ok(object) => Ok(Some(object)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stores the object with the given key, overwriting any previous value.
+    pub fn insert(&self, key: &CacheKey, object: &[u8]) -> io::Result<()> {
+        // Write to a temporary file first and then rename it, so that concurrent readers never
+        // observe a partially written object.
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{EXTENSION}.{}.tmp", std::process::id()));
+        let res = (|| {
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(object)?;
+            file.sync_all()?;
+            fs::rename(&tmp, &path)
+        })();
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        res
+    }
+
+    /// Removes the object with the given key. Returns `true` if it existed.
+    pub fn remove(&self, key: &CacheKey) -> io::Result<bool> {
+        match fs::remove_file(self.path(key)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes all objects of the current compiler version.
+    pub fn clear(&self) -> io::Result<()> {
+        fs::remove_dir_all(&self.dir)?;
+        fs::create_dir_all(&self.dir)
+    }
+
+    /// Removes all objects that were compiled by other versions of the compiler.
+    pub fn remove_stale(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() && entry.path() != self.dir {
+                debug!(path=%entry.path().display(), "removing stale cache directory");
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key() {
+        let a = CacheKey::from_parts([&b"ab"[..], b"c"]);
+        let b = CacheKey::from_parts([&b"a"[..], b"bc"]);
+        assert_ne!(a, b);
+        assert_eq!(a, CacheKey::from_parts([&b"ab"[..], b"c"]));
+        assert_eq!(a.to_string().len(), 64);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = ObjectCache::new(root.path()).unwrap();
+        let key = CacheKey::from_parts([&b"code"[..]]);
+
+        assert!(!cache.contains(&key));
+        assert_eq!(cache.get(&key).unwrap(), None);
+
+        cache.insert(&key, b"object").unwrap();
+        assert!(cache.contains(&key));
+        assert_eq!(cache.get(&key).unwrap().as_deref(), Some(&b"object"[..]));
+
+        assert!(cache.remove(&key).unwrap());
+        assert!(!cache.remove(&key).unwrap());
+        assert_eq!(cache.get(&key).unwrap(), None);
+    }
+
+    #[test]
+    fn stale() {
+        let root = tempfile::tempdir().unwrap();
+        let stale = root.path().join("0.0.0-stale");
+        fs::create_dir_all(&stale).unwrap();
+        fs::write(stale.join("a.o"), b"").unwrap();
+
+        let cache = ObjectCache::new(root.path()).unwrap();
+        cache.insert(&CacheKey::from_parts(std::iter::empty()), b"object").unwrap();
+        cache.remove_stale().unwrap();
+        assert!(!stale.exists());
+        assert!(cache.dir().exists());
+        assert!(cache.contains(&CacheKey::from_parts(std::iter::empty())));
+    }
+}
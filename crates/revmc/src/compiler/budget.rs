@@ -0,0 +1,122 @@
+//! Compilation budgets.
+
+use std::{fmt, time::Duration};
+
+/// Limits on the bytecode that [`EvmCompiler`](crate::EvmCompiler) accepts and on the time spent
+/// compiling it.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct Budget {
+    pub(super) max_bytecode_size: Option<usize>,
+    pub(super) max_instructions: Option<usize>,
+    pub(super) max_dynamic_jump_targets: Option<usize>,
+    pub(super) max_optimization_time: Option<Duration>,
+}
+
+impl Budget {
+    pub(super) fn check_bytecode_size(&self, size: usize) -> Result<(), BudgetExceeded> {
+        check(self.max_bytecode_size, size, |limit| BudgetExceeded::BytecodeSize { size, limit })
+    }
+
+    pub(super) fn check_instructions(&self, count: usize) -> Result<(), BudgetExceeded> {
+        check(self.max_instructions, count, |limit| BudgetExceeded::Instructions { count, limit })
+    }
+
+    pub(super) fn check_dynamic_jump_targets(&self, count: usize) -> Result<(), BudgetExceeded> {
+        check(self.max_dynamic_jump_targets, count, |limit| BudgetExceeded::DynamicJumpTargets {
+            count,
+            limit,
+        })
+    }
+
+    pub(super) fn check_optimization_time(&self, elapsed: Duration) -> Result<(), BudgetExceeded> {
+        check(self.max_optimization_time, elapsed, |limit| BudgetExceeded::OptimizationTime {
+            elapsed,
+            limit,
+        })
+    }
+
+    /// Returns `true` if optimizing `insts` instructions, at `time_per_inst` each, is projected to
+    /// exceed [`max_optimization_time`](crate::EvmCompiler::max_optimization_time).
+    pub(super) fn exceeds_optimization_time(
+        &self,
+        insts: usize,
+        time_per_inst: Option<Duration>,
+    ) -> bool {
+        let (Some(limit), Some(time_per_inst)) = (self.max_optimization_time, time_per_inst) else {
+            return false;
+        };
+        let insts = insts.try_into().unwrap_or(u32::MAX);
+        time_per_inst.checked_mul(insts).map_or(true, |projected| projected > limit)
+    }
+}
+
+fn check<T: PartialOrd + Copy>(
+    limit: Option<T>,
+    value: T,
+    err: impl FnOnce(T) -> BudgetExceeded,
+) -> Result<(), BudgetExceeded> {
+    match limit {
+        Some(limit) if value > limit => Err(err(limit)),
+        _ => Ok(()),
+    }
+}
+
+/// A compilation budget of [`EvmCompiler`](crate::EvmCompiler) was exceeded.
+///
+/// Returned in [`CompileError::BudgetExceeded`](crate::CompileError::BudgetExceeded).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetExceeded {
+    /// The bytecode is larger than
+    /// [`max_bytecode_size`](crate::EvmCompiler::max_bytecode_size).
+    BytecodeSize {
+        /// The size of the bytecode, in bytes.
+        size: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// The bytecode has more instructions than
+    /// [`max_instructions`](crate::EvmCompiler::max_instructions).
+    Instructions {
+        /// The number of instructions.
+        count: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// The bytecode has more dynamic jump targets than
+    /// [`max_dynamic_jump_targets`](crate::EvmCompiler::max_dynamic_jump_targets).
+    DynamicJumpTargets {
+        /// The number of `JUMPDEST`s that dynamic jumps may target.
+        count: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// Optimizing the module took longer than
+    /// [`max_optimization_time`](crate::EvmCompiler::max_optimization_time).
+    OptimizationTime {
+        /// The time spent optimizing.
+        elapsed: Duration,
+        /// The limit.
+        limit: Duration,
+    },
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BytecodeSize { size, limit } => {
+                write!(f, "bytecode size of {size} bytes exceeds the limit of {limit}")
+            }
+            Self::Instructions { count, limit } => {
+                write!(f, "{count} instructions exceed the limit of {limit}")
+            }
+            Self::DynamicJumpTargets { count, limit } => {
+                write!(f, "{count} dynamic jump targets exceed the limit of {limit}")
+            }
+            Self::OptimizationTime { elapsed, limit } => {
+                write!(f, "optimization took {elapsed:?}, exceeding the limit of {limit:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
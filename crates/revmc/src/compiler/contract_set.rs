@@ -0,0 +1,138 @@
+//! Lookup functions of AOT contract sets.
+
+use crate::{AotContractInfo, Backend, Builder, IntCC, Result};
+use revm_primitives::{SpecId, B256, U256};
+use revmc_backend::{Linkage, TypeMethods};
+use revmc_context::{AOT_CONTRACT_INFO_SYMBOL, AOT_LOOKUP_SYMBOL};
+use std::{collections::BTreeMap, mem};
+
+/// A contract that was translated as part of a contract set.
+pub(super) struct ContractSetEntry {
+    pub(super) name: String,
+    pub(super) spec_id: SpecId,
+    pub(super) code_hash: B256,
+}
+
+/// Builds the [`AotLookupFn`](crate::AotLookupFn) of the given contracts.
+pub(super) fn translate_lookup<B: Backend>(
+    backend: &mut B,
+    contracts: &[ContractSetEntry],
+) -> Result<()> {
+    let ptr = backend.type_ptr();
+    let (mut bcx, _) = backend.build_function(
+        AOT_LOOKUP_SYMBOL,
+        Some(ptr),
+        &[ptr],
+        &["code_hash"],
+        Linkage::Public,
+    )?;
+    let i64_type = bcx.type_int(64);
+    let word_type = bcx.type_int(256);
+
+    // The hash is not necessarily aligned.
+    let slot = bcx.new_stack_slot_raw(word_type, "code_hash");
+    let slot_addr = bcx.stack_addr(word_type, slot);
+    let code_hash_ptr = bcx.fn_param(0);
+    bcx.memcpy_inline(slot_addr, code_hash_ptr, 32);
+    let code_hash = bcx.stack_load(word_type, slot, "code_hash");
+
+    // Switch on the first 8 bytes of the hash, then compare the full hash of every contract that
+    // shares them.
+    let mut groups = BTreeMap::<u64, Vec<&ContractSetEntry>>::new();
+    for contract in contracts {
+        let key = u64::from_le_bytes(contract.code_hash[..8].try_into().unwrap());
+        groups.entry(key).or_default().push(contract);
+    }
+    let not_found = bcx.create_block("not_found");
+    let targets = groups
+        .keys()
+        .map(|&key| (key, bcx.create_block(&format!("{key:016x}"))))
+        .collect::<Vec<_>>();
+    let key = bcx.ireduce(i64_type, code_hash);
+    bcx.switch(key, not_found, &targets, true);
+
+    for (&(_, block), group) in targets.iter().zip(groups.values()) {
+        bcx.switch_to_block(block);
+        for contract in group {
+            let found = bcx.create_block(&contract.name);
+            let next = bcx.create_block("next");
+            let expected = bcx.iconst_256(U256::from_le_bytes(contract.code_hash.0));
+            let is_eq = bcx.icmp(IntCC::Equal, code_hash, expected);
+            bcx.brif(is_eq, found, next);
+
+            bcx.switch_to_block(found);
+            let function = bcx.get_function(&contract.name).expect("contract not translated");
+            let addr = bcx.func_addr(function);
+            bcx.ret(&[addr]);
+
+            bcx.switch_to_block(next);
+        }
+        bcx.br(not_found);
+    }
+
+    bcx.switch_to_block(not_found);
+    let null = bcx.nullptr();
+    bcx.ret(&[null]);
+    bcx.seal_all_blocks();
+    Ok(())
+}
+
+/// Builds the [`AotContractInfoFn`](crate::AotContractInfoFn) of the given contracts.
+pub(super) fn translate_contract_info<B: Backend>(
+    backend: &mut B,
+    contracts: &[ContractSetEntry],
+) -> Result<()> {
+    let isize_type = backend.type_ptr_sized_int();
+    let i8_type = backend.type_int(8);
+    let i64_type = backend.type_int(64);
+    let ptr = backend.type_ptr();
+    let (mut bcx, _) = backend.build_function(
+        AOT_CONTRACT_INFO_SYMBOL,
+        Some(i8_type),
+        &[isize_type, ptr],
+        &["index", "info"],
+        Linkage::Public,
+    )?;
+    let index = bcx.fn_param(0);
+    let info = bcx.fn_param(1);
+
+    let out_of_bounds = bcx.create_block("out_of_bounds");
+    let targets = (0..contracts.len())
+        .map(|i| (i as u64, bcx.create_block(&format!("contract.{i}"))))
+        .collect::<Vec<_>>();
+    bcx.switch(index, out_of_bounds, &targets, true);
+
+    for (&(_, block), contract) in targets.iter().zip(contracts) {
+        bcx.switch_to_block(block);
+        // Stored in words since `info` is only pointer-aligned.
+        for (i, word) in contract.code_hash.chunks_exact(8).enumerate() {
+            let word = bcx.uconst(i64_type, u64::from_le_bytes(word.try_into().unwrap()));
+            let offset = mem::offset_of!(AotContractInfo, code_hash) + i * 8;
+            store_field(&mut bcx, info, offset, word);
+        }
+        let name = bcx.str_const(&contract.name);
+        store_field(&mut bcx, info, mem::offset_of!(AotContractInfo, name), name);
+        let name_len = bcx.iconst(isize_type, contract.name.len() as i64);
+        store_field(&mut bcx, info, mem::offset_of!(AotContractInfo, name_len), name_len);
+        let function = bcx.get_function(&contract.name).expect("contract not translated");
+        let function = bcx.func_addr(function);
+        store_field(&mut bcx, info, mem::offset_of!(AotContractInfo, function), function);
+        let spec_id = bcx.iconst(i8_type, contract.spec_id as i64);
+        store_field(&mut bcx, info, mem::offset_of!(AotContractInfo, spec_id), spec_id);
+
+        let ret = bcx.iconst(i8_type, 1);
+        bcx.ret(&[ret]);
+    }
+
+    bcx.switch_to_block(out_of_bounds);
+    let ret = bcx.iconst(i8_type, 0);
+    bcx.ret(&[ret]);
+    bcx.seal_all_blocks();
+    Ok(())
+}
+
+fn store_field<B: Builder>(bcx: &mut B, info: B::Value, offset: usize, value: B::Value) {
+    let offset = bcx.iconst(bcx.type_ptr_sized_int(), offset as i64);
+    let ptr = bcx.gep(bcx.type_int(8), info, &[offset], "info.field.addr");
+    bcx.store(value, ptr);
+}
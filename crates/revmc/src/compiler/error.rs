@@ -0,0 +1,94 @@
+//! Compiler errors.
+
+use super::BudgetExceeded;
+use crate::Error;
+use revm_primitives::SpecId;
+use std::{fmt, io};
+
+/// Returns a [`CompileError::InvalidUsage`] with the given message if the condition is false.
+macro_rules! ensure_usage {
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            return Err($crate::CompileError::InvalidUsage(format!($($arg)+)));
+        }
+    };
+}
+
+/// An error returned by [`EvmCompiler`](crate::EvmCompiler).
+///
+/// Apart from [`InvalidUsage`](Self::InvalidUsage), these mean that the bytecode cannot be
+/// compiled with the current configuration, and it should be executed with the interpreter
+/// instead.
+///
+/// This can be converted into an [`Error`] with `?`, and retrieved back with
+/// [`Report::downcast_ref`](crate::eyre::Report::downcast_ref).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CompileError {
+    /// The bytecode could not be decoded, or failed validation.
+    InvalidBytecode(Error),
+    /// The bytecode is not supported in the given spec, such as an EOF container before
+    /// [`SpecId::PRAGUE_EOF`].
+    UnsupportedSpec(SpecId),
+    /// The target of the backend is not supported.
+    UnsupportedTarget(String),
+    /// A compilation budget was exceeded.
+    BudgetExceeded(BudgetExceeded),
+    /// The compiler was used incorrectly, such as translating a function with a name that is
+    /// already defined, or translating more functions after finalizing the module.
+    InvalidUsage(String),
+    /// The backend produced an invalid module. This is a bug in the compiler.
+    Verify(Error),
+    /// The module could not be JIT-compiled, or the object could not be written or loaded.
+    Emit(Error),
+    /// Any other error, such as failing to dump intermediate outputs.
+    Other(Error),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidBytecode(e) => write!(f, "invalid bytecode: {e}"),
+            Self::UnsupportedSpec(spec_id) => write!(f, "bytecode is not supported in {spec_id:?}"),
+            Self::UnsupportedTarget(msg) => write!(f, "unsupported target: {msg}"),
+            Self::BudgetExceeded(e) => write!(f, "compilation budget exceeded: {e}"),
+            Self::InvalidUsage(msg) => f.write_str(msg),
+            Self::Verify(e) => write!(f, "failed to verify module: {e}"),
+            Self::Emit(e) => write!(f, "failed to emit code: {e}"),
+            Self::Other(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            // The message of the error itself is already displayed.
+            Self::InvalidBytecode(e) | Self::Verify(e) | Self::Emit(e) | Self::Other(e) => {
+                e.source()
+            }
+            Self::UnsupportedSpec(_)
+            | Self::UnsupportedTarget(_)
+            | Self::BudgetExceeded(_)
+            | Self::InvalidUsage(_) => None,
+        }
+    }
+}
+
+impl From<BudgetExceeded> for CompileError {
+    fn from(e: BudgetExceeded) -> Self {
+        Self::BudgetExceeded(e)
+    }
+}
+
+impl From<Error> for CompileError {
+    fn from(e: Error) -> Self {
+        Self::Other(e)
+    }
+}
+
+impl From<io::Error> for CompileError {
+    fn from(e: io::Error) -> Self {
+        Self::Other(e.into())
+    }
+}
@@ -1,35 +1,46 @@
 //! EVM bytecode compiler implementation.
 
-use crate::{Backend, Builder, Bytecode, EvmCompilerFn, EvmContext, EvmStack, Result};
+use crate::{
+    analysis::BytecodeAnalysis, Backend, Builder, Bytecode, CacheKey, EvmCompilerFn, EvmContext,
+    EvmStack, ObjectCache, Result,
+};
 use revm_interpreter::{Contract, Gas};
-use revm_primitives::{Bytes, Env, Eof, SpecId, EOF_MAGIC_BYTES};
+use revm_primitives::{keccak256, Bytes, Env, Eof, SpecId, EOF_MAGIC_BYTES};
 use revmc_backend::{
-    eyre::{ensure, eyre},
-    Attribute, FunctionAttributeLocation, Linkage, OptimizationLevel,
+    eyre::eyre, Attribute, FunctionAttributeLocation, Linkage, OptimizationLevel, TargetDataLayout,
 };
-use revmc_builtins::Builtins;
-use revmc_context::RawEvmCompilerFn;
+use revmc_builtins::{Builtin, Builtins};
+use revmc_context::{RawEvmCompilerFn, AOT_CONTRACT_INFO_SYMBOL, AOT_LOOKUP_SYMBOL};
+use rustc_hash::FxHashMap;
 use std::{
     borrow::Cow,
     fs,
     io::{self, Write},
     mem,
     path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
 };
 
-// TODO: Somehow have a config to tell the backend to assume that stack stores are unobservable,
-// making it eliminate redundant stores for values outside the stack length when optimized away.
-// E.g. `PUSH0 POP` gets fully optimized away, but the `store i256 0, ptr %stack` will still get
-// emitted.
-// Use this when `stack` is passed in arguments.
-
 // TODO: Get rid of `cfg!(target_endian)` calls.
 
 // TODO: Test on big-endian hardware.
-// It probably doesn't work when loading Rust U256 into native endianness.
+// It probably doesn't work when loading Rust U256 into native endianness, so big-endian targets are
+// rejected in `check_target`.
+
+#[macro_use]
+mod error;
+pub use error::CompileError;
+
+mod budget;
+use budget::Budget;
+pub use budget::BudgetExceeded;
+
+mod contract_set;
+use contract_set::ContractSetEntry;
 
 mod translate;
-use translate::{FcxConfig, FunctionCx};
+use translate::{EofSectionFunction, FcxConfig, FunctionCx};
 
 /// EVM bytecode compiler.
 ///
@@ -42,6 +53,11 @@ use translate::{FcxConfig, FunctionCx};
 /// Performing either of these operations finalizes the module, and no more functions can be added
 /// afterwards until [`clear`] is called, which will reset the module to its initial state.
 ///
+/// The exception is a backend that detaches the module when JIT-compiling, such as the LLVM
+/// backend created with `EvmLlvmBackend::new_orc`, in which case [`jit_function`] starts a new
+/// module and more functions can be added right away. Multiple such compilers, one per thread, can
+/// then share a single JIT.
+///
 /// [`translate`]: EvmCompiler::translate
 /// [`write_object`]: EvmCompiler::write_object
 /// [`jit_function`]: EvmCompiler::jit_function
@@ -52,10 +68,22 @@ pub struct EvmCompiler<B: Backend> {
     backend: B,
     out_dir: Option<PathBuf>,
     config: FcxConfig,
+    budget: Budget,
     builtins: Builtins<B>,
 
     dump_assembly: bool,
     dump_unopt_assembly: bool,
+    /// The names of the translated functions, if writing the perf map is enabled.
+    perf_map_names: Option<FxHashMap<B::FuncId, String>>,
+
+    /// The number of instructions translated into the module since it was last finalized.
+    module_insts: usize,
+    /// The time spent optimizing each instruction of the last module optimized at the configured
+    /// optimization level.
+    optimization_time_per_inst: Option<Duration>,
+    /// Whether the last module was optimized with [`OptimizationLevel::None`] instead of the
+    /// configured optimization level, to stay within the time budget.
+    optimization_skipped: bool,
 
     finalized: bool,
 }
@@ -68,9 +96,14 @@ impl<B: Backend> EvmCompiler<B> {
             backend,
             out_dir: None,
             config: FcxConfig::default(),
+            budget: Budget::default(),
             builtins: Builtins::new(),
             dump_assembly: true,
             dump_unopt_assembly: false,
+            perf_map_names: None,
+            module_insts: 0,
+            optimization_time_per_inst: None,
+            optimization_skipped: false,
             finalized: false,
         }
     }
@@ -97,6 +130,9 @@ impl<B: Backend> EvmCompiler<B> {
 
     /// Dumps intermediate outputs and other debug info to the given directory after compilation.
     ///
+    /// This includes the analyzed bytecode, and its control-flow graph as Graphviz `cfg.dot` and
+    /// JSON `cfg.json`.
+    ///
     /// Disables dumping if `output_dir` is `None`.
     pub fn set_dump_to(&mut self, output_dir: Option<PathBuf>) {
         self.backend.set_is_dumping(output_dir.is_some());
@@ -193,6 +229,56 @@ impl<B: Backend> EvmCompiler<B> {
         self.config.inspect_stack_length = yes;
     }
 
+    /// Sets whether to call the [`InstructionInspector`] set in [`EvmContext::inspector`] before
+    /// each instruction is executed.
+    ///
+    /// This allows tracing the execution of compiled functions like revm's `Inspector::step`, but
+    /// it disables keeping stack values in registers and has a large performance penalty.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`InstructionInspector`]: crate::InstructionInspector
+    /// [`EvmContext::inspector`]: crate::EvmContext::inspector
+    pub fn inspect_instructions(&mut self, yes: bool) {
+        self.config.inspect_instructions = yes;
+    }
+
+    /// Sets whether to emit debug info that maps the compiled code back to the EVM bytecode.
+    ///
+    /// The source file of each function is a listing of its bytecode with one line per byte, so
+    /// that line `pc + 1` is the instruction at `pc`. It is named `<name>.evm`, and it is written
+    /// to the dump directory if [enabled](Self::set_dump_to). Otherwise only its name is
+    /// recorded, which is still enough for profilers to attribute samples to instructions.
+    ///
+    /// Only supported by the LLVM backend, which emits DWARF line tables.
+    ///
+    /// JIT-compiled code is only registered with debuggers by a backend created with
+    /// `EvmLlvmBackend::new_orc`, using a JIT created with `EvmLlvmOrcJit::with_event_listeners`
+    /// with `gdb` enabled. The default MCJIT backend does not register it. Shared libraries must be
+    /// linked with [`Linker::strip_debug`](crate::Linker::strip_debug) disabled to keep the line
+    /// tables.
+    ///
+    /// Defaults to `false`.
+    pub fn debug_info(&mut self, yes: bool) {
+        self.config.debug_info = yes;
+    }
+
+    /// Sets whether to write an entry to `/tmp/perf-<pid>.map` for each function returned by
+    /// [`jit_function`](Self::jit_function), so that `perf` can attribute samples to the `name`
+    /// passed to [`translate`](Self::translate).
+    ///
+    /// Requires a backend that knows the size of the compiled functions, which are Cranelift and
+    /// LLVM with ORC, on ELF targets. With ORC, the JIT must be created with
+    /// `JitEventListeners::perf_map`. Functions whose size is unknown are skipped.
+    ///
+    /// With LLVM, the functions can also be registered with debuggers or `perf inject --jit` using
+    /// `EvmLlvmOrcJit::with_event_listeners`.
+    ///
+    /// Defaults to `false`.
+    pub fn perf_map(&mut self, yes: bool) {
+        self.perf_map_names = yes.then(FxHashMap::default);
+    }
+
     /// Sets whether to enable stack bound checks.
     ///
     /// Ignored for EOF bytecodes, as they are assumed to be correct.
@@ -226,6 +312,119 @@ impl<B: Backend> EvmCompiler<B> {
         self.config.gas_metering = yes;
     }
 
+    /// Sets whether to charge the static gas of each section once at its start, instead of before
+    /// each instruction.
+    ///
+    /// A section is a sequence of instructions that are executed sequentially without any jumps or
+    /// branches, so this greatly reduces the number of gas checks in hot code. If execution stops
+    /// in the middle of a section, the static gas of the instructions that were not executed is
+    /// refunded, so that the remaining gas matches the interpreter's.
+    ///
+    /// The only exception is stack underflow or overflow, which is checked at the start of the
+    /// section for all of its instructions: the refund is then computed as if the first instruction
+    /// of the section failed.
+    ///
+    /// Out of gas errors, including the ones returned by builtins, are never refunded, as all of
+    /// the gas is consumed anyway, like in revm. The remaining gas is therefore unspecified
+    /// after an out of gas error.
+    ///
+    /// Has no effect if [`gas_metering`](Self::gas_metering) is disabled.
+    ///
+    /// Defaults to `true`.
+    pub fn section_gas_metering(&mut self, yes: bool) {
+        self.config.section_gas_metering = yes;
+    }
+
+    /// Sets whether `SLOAD` and `SSTORE` look up the slot in the [`StorageCache`] set in
+    /// [`EvmContext::storage_cache`] before calling into the host.
+    ///
+    /// On a hit, the instruction is executed inline without any calls, charging gas and recording
+    /// refunds for a warm slot as per EIP-2929 and EIP-2200. Misses, static calls, and `SSTORE`s
+    /// below the call stipend fall back to the host.
+    ///
+    /// Only applies to specs starting from Berlin, and has no effect if
+    /// [`gas_metering`](Self::gas_metering) is disabled.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`StorageCache`]: crate::StorageCache
+    pub fn storage_cache(&mut self, yes: bool) {
+        self.config.storage_cache = yes;
+    }
+
+    /// Sets whether to translate each code section of EOF bytecode into its own native function.
+    ///
+    /// The code sections are otherwise flattened into a single function, where `CALLF` pushes the
+    /// return address onto the function stack in [`EvmContext`], and `RETF` pops it and branches
+    /// to it indirectly. With this enabled, `CALLF` is a direct call to the function of the called
+    /// section, `JUMPF` is a tail call, or a branch if it targets the current section, and `RETF`
+    /// returns, so the function stack is not used.
+    ///
+    /// Bytecode with only one code section, where a code section other than the first one may
+    /// suspend execution, where `JUMPF` targets the first code section, or where `JUMPF`s between
+    /// different code sections form a cycle, is still flattened.
+    ///
+    /// Each nested `CALLF` is a native call, so a chain of `CALLF`s up to the EOF function stack
+    /// limit of 1024 uses as many native stack frames. The thread executing the functions must
+    /// have enough stack space for them.
+    ///
+    /// Defaults to `false`.
+    pub fn eof_section_functions(&mut self, yes: bool) {
+        self.config.eof_section_functions = yes;
+    }
+
+    /// Sets the maximum size of the bytecode to compile, in bytes.
+    ///
+    /// Larger bytecodes are rejected with [`BudgetExceeded::BytecodeSize`] before being parsed.
+    ///
+    /// Defaults to `None` (no limit).
+    pub fn max_bytecode_size(&mut self, limit: Option<usize>) {
+        self.budget.max_bytecode_size = limit;
+    }
+
+    /// Sets the maximum number of instructions in the bytecode to compile.
+    ///
+    /// Bytecodes with more instructions are rejected with [`BudgetExceeded::Instructions`] before
+    /// being analyzed.
+    ///
+    /// Defaults to `None` (no limit).
+    pub fn max_instructions(&mut self, limit: Option<usize>) {
+        self.budget.max_instructions = limit;
+    }
+
+    /// Sets the maximum number of `JUMPDEST`s that unresolved dynamic jumps may branch to.
+    ///
+    /// Every such `JUMPDEST` is a case in the dynamic jump table, which is a common source of
+    /// slow optimization. Bytecodes with more targets are rejected with
+    /// [`BudgetExceeded::DynamicJumpTargets`] after being analyzed.
+    ///
+    /// Defaults to `None` (no limit).
+    pub fn max_dynamic_jump_targets(&mut self, limit: Option<usize>) {
+        self.budget.max_dynamic_jump_targets = limit;
+    }
+
+    /// Sets the maximum wall-clock time to spend optimizing the module.
+    ///
+    /// Optimization passes cannot be interrupted, so the time itself is not bounded. What is
+    /// guaranteed is that a module whose optimization took longer than the limit is rejected with
+    /// [`BudgetExceeded::OptimizationTime`] instead of being compiled to machine code. The module
+    /// is then left unfinalized, and should be [cleared](Self::clear) before compiling more
+    /// functions.
+    ///
+    /// To stay within the limit, modules that are projected to exceed it, based on the time spent
+    /// per instruction by the previous module, are optimized with [`OptimizationLevel::None`]
+    /// instead. This only applies to backends that can change the optimization level of the
+    /// current module, such as LLVM. Such modules are not stored by
+    /// [`jit_cached`](Self::jit_cached).
+    ///
+    /// The other limits should be used to avoid optimizing pathological bytecodes in the first
+    /// place.
+    ///
+    /// Defaults to `None` (no limit).
+    pub fn max_optimization_time(&mut self, limit: Option<Duration>) {
+        self.budget.max_optimization_time = limit;
+    }
+
     /// Translates the given EVM bytecode into an internal function.
     ///
     /// NOTE: `name` must be unique for each function, as it is used as the name of the final
@@ -235,13 +434,70 @@ impl<B: Backend> EvmCompiler<B> {
         name: &str,
         input: impl Into<EvmCompilerInput<'a>>,
         spec_id: SpecId,
-    ) -> Result<B::FuncId> {
-        ensure!(cfg!(target_endian = "little"), "only little-endian is supported");
-        ensure!(!self.finalized, "cannot compile more functions after finalizing the module");
+    ) -> Result<B::FuncId, CompileError> {
+        self.check_target()?;
+        ensure_usage!(!self.finalized, "cannot compile more functions after finalizing the module");
         let bytecode = self.parse(input.into(), spec_id)?;
         self.translate_inner(name, &bytecode)
     }
 
+    /// (AOT) Translates a set of contracts, along with the functions that allow hosts to find them
+    /// once the object is loaded:
+    /// - [`AOT_LOOKUP_SYMBOL`], an [`AotLookupFn`] that returns the function of a code hash;
+    /// - [`AOT_CONTRACT_INFO_SYMBOL`], an [`AotContractInfoFn`] that returns the name, spec ID,
+    ///   code hash and function of each contract.
+    ///
+    /// The code hash of each contract is the `keccak256` hash of its bytecode, or of the whole
+    /// container for EOF, same as the hash of the account code. It must be unique in the set.
+    ///
+    /// The object can then be written with [`write_object`](Self::write_object) and linked into a
+    /// shared library with [`Linker`](crate::Linker), so that the whole set can be loaded at once.
+    /// Since the lookup functions can only be defined once per module, no other contracts can be
+    /// translated afterwards.
+    ///
+    /// Returns the IDs of the functions, in the same order as `contracts`.
+    ///
+    /// [`AOT_LOOKUP_SYMBOL`]: crate::AOT_LOOKUP_SYMBOL
+    /// [`AotLookupFn`]: crate::AotLookupFn
+    /// [`AOT_CONTRACT_INFO_SYMBOL`]: crate::AOT_CONTRACT_INFO_SYMBOL
+    /// [`AotContractInfoFn`]: crate::AotContractInfoFn
+    pub fn translate_contract_set<'a>(
+        &mut self,
+        contracts: impl IntoIterator<Item = AotContract<'a>>,
+    ) -> Result<Vec<B::FuncId>, CompileError> {
+        ensure_usage!(self.is_aot(), "contract sets can only be compiled in AOT mode");
+        self.check_target()?;
+        for symbol in [AOT_LOOKUP_SYMBOL, AOT_CONTRACT_INFO_SYMBOL] {
+            ensure_usage!(
+                self.backend.function_name_is_unique(symbol),
+                "`{symbol}` is already defined"
+            );
+        }
+
+        let mut entries = Vec::<ContractSetEntry>::new();
+        let mut ids = Vec::new();
+        for AotContract { name, input, spec_id } in contracts {
+            ensure_usage!(
+                !self.finalized,
+                "cannot compile more functions after finalizing the module"
+            );
+            let code_hash = keccak256(input.raw());
+            let bytecode = self.parse(input, spec_id)?;
+            if let Some(other) = entries.iter().find(|e| e.code_hash == code_hash) {
+                return Err(CompileError::InvalidUsage(format!(
+                    "`{name}` has the same code hash as `{}`: {code_hash}",
+                    other.name
+                )));
+            }
+            ids.push(self.translate_inner(name, &bytecode)?);
+            entries.push(ContractSetEntry { name: name.to_string(), spec_id, code_hash });
+        }
+
+        contract_set::translate_lookup(&mut self.backend, &entries)?;
+        contract_set::translate_contract_info(&mut self.backend, &entries)?;
+        Ok(ids)
+    }
+
     /// (JIT) Compiles the given EVM bytecode into a JIT function.
     ///
     /// See [`translate`](Self::translate) for more information.
@@ -255,7 +511,7 @@ impl<B: Backend> EvmCompiler<B> {
         name: &str,
         bytecode: impl Into<EvmCompilerInput<'a>>,
         spec_id: SpecId,
-    ) -> Result<EvmCompilerFn> {
+    ) -> Result<EvmCompilerFn, CompileError> {
         let id = self.translate(name, bytecode.into(), spec_id)?;
         unsafe { self.jit_function(id) }
     }
@@ -266,28 +522,174 @@ impl<B: Backend> EvmCompiler<B> {
     ///
     /// The returned function pointer is owned by the module, and must not be called after the
     /// module is cleared or the function is freed.
-    pub unsafe fn jit_function(&mut self, id: B::FuncId) -> Result<EvmCompilerFn> {
-        ensure!(self.is_jit(), "cannot JIT functions during AOT compilation");
+    pub unsafe fn jit_function(&mut self, id: B::FuncId) -> Result<EvmCompilerFn, CompileError> {
+        ensure_usage!(self.is_jit(), "cannot JIT functions during AOT compilation");
         self.finalize()?;
-        let addr = self.backend.jit_function(id)?;
+        let addr = self.backend.jit_function(id).map_err(CompileError::Emit)?;
         debug_assert!(addr != 0);
+        if let Some(name) = self.perf_map_names.as_mut().and_then(|names| names.remove(&id)) {
+            if let Some(size) = self.backend.jit_function_size(id) {
+                if let Err(e) = write_perf_map_entry(addr, size, &name) {
+                    warn!(%e, "failed to write perf map entry");
+                }
+            }
+        }
+        if self.backend.jit_detaches_module() {
+            self.builtins.clear();
+            self.finalized = false;
+        }
         Ok(EvmCompilerFn::new(unsafe { std::mem::transmute::<usize, RawEvmCompilerFn>(addr) }))
     }
 
+    /// (JIT) Compiles the given EVM bytecode into a JIT function, using `cache` to skip
+    /// translation and optimization if the same bytecode was compiled before with the same
+    /// configuration.
+    ///
+    /// On a cache miss, the bytecode is translated and the resulting object is stored in `cache`,
+    /// unless its optimization was skipped to stay within the
+    /// [time budget](Self::max_optimization_time).
+    /// Both hits and misses are then loaded with [`jit_object`](Self::jit_object).
+    ///
+    /// A cache miss finalizes the module and then resets it afterwards, so it cannot be mixed with
+    /// functions that were translated but not yet finalized.
+    ///
+    /// The function is named after the cache key, see [`CacheKey::symbol_name`].
+    ///
+    /// See [`cache_key`](Self::cache_key) for the inputs that make up the cache key, and
+    /// [`translate`](Self::translate) for more information.
+    ///
+    /// # Safety
+    ///
+    /// A cache miss invalidates any pointers retrieved with [`jit_function`](Self::jit_function),
+    /// see [`clear`](Self::clear). Also see [`jit_object`](Self::jit_object).
+    pub unsafe fn jit_cached<'a>(
+        &mut self,
+        cache: &ObjectCache,
+        input: impl Into<EvmCompilerInput<'a>>,
+        spec_id: SpecId,
+    ) -> Result<EvmCompilerFn, CompileError> {
+        ensure_usage!(self.is_jit(), "cannot JIT functions during AOT compilation");
+        let input = input.into();
+        let key = self.cache_key(input, spec_id);
+        let name = &key.symbol_name();
+        let object = if let Some(object) = cache.get(&key)? {
+            debug!(%key, "cache hit");
+            object
+        } else {
+            debug!(%key, "cache miss");
+            self.translate(name, input, spec_id)?;
+            self.finalize()?;
+            let mut object = Vec::new();
+            self.backend.write_object(&mut object).map_err(CompileError::Emit)?;
+            unsafe { self.clear_functions()? };
+            // The key records the configured optimization level, which was not applied.
+            if self.optimization_skipped {
+                debug!(%key, "optimization was skipped, not caching");
+            } else {
+                cache.insert(&key, &object)?;
+            }
+            object
+        };
+        unsafe { self.jit_object(name, &object) }
+    }
+
+    /// (JIT) Loads an object previously emitted by [`write_object`](Self::write_object) into the
+    /// JIT session, and returns the function with the given name.
+    ///
+    /// The object must have been compiled for the host machine. Builtins referenced by the object
+    /// are resolved to the ones linked into the current process.
+    ///
+    /// If an object with the same `name` was already loaded, the existing function is returned.
+    ///
+    /// # Safety
+    ///
+    /// The object must have been emitted by this version of the compiler with a compatible
+    /// configuration, as it is not validated in any way.
+    ///
+    /// The returned function pointer is owned by the backend, and must not be called after the
+    /// module is cleared.
+    pub unsafe fn jit_object(
+        &mut self,
+        name: &str,
+        object: &[u8],
+    ) -> Result<EvmCompilerFn, CompileError> {
+        ensure_usage!(self.is_jit(), "cannot JIT functions during AOT compilation");
+        let symbols = Builtin::ALL.map(|builtin| (builtin.name(), builtin.addr()));
+        let addr = self.backend.jit_object(name, object, &symbols).map_err(CompileError::Emit)?;
+        debug_assert!(addr != 0);
+        Ok(EvmCompilerFn::new(unsafe { std::mem::transmute::<usize, RawEvmCompilerFn>(addr) }))
+    }
+
+    /// Returns the [`ObjectCache`] key for the given bytecode.
+    ///
+    /// The key is derived from the bytecode hash, the spec ID, the compiler configuration, the
+    /// optimization level, the backend's target and code generator version, and the compiler
+    /// version and build.
+    pub fn cache_key<'a>(
+        &self,
+        input: impl Into<EvmCompilerInput<'a>>,
+        spec_id: SpecId,
+    ) -> CacheKey {
+        let code = match input.into() {
+            EvmCompilerInput::Code(code) => code,
+            EvmCompilerInput::Eof(eof) => &eof.raw[..],
+        };
+        // Destructure so that new fields have to be considered here.
+        let FcxConfig {
+            comments: _,
+            debug_assertions,
+            debug_info,
+            frame_pointers,
+            validate_eof,
+            local_stack,
+            inspect_stack_length,
+            inspect_instructions,
+            stack_bound_checks,
+            gas_metering,
+            section_gas_metering,
+            storage_cache,
+            eof_section_functions,
+        } = self.config;
+        let config = [
+            debug_assertions,
+            debug_info,
+            frame_pointers,
+            validate_eof,
+            local_stack,
+            inspect_stack_length,
+            inspect_instructions,
+            stack_bound_checks,
+            gas_metering,
+            section_gas_metering,
+            storage_cache,
+            eof_section_functions,
+        ]
+        .map(u8::from);
+        CacheKey::from_parts([
+            &keccak256(code)[..],
+            &[spec_id as u8, self.opt_level() as u8][..],
+            &config[..],
+            self.backend.codegen_version().as_bytes(),
+            self.backend.target_triple().as_bytes(),
+            self.backend.target_cpu().as_bytes(),
+            self.backend.target_features().as_bytes(),
+        ])
+    }
+
     /// (AOT) Writes the compiled object to the given file.
-    pub fn write_object_to_file(&mut self, path: &Path) -> Result<()> {
-        let file = fs::File::create(path)?;
+    pub fn write_object_to_file(&mut self, path: &Path) -> Result<(), CompileError> {
+        let emit = |e: io::Error| CompileError::Emit(e.into());
+        let file = fs::File::create(path).map_err(emit)?;
         let mut writer = io::BufWriter::new(file);
         self.write_object(&mut writer)?;
-        writer.flush()?;
-        Ok(())
+        writer.flush().map_err(emit)
     }
 
     /// (AOT) Finalizes the module and writes the compiled object to the given writer.
-    pub fn write_object<W: io::Write>(&mut self, w: W) -> Result<()> {
-        ensure!(self.is_aot(), "cannot write AOT object during JIT compilation");
+    pub fn write_object<W: io::Write>(&mut self, w: W) -> Result<(), CompileError> {
+        ensure_usage!(self.is_aot(), "cannot write AOT object during JIT compilation");
         self.finalize()?;
-        self.backend.write_object(w)
+        self.backend.write_object(w).map_err(CompileError::Emit)
     }
 
     /// (JIT) Frees the memory associated with a single function.
@@ -295,6 +697,11 @@ impl<B: Backend> EvmCompiler<B> {
     /// Note that this will not reset the state of the internal module even if all functions are
     /// freed with this function. Use [`clear`] to reset the module.
     ///
+    /// With LLVM ORC, machine code is freed by module, and all the functions translated before the
+    /// same call to [`jit_function`](Self::jit_function) share one. Freeing only one of them
+    /// fails, as it would free the others too: use [`clear`] instead, or JIT-compile each function
+    /// before translating the next one so that it can be freed on its own.
+    ///
     /// [`clear`]: EvmCompiler::clear
     ///
     /// # Safety
@@ -302,53 +709,66 @@ impl<B: Backend> EvmCompiler<B> {
     /// Because this function invalidates any pointers retrieved from the corresponding module, it
     /// should only be used when none of the functions from that module are currently executing and
     /// none of the `fn` pointers are called afterwards.
-    pub unsafe fn free_function(&mut self, id: B::FuncId) -> Result<()> {
-        self.backend.free_function(id)
+    pub unsafe fn free_function(&mut self, id: B::FuncId) -> Result<(), CompileError> {
+        Ok(self.backend.free_function(id)?)
     }
 
-    /// Frees all functions and resets the state of the internal module, allowing for new functions
-    /// to be compiled.
+    /// Frees all functions and loaded objects, and resets the state of the internal module,
+    /// allowing for new functions to be compiled.
     ///
     /// # Safety
     ///
     /// Because this function invalidates any pointers retrieved from the corresponding module, it
     /// should only be used when none of the functions from that module are currently executing and
     /// none of the `fn` pointers are called afterwards.
-    pub unsafe fn clear(&mut self) -> Result<()> {
+    pub unsafe fn clear(&mut self) -> Result<(), CompileError> {
+        self.clear_functions()?;
+        Ok(self.backend.free_all_objects()?)
+    }
+
+    unsafe fn clear_functions(&mut self) -> Result<()> {
         self.builtins.clear();
+        if let Some(names) = &mut self.perf_map_names {
+            names.clear();
+        }
+        self.module_insts = 0;
         self.finalized = false;
         self.backend.free_all_functions()
     }
 
+    /// Parses and analyzes the given EVM bytecode.
+    ///
+    /// Unlike [`BytecodeAnalysis::new`], this respects the compiler's configuration, such as
+    /// [`validate_eof`](Self::validate_eof), and dumps the analyzed bytecode if
+    /// [enabled](Self::set_dump_to).
+    pub fn analyze<'a>(
+        &mut self,
+        input: impl Into<EvmCompilerInput<'a>>,
+        spec_id: SpecId,
+    ) -> Result<BytecodeAnalysis<'a>, CompileError> {
+        self.parse(input.into(), spec_id).map(BytecodeAnalysis::from_bytecode)
+    }
+
     /// Parses the given EVM bytecode. Not public API.
     #[doc(hidden)] // Not public API.
     pub fn parse<'a>(
         &mut self,
         input: EvmCompilerInput<'a>,
         spec_id: SpecId,
-    ) -> Result<Bytecode<'a>> {
-        let bytecode;
-        let eof;
-        match input {
-            EvmCompilerInput::Code(code) => {
-                bytecode = code;
-                if spec_id.is_enabled_in(SpecId::PRAGUE_EOF) && code.starts_with(&EOF_MAGIC_BYTES) {
-                    eof = Some(Cow::Owned(Eof::decode(Bytes::copy_from_slice(code))?));
-                } else {
-                    eof = None;
-                }
-            }
-            EvmCompilerInput::Eof(e) => {
-                bytecode = &e.raw[..];
-                eof = Some(Cow::Borrowed(e));
-            }
+    ) -> Result<Bytecode<'a>, CompileError> {
+        self.budget.check_bytecode_size(input.len())?;
+        if matches!(input, EvmCompilerInput::Eof(_)) && !spec_id.is_enabled_in(SpecId::PRAGUE_EOF) {
+            return Err(CompileError::UnsupportedSpec(spec_id));
         }
+        let (bytecode, eof) = input.decode(spec_id).map_err(CompileError::InvalidBytecode)?;
         if let Some(eof) = &eof {
-            self.do_validate_eof(eof)?;
+            self.do_validate_eof(eof).map_err(CompileError::InvalidBytecode)?;
         }
 
         let mut bytecode = Bytecode::new(bytecode, eof, spec_id);
-        bytecode.analyze()?;
+        self.budget.check_instructions(bytecode.num_insts())?;
+        bytecode.analyze().map_err(CompileError::InvalidBytecode)?;
+        self.budget.check_dynamic_jump_targets(bytecode.num_dynamic_jump_targets())?;
         if let Some(dump_dir) = &self.dump_dir() {
             Self::dump_bytecode(dump_dir, &bytecode)?;
         }
@@ -359,43 +779,112 @@ impl<B: Backend> EvmCompiler<B> {
         if !self.config.validate_eof {
             return Ok(());
         }
-        revm_interpreter::analysis::validate_eof_inner(eof, None).map_err(|e| match e {
-            revm_interpreter::analysis::EofError::Decode(e) => e.into(),
-            revm_interpreter::analysis::EofError::Validation(e) => {
-                eyre!("validation error: {e:?}")
-            }
-        })
+        validate_eof(eof)
+    }
+
+    /// Checks that the target lays out the structs accessed by translated code in the same way as
+    /// the host, since their field offsets are computed on the host.
+    fn check_target(&self) -> Result<(), CompileError> {
+        if !cfg!(target_endian = "little") {
+            return Err(CompileError::UnsupportedTarget("only little-endian is supported".into()));
+        }
+        let host = TargetDataLayout::host();
+        let target = self.backend.target_data_layout();
+        if target != host {
+            return Err(CompileError::UnsupportedTarget(format!(
+                "the data layout of `{}` {target:?} does not match the host's {host:?}",
+                self.backend.target_triple(),
+            )));
+        }
+        Ok(())
     }
 
     #[instrument(name = "translate", level = "debug", skip_all)]
-    fn translate_inner(&mut self, name: &str, bytecode: &Bytecode<'_>) -> Result<B::FuncId> {
-        ensure!(self.backend.function_name_is_unique(name), "function name `{name}` is not unique");
+    fn translate_inner(
+        &mut self,
+        name: &str,
+        bytecode: &Bytecode<'_>,
+    ) -> Result<B::FuncId, CompileError> {
+        ensure_usage!(
+            self.backend.function_name_is_unique(name),
+            "function name `{name}` is not unique"
+        );
         let linkage = Linkage::Public;
-        let (bcx, id) = Self::make_builder(&mut self.backend, &self.config, name, linkage)?;
-        FunctionCx::translate(bcx, self.config, &mut self.builtins, bytecode)?;
+        let debug_file = if self.config.debug_info {
+            Some(self.write_debug_source(name, bytecode)?)
+        } else {
+            None
+        };
+        let eof_section = (self.config.eof_section_functions
+            && bytecode.supports_eof_section_functions())
+        .then(|| EofSectionFunction { entry: name.to_string(), section: 0 });
+
+        let (mut bcx, id) =
+            Self::make_builder(&mut self.backend, &self.config, name, linkage, false)?;
+        if let Some(debug_file) = &debug_file {
+            bcx.set_debug_file(debug_file);
+        }
+        FunctionCx::translate(bcx, self.config, &mut self.builtins, bytecode, eof_section.clone())?;
+        self.module_insts += bytecode.num_insts();
+
+        // Translate the other EOF code sections into their own functions, called by the entry one.
+        if let Some(entry) = eof_section {
+            for section in 1..bytecode.expect_eof().body.code_section.len() {
+                let eof_section = EofSectionFunction { section, ..entry.clone() };
+                let name = eof_section.function_name(section);
+                let (mut bcx, _) = Self::make_builder(
+                    &mut self.backend,
+                    &self.config,
+                    &name,
+                    Linkage::Private,
+                    true,
+                )?;
+                if let Some(debug_file) = &debug_file {
+                    bcx.set_debug_file(debug_file);
+                }
+                FunctionCx::translate(
+                    bcx,
+                    self.config,
+                    &mut self.builtins,
+                    bytecode,
+                    Some(eof_section),
+                )?;
+            }
+        }
+
+        if let Some(names) = &mut self.perf_map_names {
+            names.insert(id, name.to_string());
+        }
         Ok(id)
     }
 
     #[instrument(level = "debug", skip_all)]
-    fn finalize(&mut self) -> Result<()> {
+    fn finalize(&mut self) -> Result<(), CompileError> {
         if self.finalized {
             return Ok(());
         }
         self.finalized = true;
+        let res = self.finalize_inner();
+        if res.is_err() {
+            self.finalized = false;
+        }
+        res
+    }
 
+    fn finalize_inner(&mut self) -> Result<(), CompileError> {
         if let Some(dump_dir) = &self.dump_dir() {
             let path = dump_dir.join("unopt").with_extension(self.backend.ir_extension());
             self.dump_ir(&path)?;
 
             // Dump IR before verifying for better debugging.
-            self.verify_module()?;
+            self.verify_module().map_err(CompileError::Verify)?;
 
             if self.dump_assembly && self.dump_unopt_assembly {
                 let path = dump_dir.join("unopt.s");
                 self.dump_disasm(&path)?;
             }
         } else {
-            self.verify_module()?;
+            self.verify_module().map_err(CompileError::Verify)?;
         }
 
         self.optimize_module()?;
@@ -419,6 +908,7 @@ impl<B: Backend> EvmCompiler<B> {
         config: &FcxConfig,
         name: &str,
         linkage: Linkage,
+        eof_section: bool,
     ) -> Result<(B::Builder<'a>, B::FuncId)> {
         fn size_align<T>(i: usize) -> (usize, usize, usize) {
             (i, mem::size_of::<T>(), mem::align_of::<T>())
@@ -446,11 +936,21 @@ impl<B: Backend> EvmCompiler<B> {
                 size_align::<EvmContext<'_>>(5),
             ],
         );
+        // The functions of EOF code sections are also passed the depth of the function stack.
+        let mut params = params.to_vec();
+        let mut param_names = param_names.to_vec();
+        if eof_section {
+            params.push(backend.type_ptr_sized_int());
+            param_names.push("arg.eof_depth");
+        }
         debug_assert_eq!(params.len(), param_names.len());
-        let (mut bcx, id) = backend.build_function(name, ret, params, param_names, linkage)?;
+        let (mut bcx, id) = backend.build_function(name, ret, &params, &param_names, linkage)?;
 
         // Function attributes.
+        // The functions of EOF code sections may call themselves and each other through `CALLF`.
+        let may_recurse = eof_section || config.eof_section_functions;
         let function_attributes = default_attrs::for_fn()
+            .filter(|&attr| !(may_recurse && attr == Attribute::NoRecurse))
             .chain(config.frame_pointers.then_some(Attribute::AllFramePointers))
             // We can unwind in panics, which are present only in debug assertions.
             .chain((!config.debug_assertions).then_some(Attribute::NoUnwind));
@@ -490,8 +990,28 @@ impl<B: Backend> EvmCompiler<B> {
     }
 
     #[instrument(level = "debug", skip_all)]
-    fn optimize_module(&mut self) -> Result<()> {
-        self.backend.optimize_module()
+    fn optimize_module(&mut self) -> Result<(), CompileError> {
+        let insts = mem::take(&mut self.module_insts);
+        let opt_level = self.backend.opt_level();
+        let downgrade = opt_level != OptimizationLevel::None
+            && self.budget.exceeds_optimization_time(insts, self.optimization_time_per_inst);
+        self.optimization_skipped = downgrade;
+        if downgrade {
+            debug!(insts, "optimization is projected to exceed the budget, not optimizing");
+            self.backend.set_opt_level(OptimizationLevel::None);
+        }
+
+        let start = Instant::now();
+        let res = self.backend.optimize_module();
+        let elapsed = start.elapsed();
+
+        if downgrade {
+            self.backend.set_opt_level(opt_level);
+        } else if insts != 0 {
+            self.optimization_time_per_inst = Some(elapsed / insts.try_into().unwrap_or(u32::MAX));
+        }
+        res?;
+        Ok(self.budget.check_optimization_time(elapsed)?)
     }
 
     #[instrument(level = "debug", skip_all)]
@@ -510,9 +1030,38 @@ impl<B: Backend> EvmCompiler<B> {
             writer.flush()?;
         }
 
+        let cfg = bytecode.cfg();
+        {
+            let file = fs::File::create(dump_dir.join("cfg.dot"))?;
+            let mut writer = io::BufWriter::new(file);
+            cfg.write_dot(bytecode, &mut writer)?;
+            writer.flush()?;
+        }
+
+        {
+            let file = fs::File::create(dump_dir.join("cfg.json"))?;
+            let mut writer = io::BufWriter::new(file);
+            cfg.write_json(bytecode, &mut writer)?;
+            writer.flush()?;
+        }
+
         Ok(())
     }
 
+    /// Returns the path of the debug info source file of the given function, writing it to the
+    /// dump directory if enabled.
+    fn write_debug_source(&self, name: &str, bytecode: &Bytecode<'_>) -> Result<PathBuf> {
+        let file_name = format!("{}.evm", name.replace(char::is_whitespace, "_"));
+        let Some(dump_dir) = self.dump_dir() else { return Ok(PathBuf::from(file_name)) };
+        let path = dump_dir.join(file_name);
+        let file = fs::File::create(&path)?;
+        let mut writer = io::BufWriter::new(file);
+        bytecode.write_debug_source(&mut writer)?;
+        writer.flush()?;
+        // Debuggers resolve relative paths from the current directory.
+        Ok(path.canonicalize().unwrap_or(path))
+    }
+
     fn dump_dir(&self) -> Option<PathBuf> {
         let mut dump_dir = self.out_dir.clone()?;
         if let Some(name) = &self.name {
@@ -525,7 +1074,55 @@ impl<B: Backend> EvmCompiler<B> {
     }
 }
 
+/// Returns the path of the perf map file of the current process.
+pub(crate) fn perf_map_path() -> PathBuf {
+    PathBuf::from(format!("/tmp/perf-{}.map", std::process::id()))
+}
+
+/// Appends an entry for a JIT-compiled function to the [perf map file](perf_map_path).
+///
+/// See <https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jit-interface.txt>.
+fn write_perf_map_entry(addr: usize, size: usize, name: &str) -> io::Result<()> {
+    // Shared by all the compilers in the process.
+    static FILE: Mutex<Option<fs::File>> = Mutex::new(None);
+    let mut file = FILE.lock().unwrap_or_else(PoisonError::into_inner);
+    let file = match &mut *file {
+        Some(file) => file,
+        None => file.insert(fs::File::options().create(true).append(true).open(perf_map_path())?),
+    };
+    let name = name.replace(char::is_whitespace, "_");
+    file.write_all(format!("{addr:x} {size:x} {name}\n").as_bytes())
+}
+
+/// Validates the given EOF container.
+pub(crate) fn validate_eof(eof: &Eof) -> Result<()> {
+    revm_interpreter::analysis::validate_eof_inner(eof, None).map_err(|e| match e {
+        revm_interpreter::analysis::EofError::Decode(e) => e.into(),
+        revm_interpreter::analysis::EofError::Validation(e) => eyre!("validation error: {e:?}"),
+    })
+}
+
+/// A contract of a set compiled with [`EvmCompiler::translate_contract_set`].
+#[derive(Clone, Copy)]
+#[allow(missing_debug_implementations)]
+pub struct AotContract<'a> {
+    /// The name of the function. See [`EvmCompiler::translate`].
+    pub name: &'a str,
+    /// The bytecode.
+    pub input: EvmCompilerInput<'a>,
+    /// The spec ID to compile the bytecode for.
+    pub spec_id: SpecId,
+}
+
+impl<'a> AotContract<'a> {
+    /// Creates a new contract.
+    pub fn new(name: &'a str, input: impl Into<EvmCompilerInput<'a>>, spec_id: SpecId) -> Self {
+        Self { name, input: input.into(), spec_id }
+    }
+}
+
 /// [`EvmCompiler`] input.
+#[derive(Clone, Copy)]
 #[allow(missing_debug_implementations)]
 pub enum EvmCompilerInput<'a> {
     /// EVM bytecode. Can also be raw EOF code, which will be parsed.
@@ -534,6 +1131,38 @@ pub enum EvmCompilerInput<'a> {
     Eof(&'a Eof),
 }
 
+impl<'a> EvmCompilerInput<'a> {
+    /// Returns the size of the input, in bytes.
+    pub(crate) fn len(&self) -> usize {
+        self.raw().len()
+    }
+
+    /// Returns the raw bytecode, which is the whole container for EOF.
+    pub(crate) fn raw(&self) -> &'a [u8] {
+        match *self {
+            EvmCompilerInput::Code(code) => code,
+            EvmCompilerInput::Eof(eof) => &eof.raw[..],
+        }
+    }
+
+    /// Returns the raw bytecode and the EOF container, decoding it if necessary.
+    pub(crate) fn decode(self, spec_id: SpecId) -> Result<(&'a [u8], Option<Cow<'a, Eof>>)> {
+        match self {
+            EvmCompilerInput::Code(code) => {
+                let eof = if spec_id.is_enabled_in(SpecId::PRAGUE_EOF)
+                    && code.starts_with(&EOF_MAGIC_BYTES)
+                {
+                    Some(Cow::Owned(Eof::decode(Bytes::copy_from_slice(code))?))
+                } else {
+                    None
+                };
+                Ok((code, eof))
+            }
+            EvmCompilerInput::Eof(eof) => Ok((&eof.raw[..], Some(Cow::Borrowed(eof)))),
+        }
+    }
+}
+
 impl<'a> From<&'a [u8]> for EvmCompilerInput<'a> {
     fn from(code: &'a [u8]) -> Self {
         EvmCompilerInput::Code(code)
@@ -2,15 +2,17 @@
 
 use super::default_attrs;
 use crate::{
-    Backend, Builder, Bytecode, EvmContext, Inst, InstData, InstFlags, IntCC, Result, I256_MIN,
+    Backend, Builder, Bytecode, EvmContext, Inst, InstData, InstFlags, IntCC, Result,
+    StorageCacheEntry, I256_MIN,
 };
 use revm_interpreter::{
-    opcode as op, Contract, FunctionReturnFrame, FunctionStack, InstructionResult,
+    gas, opcode as op, Contract, FunctionReturnFrame, FunctionStack, InstructionResult,
     OPCODE_INFO_JUMPTABLE,
 };
 use revm_primitives::{BlockEnv, CfgEnv, Env, Eof, SpecId, TxEnv, U256};
 use revmc_backend::{
-    eyre::ensure, Attribute, BackendTypes, FunctionAttributeLocation, Pointer, TypeMethods,
+    eyre::ensure, Attribute, BackendTypes, FunctionAttributeLocation, Linkage, Pointer,
+    TailCallKind, TypeMethods,
 };
 use revmc_builtins::{Builtin, Builtins, CallKind, CreateKind, ExtCallKind, EXTCALL_LIGHT_FAILURE};
 use std::{fmt::Write, mem, sync::atomic::AtomicPtr};
@@ -22,13 +24,18 @@ const STACK_CAP: usize = 1024;
 pub(super) struct FcxConfig {
     pub(super) comments: bool,
     pub(super) debug_assertions: bool,
+    pub(super) debug_info: bool,
     pub(super) frame_pointers: bool,
     pub(super) validate_eof: bool,
 
     pub(super) local_stack: bool,
     pub(super) inspect_stack_length: bool,
+    pub(super) inspect_instructions: bool,
     pub(super) stack_bound_checks: bool,
     pub(super) gas_metering: bool,
+    pub(super) section_gas_metering: bool,
+    pub(super) storage_cache: bool,
+    pub(super) eof_section_functions: bool,
 }
 
 impl Default for FcxConfig {
@@ -36,12 +43,17 @@ impl Default for FcxConfig {
         Self {
             debug_assertions: cfg!(debug_assertions),
             comments: false,
+            debug_info: false,
             frame_pointers: cfg!(debug_assertions),
             validate_eof: true,
             local_stack: false,
             inspect_stack_length: false,
+            inspect_instructions: false,
             stack_bound_checks: true,
             gas_metering: true,
+            section_gas_metering: true,
+            storage_cache: false,
+            eof_section_functions: false,
         }
     }
 }
@@ -50,9 +62,30 @@ impl Default for FcxConfig {
 type Incoming<B> = Vec<(<B as BackendTypes>::Value, <B as BackendTypes>::BasicBlock)>;
 
 /// A list of `switch` targets.
-#[allow(dead_code)]
 type SwitchTargets<B> = Vec<(u64, <B as BackendTypes>::BasicBlock)>;
 
+/// An EOF code section that is translated into its own function.
+///
+/// See [`EvmCompiler::eof_section_functions`](crate::EvmCompiler::eof_section_functions).
+#[derive(Clone, Debug)]
+pub(super) struct EofSectionFunction {
+    /// The name of the entry function, which the names of the other functions are derived from.
+    pub(super) entry: String,
+    /// The index of the code section.
+    pub(super) section: usize,
+}
+
+impl EofSectionFunction {
+    /// Returns the name of the function of the given code section.
+    pub(super) fn function_name(&self, section: usize) -> String {
+        if section == 0 {
+            self.entry.clone()
+        } else {
+            format!("{}.section{section}", self.entry)
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum ResumeKind {
     /// Use `indirectbr`.
@@ -83,6 +116,8 @@ pub(super) struct FunctionCx<'a, B: Backend> {
     stack: Pointer<B::Builder<'a>>,
     /// The amount of gas remaining. `i64`. See `Gas`.
     gas_remaining: Pointer<B::Builder<'a>>,
+    /// The amount of gas refunded. `i64`. See `Gas`.
+    gas_refunded: Pointer<B::Builder<'a>>,
     /// The environment. Constant throughout the function.
     env: B::Value,
     /// The contract. Constant throughout the function.
@@ -93,13 +128,29 @@ pub(super) struct FunctionCx<'a, B: Backend> {
     len_before: B::Value,
     /// Stack length offset for the current instruction, used for push/pop.
     len_offset: i8,
+    /// The stack length difference of the current instruction.
+    len_diff: i64,
+    /// Stack values that are kept in SSA registers instead of being stored to `stack`, as
+    /// `(offset, value)` pairs where `offset` is relative to `len_before`.
+    ///
+    /// These are spilled before instructions marked with `SPILL_STACK` use the stack pointer or
+    /// leave the current section. See `StackAnalysis`.
+    stack_values: Vec<(i64, B::Value)>,
 
     /// The bytecode being translated.
     bytecode: &'a Bytecode<'a>,
+    /// The EOF code section being translated, if each code section is its own function.
+    eof_section: Option<EofSectionFunction>,
+    /// The number of `CALLF` frames below the current function, if `eof_section` is set. `isize`.
+    /// Passed in the arguments, except for the entry function where it is `0`.
+    eof_depth: B::Value,
     /// All entry blocks for each instruction.
     inst_entries: Vec<B::BasicBlock>,
     /// The current instruction being translated.
     current_inst: Inst,
+    /// The static gas that was paid in advance at the start of the current section but is not
+    /// spent if the current instruction fails. It is refunded on failure.
+    unspent_gas: u64,
 
     // Basic blocks are `None` when outside of a main function.
     /// `dynamic_jump_table` incoming values.
@@ -184,14 +235,49 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
     ///     }
     /// }
     /// ```
+    ///
+    /// If `eof_section` is set, only the instructions of the given EOF code section are translated.
+    /// The functions of the other code sections take an additional `eof_depth: usize` argument and
+    /// operate directly on the caller's stack and stack length, so they don't set up the stack
+    /// length or resume execution:
+    ///
+    /// ```ignore (pseudo-code)
+    /// fn evm_bytecode.section1(args: ..., eof_depth: usize) {
+    ///     op.inst0: { /* ... */ };
+    ///     // ...
+    ///     op.callf: {
+    ///         if eof_depth >= 1024 { goto return(InstructionResult::EOFFunctionStackOverflow) };
+    ///         let ir = evm_bytecode.section2(args..., eof_depth + 1);
+    ///         if ir != InstructionResult::Continue { goto return(ir) };
+    ///         goto next_inst;
+    ///     };
+    ///     op.jumpf: {
+    ///         return evm_bytecode.section3(args..., eof_depth);
+    ///     };
+    ///     op.retf: {
+    ///         goto return(InstructionResult::Continue);
+    ///     };
+    ///
+    ///     return(ir: InstructionResult): {
+    ///         return ir;
+    ///     }
+    /// }
+    /// ```
     #[allow(rustdoc::invalid_rust_codeblocks)] // Syntax highlighting.
     pub(super) fn translate(
         mut bcx: B::Builder<'a>,
         config: FcxConfig,
         builtins: &'a mut Builtins<B>,
         bytecode: &'a Bytecode<'a>,
+        eof_section: Option<EofSectionFunction>,
     ) -> Result<()> {
         let entry_block = bcx.current_block().unwrap();
+        // The functions of code sections other than the first one are called by other functions.
+        let is_callee = eof_section.as_ref().is_some_and(|f| f.section != 0);
+        let insts = match &eof_section {
+            Some(f) => bytecode.eof_section_insts(f.section),
+            None => 0..bytecode.num_insts(),
+        };
 
         // Get common types.
         let ptr_type = bcx.type_ptr();
@@ -208,28 +294,38 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
             let name = "gas.remaining.addr";
             Pointer::new_address(i64_type, bcx.gep(i8_type, gas_ptr, &[offset], name))
         };
+        let gas_refunded = {
+            let offset = bcx.iconst(i64_type, mem::offset_of!(pf::Gas, refunded) as i64);
+            let name = "gas.refunded.addr";
+            Pointer::new_address(i64_type, bcx.gep(i8_type, gas_ptr, &[offset], name))
+        };
 
         let sp_arg = bcx.fn_param(1);
-        let stack = if config.local_stack {
+        let stack = if config.local_stack && !is_callee {
             bcx.new_stack_slot(word_type, "stack.addr")
         } else {
             Pointer::new_address(word_type, sp_arg)
         };
 
         let stack_len_arg = bcx.fn_param(2);
-        // This is initialized later in `post_entry_block`.
-        let stack_len = bcx.new_stack_slot(isize_type, "len.addr");
+        let stack_len = if is_callee {
+            Pointer::new_address(isize_type, stack_len_arg)
+        } else {
+            // This is initialized later in `post_entry_block`.
+            bcx.new_stack_slot(isize_type, "len.addr")
+        };
 
         let env = bcx.fn_param(3);
         let contract = bcx.fn_param(4);
         let ecx = bcx.fn_param(5);
+        let eof_depth = if is_callee { bcx.fn_param(6) } else { bcx.iconst(isize_type, 0) };
 
         // Create all instruction entry blocks.
         let unreachable_block = bcx.create_block("unreachable");
         let inst_entries: Vec<_> = bytecode
             .iter_all_insts()
             .map(|(i, data)| {
-                if data.is_dead_code() {
+                if data.is_dead_code() || !insts.contains(&i) {
                     unreachable_block
                 } else {
                     bcx.create_block(&bytecode.op_block_name(i, ""))
@@ -254,16 +350,22 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
             stack_len,
             stack,
             gas_remaining,
+            gas_refunded,
             env,
             contract,
             ecx,
             len_before: bcx.iconst(isize_type, 0),
             len_offset: 0,
+            len_diff: 0,
+            stack_values: Vec::new(),
             bcx,
 
             bytecode,
+            eof_section,
+            eof_depth,
             inst_entries,
             current_inst: usize::MAX,
+            unspent_gas: 0,
 
             incoming_dynamic_jumps: Vec::new(),
             dynamic_jump_table,
@@ -282,10 +384,13 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         };
 
         // We store the stack length if requested or necessary due to the bytecode.
-        let stack_length_observable = config.inspect_stack_length || bytecode.may_suspend();
+        // Callees store it directly to the caller's.
+        let stack_length_observable =
+            (config.inspect_stack_length || bytecode.may_suspend()) && !is_callee;
 
         // Add debug assertions for the parameters.
-        if config.debug_assertions {
+        // Callees are only called with the arguments of the entry function, which are checked.
+        if config.debug_assertions && !is_callee {
             fx.pointer_panic_with_bool(
                 config.gas_metering,
                 gas_ptr,
@@ -314,14 +419,21 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         }
 
         // The bytecode is guaranteed to have at least one instruction.
-        let first_inst_block = fx.inst_entries[0];
+        let first_inst_block = fx.inst_entries[insts.start];
         let post_entry_block = fx.bcx.create_block_after(entry_block, "entry.post");
         let resume_block = fx.bcx.create_block_after(post_entry_block, "resume");
         fx.bcx.br(post_entry_block);
 
         // Translate individual instructions into their respective blocks.
         for (inst, _) in bytecode.iter_insts() {
-            fx.translate_inst(inst)?;
+            if insts.contains(&inst) {
+                fx.translate_inst(inst)?;
+            }
+        }
+        fx.unspent_gas = 0;
+        if config.debug_info {
+            // The rest of the function does not belong to any instruction.
+            fx.bcx.set_debug_location(0);
         }
 
         // Finalize the dynamic jump table.
@@ -330,7 +442,9 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         if bytecode.has_dynamic_jumps() {
             fx.bcx.switch_to_block(fx.dynamic_jump_table);
             // TODO: Manually reduce to i32?
-            let jumpdests = bytecode.iter_insts().filter(|(_, data)| data.opcode == op::JUMPDEST);
+            let jumpdests = bytecode
+                .iter_insts()
+                .filter(|(_, data)| data.flags.contains(InstFlags::DYNAMIC_JUMPDEST));
             // let max_pc =
             //     jumpdests.clone().map(|(_, data)| data.pc).next_back().expect("no jumpdests");
             let targets = jumpdests
@@ -357,14 +471,17 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         // Also here is where the stack length is initialized.
         let load_len_at_start = |fx: &mut Self| {
             // Loaded from args only for the config.
-            if config.inspect_stack_length {
+            if is_callee {
+                // Owned by the caller.
+            } else if config.inspect_stack_length {
                 let stack_len = fx.bcx.load(fx.isize_type, stack_len_arg, "stack_len");
                 fx.stack_len.store(&mut fx.bcx, stack_len);
             } else {
                 fx.stack_len.store_imm(&mut fx.bcx, 0);
             }
         };
-        let generate_resume = bytecode.may_suspend();
+        // Callees don't suspend, and the entry function only resumes at its own instructions.
+        let generate_resume = bytecode.may_suspend() && !is_callee;
         if generate_resume {
             let get_ecx_resume_at_ptr = |fx: &mut Self| {
                 fx.get_field(
@@ -409,10 +526,6 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                     }
                     ResumeKind::Indexes => {
                         let default = fx.bcx.create_block_after(resume_block, "resume_invalid");
-                        fx.bcx.switch_to_block(default);
-                        fx.call_panic("invalid `resume_at` value");
-
-                        fx.bcx.switch_to_block(resume_block);
                         let targets = fx
                             .resume_blocks
                             .iter()
@@ -420,6 +533,9 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                             .map(|(i, b)| (i as u64 + 1, *b))
                             .collect::<Vec<_>>();
                         fx.bcx.switch(resume_at, default, &targets, true);
+
+                        fx.bcx.switch_to_block(default);
+                        fx.call_panic("invalid `resume_at` value");
                     }
                 }
             }
@@ -481,6 +597,18 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         let opcode = data.opcode;
         let entry_block = self.inst_entries[inst];
         self.bcx.switch_to_block(entry_block);
+        if self.config.debug_info {
+            // See `Bytecode::write_debug_source`.
+            self.bcx.set_debug_location(data.pc + 1);
+        }
+
+        // Make the stack values kept in registers relative to the stack length before this
+        // instruction.
+        let len_diff = mem::take(&mut self.len_diff);
+        for (offset, _) in &mut self.stack_values {
+            *offset -= len_diff;
+        }
+        self.len_before = self.stack_len.load(&mut self.bcx, "stack_len");
 
         let is_eof = self.bytecode.is_eof();
         let is_eof_enabled = self.bytecode.spec_id.is_enabled_in(SpecId::PRAGUE_EOF);
@@ -495,6 +623,9 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                 !this.bytecode.is_instr_diverging(inst),
                 "attempted to branch to next instruction in a diverging instruction: {data:?}",
             );
+            if data.flags.contains(InstFlags::SPILL_STACK) {
+                this.spill_stack();
+            }
             if let Some(next) = this.inst_entries.get(inst + 1) {
                 this.bcx.br(*next);
             }
@@ -518,6 +649,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                 return Ok(());
             };
             (build $ret:expr) => {{
+                self.spill_stack();
                 self.build_return_imm($ret);
                 goto_return!(no_branch);
             }};
@@ -534,6 +666,18 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         // Assert that we already skipped the block.
         debug_assert!(!data.flags.contains(InstFlags::DEAD_CODE));
 
+        // Failing before paying the gas of the current section refunds the static gas that was paid
+        // in advance for this instruction, unless it starts the section.
+        self.unspent_gas = if self.pays_section_gas() && data.section.is_empty() {
+            self.bytecode.unspent_section_gas(inst) + data.base_gas as u64
+        } else {
+            0
+        };
+
+        if self.config.inspect_instructions {
+            self.inspect_inst(data);
+        }
+
         #[cfg(test)]
         if opcode == crate::TEST_SUSPEND {
             self.suspend();
@@ -578,8 +722,15 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
             }
         }
 
-        // Pay static gas for the current section.
-        self.gas_cost_imm(data.section.gas_cost as u64);
+        // Pay static gas for the current section, or only for the current instruction.
+        if self.config.section_gas_metering {
+            self.gas_cost_imm(data.section.gas_cost as u64);
+        } else {
+            self.gas_cost_imm(data.base_gas as u64);
+        }
+        if self.pays_section_gas() {
+            self.unspent_gas = self.bytecode.unspent_section_gas(inst);
+        }
 
         if data.flags.contains(InstFlags::SKIP_LOGIC) {
             goto_return!("skipped");
@@ -587,7 +738,6 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
 
         // Reset the stack length offset for this instruction.
         self.len_offset = 0;
-        self.len_before = self.stack_len.load(&mut self.bcx, "stack_len");
 
         // Check stack length for the current section.
         // Skip doing this for EOF bytecode, as it is done at deploy time.
@@ -625,7 +775,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                         self.bcx.iconst(self.i8_type, InstructionResult::StackOverflow as i64);
                     self.bcx.select(underflow, under, over)
                 };
-                let target = self.build_check_inner(true, cond, ret);
+                let target = self.build_check_inner(true, cond, ret, self.unspent_gas);
                 self.bcx.switch_to_block(target);
             } else if may_underflow {
                 let cond = underflow(self);
@@ -650,6 +800,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                 }
                 let len_changed = self.bcx.iadd_imm(self.len_before, diff);
                 self.stack_len.store(&mut self.bcx, len_changed);
+                self.len_diff = diff;
             }
         }
 
@@ -724,6 +875,10 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         }
 
         match data.opcode {
+            _ if data.flags.contains(InstFlags::NARROW) => {
+                self.build_narrow_op(opcode, data.input_bits());
+            }
+
             op::STOP => goto_return!(build InstructionResult::Stop),
 
             op::ADD => binop!(iadd),
@@ -810,6 +965,9 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                 self.bcx.select(is_negative, max, zero)
             }),
 
+            op::KECCAK256 if data.flags.contains(InstFlags::CONST_LEN) => {
+                self.build_keccak256_const(data.data as u64);
+            }
             op::KECCAK256 => {
                 let sp = self.sp_after_inputs();
                 self.call_fallible_builtin(Builtin::Keccak256, &[self.ecx, sp]);
@@ -914,35 +1072,50 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                 let _ = self.call_builtin(Builtin::BlobHash, &[self.ecx, sp]);
             }
             op::BLOBBASEFEE => {
-                let len = self.len_before();
-                let slot = self.sp_at(len);
+                let slot = self.sp_at_top();
                 let _ = self.call_builtin(Builtin::BlobBaseFee, &[self.ecx, slot]);
             }
 
             op::POP => { /* Already handled in stack_io */ }
             op::MLOAD => {
                 let offset = self.pop();
-                let value = self.call_mload(offset);
+                let value = self.build_mload(offset);
                 self.push(value);
             }
             op::MSTORE => {
                 let [offset, value] = self.popn();
-                self.call_mstore(offset, value);
+                self.build_mstore(offset, value);
             }
             op::MSTORE8 => {
                 let [offset, value] = self.popn();
                 let value = self.bcx.ireduce(self.i8_type, value);
-                self.call_mstore8(offset, value);
+                self.build_mstore8(offset, value);
             }
             op::SLOAD => {
                 let sp = self.sp_after_inputs();
-                let spec_id = self.const_spec_id();
-                self.call_fallible_builtin(Builtin::Sload, &[self.ecx, sp, spec_id]);
+                if self.uses_storage_cache() {
+                    self.sload_cached(sp);
+                } else {
+                    let spec_id = self.const_spec_id();
+                    self.call_fallible_builtin(Builtin::Sload, &[self.ecx, sp, spec_id]);
+                }
             }
             op::SSTORE => {
                 let sp = self.sp_after_inputs();
-                let spec_id = self.const_spec_id();
-                self.call_fallible_builtin(Builtin::Sstore, &[self.ecx, sp, spec_id]);
+                if self.uses_storage_cache() {
+                    self.sstore_cached(sp);
+                } else {
+                    let spec_id = self.const_spec_id();
+                    self.call_fallible_builtin(Builtin::Sstore, &[self.ecx, sp, spec_id]);
+                }
+            }
+            // Inspected instructions must be executed one by one.
+            op::JUMPI
+                if data.flags.contains(InstFlags::DISPATCH)
+                    && !self.config.inspect_instructions =>
+            {
+                self.build_dispatcher();
+                goto_return!(no_branch);
             }
             op::JUMP | op::JUMPI => {
                 let is_invalid = data.flags.contains(InstFlags::INVALID_JUMP);
@@ -957,6 +1130,10 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                         self.len_offset -= 1;
                         self.return_block.unwrap()
                     } else if data.flags.contains(InstFlags::STATIC_JUMP) {
+                        if data.flags.contains(InstFlags::RESOLVED_JUMP) {
+                            // The target is on the stack, but it is already known.
+                            self.len_offset -= 1;
+                        }
                         let target_inst = data.data as usize;
                         debug_assert_eq!(
                             *self.bytecode.inst(target_inst),
@@ -977,11 +1154,13 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                         let cond_word = self.pop();
                         let cond = self.bcx.icmp_imm(IntCC::NotEqual, cond_word, 0);
                         let next = self.inst_entries[inst + 1];
+                        self.spill_stack();
                         if target == self.return_block.unwrap() {
                             self.add_invalid_jump();
                         }
                         self.bcx.brif(cond, target, next);
                     } else {
+                        self.spill_stack();
                         self.bcx.br(target);
                     }
                     self.inst_entries[inst] = self.bcx.current_block().unwrap();
@@ -1066,11 +1245,13 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                 let (_, target_inst) = self.bytecode.iter_rjump_target_insts(data).next().unwrap();
                 let target = self.inst_entries[target_inst];
                 if opcode == op::RJUMP {
+                    self.spill_stack();
                     self.bcx.br(target);
                 } else {
                     let next = self.inst_entries[inst + 1];
                     let value = self.pop();
                     let cond = self.bcx.icmp_imm(IntCC::NotEqual, value, 0);
+                    self.spill_stack();
                     self.bcx.brif(cond, target, next);
                 }
                 goto_return!(no_branch);
@@ -1083,6 +1264,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                     .iter_rjump_target_insts(data)
                     .map(|(i, inst)| (i as u64, self.inst_entries[inst]))
                     .collect::<Vec<_>>();
+                self.spill_stack();
                 self.bcx.switch(index, default, &targets, false);
                 goto_return!(no_branch);
             }
@@ -1091,16 +1273,31 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                 self.callf_common(imm, false);
                 goto_return!(no_branch);
             }
+            op::RETF if self.eof_section.is_some() => {
+                goto_return!(build InstructionResult::Continue);
+            }
             op::RETF => {
+                self.spill_stack();
                 let address = self.call_func_stack_pop();
                 let section = self.bytecode.pc_to_eof_section(data.pc as usize);
-                let destinations = self
-                    .bytecode
-                    .eof_section_called_by(section)
-                    .iter()
-                    .map(|inst| self.inst_entries[*inst + 1])
-                    .collect::<Vec<_>>();
-                self.bcx.br_indirect(address, &destinations);
+                let callers = self.bytecode.eof_section_called_by(section);
+                let destinations =
+                    callers.iter().map(|inst| self.inst_entries[*inst + 1]).collect::<Vec<_>>();
+                if destinations.first().is_some_and(|&b| self.bcx.block_addr(b).is_some()) {
+                    self.bcx.br_indirect(address, &destinations);
+                } else {
+                    // The return address is the index of the calling `CALLF` instruction.
+                    let targets = callers
+                        .iter()
+                        .zip(&destinations)
+                        .map(|(&inst, &block)| (inst as u64, block))
+                        .collect::<Vec<_>>();
+                    let default = self.create_block_after_current("retf_invalid");
+                    self.bcx.switch(address, default, &targets, true);
+
+                    self.bcx.switch_to_block(default);
+                    self.call_panic("invalid `RETF` return address");
+                }
                 goto_return!(no_branch);
             }
             op::JUMPF => {
@@ -1210,17 +1407,12 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
     }
 
     /// Pushes 256-bit values onto the stack.
+    ///
+    /// The values are kept in registers until they are spilled.
     fn pushn(&mut self, values: &[B::Value]) {
-        let len_start = self.len_before();
         for &value in values {
-            let len = if self.len_offset != 0 {
-                self.bcx.iadd_imm(len_start, self.len_offset as i64)
-            } else {
-                len_start
-            };
+            self.set_stack_value(self.len_offset as i64, value);
             self.len_offset += 1;
-            let sp = self.sp_at(len);
-            self.bcx.store(value, sp);
         }
     }
 
@@ -1235,15 +1427,13 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
     fn popn<const N: usize>(&mut self) -> [B::Value; N] {
         debug_assert_ne!(N, 0);
 
-        let len_start = self.len_before();
         std::array::from_fn(|i| {
             self.len_offset -= 1;
-            let len = if self.len_offset != 0 {
-                self.bcx.iadd_imm(len_start, self.len_offset as i64)
-            } else {
-                len_start
-            };
-            let sp = self.sp_at(len);
+            let offset = self.len_offset as i64;
+            if let Some(idx) = self.stack_values.iter().position(|&(o, _)| o == offset) {
+                return self.stack_values.swap_remove(idx).1;
+            }
+            let sp = self.sp_at_offset(offset);
             let name = b'a' + i as u8;
             self.load_word(sp, std::str::from_utf8(&[name]).unwrap())
         })
@@ -1253,9 +1443,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
     /// `n` cannot be `0`.
     fn dup(&mut self, n: usize) {
         debug_assert_ne!(n, 0);
-        let len = self.len_before();
-        let sp = self.sp_from_top(len, n);
-        let value = self.load_word(sp, &format!("dup{n}"));
+        let value = self.stack_value(-(n as i64), &format!("dup{n}"));
         self.push(value);
     }
 
@@ -1270,16 +1458,48 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
     /// `m` cannot be `0`.
     fn exchange(&mut self, n: usize, m: usize) {
         debug_assert_ne!(m, 0);
-        let len = self.len_before();
-        // Load a.
-        let a_sp = self.sp_from_top(len, n + 1);
-        let a = self.load_word(a_sp, "swap.a");
-        // Load b.
-        let b_sp = self.sp_from_top(len, n + m + 1);
-        let b = self.load_word(b_sp, "swap.b");
-        // Store.
-        self.bcx.store(a, b_sp);
-        self.bcx.store(b, a_sp);
+        let a_offset = -(n as i64 + 1);
+        let b_offset = -((n + m) as i64 + 1);
+        let a = self.stack_value(a_offset, "swap.a");
+        let b = self.stack_value(b_offset, "swap.b");
+        self.set_stack_value(b_offset, a);
+        self.set_stack_value(a_offset, b);
+    }
+
+    /// Returns the stack value at `offset` from `len_before`, loading it if it is not in a
+    /// register.
+    fn stack_value(&mut self, offset: i64, name: &str) -> B::Value {
+        if let Some(&(_, value)) = self.stack_values.iter().find(|&&(o, _)| o == offset) {
+            return value;
+        }
+        let sp = self.sp_at_offset(offset);
+        self.load_word(sp, name)
+    }
+
+    /// Sets the stack value at `offset` from `len_before`, keeping it in a register.
+    fn set_stack_value(&mut self, offset: i64, value: B::Value) {
+        match self.stack_values.iter_mut().find(|(o, _)| *o == offset) {
+            Some((_, v)) => *v = value,
+            None => self.stack_values.push((offset, value)),
+        }
+    }
+
+    /// Stores the stack values that are kept in registers to the stack.
+    fn spill_stack(&mut self) {
+        for (offset, value) in mem::take(&mut self.stack_values) {
+            let sp = self.sp_at_offset(offset);
+            self.bcx.store(value, sp);
+        }
+    }
+
+    /// Calls the instruction inspector with the stack before the current instruction.
+    fn inspect_inst(&mut self, data: &InstData) {
+        self.len_before = self.stack_len.load(&mut self.bcx, "stack_len");
+        self.spill_stack();
+        let stack = self.stack.addr(&mut self.bcx);
+        let pc = self.bcx.iconst(self.isize_type, data.pc as i64);
+        let opcode = self.bcx.iconst(self.i8_type, data.opcode as i64);
+        let _ = self.call_builtin(Builtin::Step, &[self.ecx, stack, self.len_before, pc, opcode]);
     }
 
     /// `RETURN` or `REVERT` instruction.
@@ -1323,6 +1543,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         self.bcx.switch_to_block(fail);
         let one = self.bcx.iconst_256(U256::from(1));
         self.push(one);
+        self.spill_stack();
         self.bcx.br(self.inst_entries[self.current_inst + 1]);
 
         self.bcx.switch_to_block(cont);
@@ -1351,22 +1572,83 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         let cond = self.bcx.icmp_imm(IntCC::UnsignedGreaterThan, max_len, STACK_CAP as i64);
         self.build_check(cond, InstructionResult::StackOverflow);
 
+        if let Some(eof_section) = &self.eof_section {
+            if is_jumpf && idx == eof_section.section {
+                // Loop back to the start of the current section instead of calling it again.
+                let inst = self.bytecode.eof_section_inst(idx);
+                self.spill_stack();
+                self.bcx.br(self.inst_entries[inst]);
+            } else {
+                self.call_eof_section(idx, is_jumpf);
+            }
+            return;
+        }
+
         // Push the return address to the function stack.
-        let next_block = self.inst_entries[self.current_inst + 1];
         if is_jumpf {
             self.func_stack_set(idx);
         } else {
+            let next_block = self.inst_entries[self.current_inst + 1];
+            // Backends without block addresses return to the `CALLF` instruction index instead.
+            // See `RETF`.
             let value = match self.bcx.block_addr(next_block) {
                 Some(addr) => addr,
-                None => todo!(),
+                None => self.bcx.iconst(self.isize_type, self.current_inst as i64),
             };
             self.call_func_stack_push(value, idx);
         }
 
         let inst = self.bytecode.eof_section_inst(idx);
+        self.spill_stack();
         self.bcx.br(self.inst_entries[inst]);
     }
 
+    /// Calls the function of the given EOF code section for a `CALLF` or `JUMPF` instruction.
+    fn call_eof_section(&mut self, section: usize, is_jumpf: bool) {
+        let depth = if is_jumpf {
+            self.eof_depth
+        } else {
+            let cond = self.bcx.icmp_imm(
+                IntCC::UnsignedGreaterThanOrEqual,
+                self.eof_depth,
+                STACK_CAP as i64,
+            );
+            self.build_check(cond, InstructionResult::EOFFunctionStackOverflow);
+            self.bcx.iadd_imm(self.eof_depth, 1)
+        };
+
+        let function = self.eof_section_function(section);
+        self.spill_stack();
+        let gas = self.bcx.fn_param(0);
+        let stack = self.stack.addr(&mut self.bcx);
+        let stack_len = self.stack_len.addr(&mut self.bcx);
+        let args = [gas, stack, stack_len, self.env, self.contract, self.ecx, depth];
+        let is_callee = self.eof_section.as_ref().is_some_and(|f| f.section != 0);
+        if !is_jumpf {
+            let ret = self.bcx.call(function, &args).unwrap();
+            self.build_check_instruction_result(ret);
+            self.bcx.br(self.inst_entries[self.current_inst + 1]);
+        } else if is_callee {
+            let ret = self.bcx.tail_call(function, &args, TailCallKind::Tail).unwrap();
+            self.bcx.ret(&[ret]);
+        } else {
+            // The entry function may have to store the stack length before returning.
+            let ret = self.bcx.call(function, &args).unwrap();
+            self.build_return(ret);
+        }
+    }
+
+    /// Returns the function of the given EOF code section, declaring it if it was not built yet.
+    fn eof_section_function(&mut self, section: usize) -> B::Function {
+        let name = self.eof_section.as_ref().unwrap().function_name(section);
+        if let Some(function) = self.bcx.get_function(&name) {
+            return function;
+        }
+        let ptr = self.ptr_type;
+        let params = [ptr, ptr, ptr, ptr, ptr, ptr, self.isize_type];
+        self.bcx.add_function(&name, &params, Some(self.i8_type), None, Linkage::Private)
+    }
+
     fn func_stack_set(&mut self, idx: usize) {
         let func_stack = self.func_stack(self.ecx);
         let idx_ptr = self.get_field(
@@ -1390,6 +1672,8 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
 
     /// Suspend execution, storing the resume point in the context.
     fn suspend(&mut self) {
+        self.spill_stack();
+
         // Register the next instruction as the resume block.
         let idx = self.resume_blocks.len();
         let value = self.add_resume_at(self.inst_entries[self.current_inst + 1]);
@@ -1464,15 +1748,17 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         self.bcx.fn_param(2)
     }
 
-    /// Returns the stack pointer at the top (`&stack[stack.len]`).
+    /// Returns the stack pointer at the top (`&stack[stack.len]`) to pass to a builtin.
     fn sp_at_top(&mut self) -> B::Value {
+        self.spill_stack_for_builtin();
         let len = self.len_before();
         self.sp_at(len)
     }
 
     /// Returns the stack pointer after the input has been popped
-    /// (`&stack[stack.len - op.input()]`).
+    /// (`&stack[stack.len - op.input()]`) to pass to a builtin.
     fn sp_after_inputs(&mut self) -> B::Value {
+        self.spill_stack_for_builtin();
         let mut len = self.len_before();
         let (inputs, _) = self.current_inst().stack_io();
         if inputs > 0 {
@@ -1481,17 +1767,51 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         self.sp_at(len)
     }
 
+    /// Spills the stack values kept in registers before the stack pointer is passed to a builtin.
+    fn spill_stack_for_builtin(&mut self) {
+        debug_assert!(
+            self.stack_values.is_empty()
+                || self.current_inst().flags.contains(InstFlags::SPILL_STACK),
+            "stack values in registers were not marked to be spilled: {:?}",
+            self.current_inst(),
+        );
+        self.spill_stack();
+    }
+
+    /// Returns the stack pointer at `offset` from `len_before` (`&stack[stack.len + offset]`).
+    fn sp_at_offset(&mut self, offset: i64) -> B::Value {
+        let mut len = self.len_before();
+        if offset != 0 {
+            len = self.bcx.iadd_imm(len, offset);
+        }
+        self.sp_at(len)
+    }
+
     /// Returns the stack pointer at `len` (`&stack[len]`).
     fn sp_at(&mut self, len: B::Value) -> B::Value {
         let ptr = self.stack.addr(&mut self.bcx);
         self.bcx.gep(self.word_type, ptr, &[len], "sp")
     }
 
-    /// Returns the stack pointer at `len` from the top (`&stack[CAPACITY - len]`).
-    fn sp_from_top(&mut self, len: B::Value, n: usize) -> B::Value {
-        debug_assert_ne!(n, 0);
-        let len = self.bcx.isub_imm(len, n as i64);
-        self.sp_at(len)
+    /// Returns `true` if the static gas of each section is paid in advance at its start.
+    fn pays_section_gas(&self) -> bool {
+        self.config.gas_metering && self.config.section_gas_metering
+    }
+
+    /// Builds a refund of static gas that was paid in advance but not spent.
+    fn gas_refund_imm(&mut self, refund: u64) {
+        if refund == 0 {
+            return;
+        }
+        let refund = self.bcx.iconst(self.isize_type, refund as i64);
+        self.gas_refund(refund);
+    }
+
+    /// Adds `refund` back to the remaining gas.
+    fn gas_refund(&mut self, refund: B::Value) {
+        let gas_remaining = self.load_gas_remaining();
+        let res = self.bcx.iadd(gas_remaining, refund);
+        self.store_gas_remaining(res);
     }
 
     /// Builds a gas cost deduction for an immediate value.
@@ -1537,7 +1857,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
     /// Builds a check, failing if `ret` is not `InstructionResult::Continue`.
     fn build_check_instruction_result(&mut self, ret: B::Value) {
         let failure = self.bcx.icmp_imm(IntCC::NotEqual, ret, InstructionResult::Continue as i64);
-        let target = self.build_check_inner(true, failure, ret);
+        let target = self.build_check_inner(true, failure, ret, self.unspent_gas);
         self.bcx.switch_to_block(target);
     }
 
@@ -1550,33 +1870,68 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
 
     fn build_check_imm_inner(&mut self, is_failure: bool, cond: B::Value, ret: InstructionResult) {
         let ret_value = self.bcx.iconst(self.i8_type, ret as i64);
-        let target = self.build_check_inner(is_failure, cond, ret_value);
+        let refund = self.failure_refund(is_failure, ret);
+        let target = self.build_check_inner(is_failure, cond, ret_value, refund);
         if self.config.comments {
             self.add_comment(&format!("check {ret:?}"));
         }
         self.bcx.switch_to_block(target);
     }
 
+    /// Returns the gas to refund when returning `ret` from the current instruction.
+    ///
+    /// Gas is never refunded on out of gas errors, as all of it is consumed anyway, like in revm.
+    /// Results that are only known at runtime are checked in the refund block instead.
+    fn failure_refund(&self, is_failure: bool, ret: InstructionResult) -> u64 {
+        if !is_failure || is_out_of_gas(ret) {
+            return 0;
+        }
+        self.unspent_gas
+    }
+
+    /// Builds a conditional branch to the return block, refunding `refund` gas before returning.
     #[must_use]
     fn build_check_inner(
         &mut self,
         is_failure: bool,
         cond: B::Value,
         ret: B::Value,
+        refund: u64,
     ) -> B::BasicBlock {
         let current_block = self.current_block();
         let target = self.create_block_after(current_block, "contd");
 
+        // Refund in a separate block so that the non-returning path is not affected.
+        let refund_block = (refund != 0).then(|| self.create_block_after(target, "refund"));
         let return_block = if let Some(return_block) = self.return_block {
-            self.incoming_returns.push((ret, current_block));
+            self.incoming_returns.push((ret, refund_block.unwrap_or(current_block)));
             return_block
         } else {
             self.create_block_after(target, "return")
         };
-        let then_block = if is_failure { return_block } else { target };
-        let else_block = if is_failure { target } else { return_block };
+        let exit_block = refund_block.unwrap_or(return_block);
+        let then_block = if is_failure { exit_block } else { target };
+        let else_block = if is_failure { target } else { exit_block };
         self.bcx.brif_cold(cond, then_block, else_block, is_failure);
 
+        if let Some(refund_block) = refund_block {
+            self.bcx.switch_to_block(refund_block);
+            self.bcx.set_current_block_cold();
+            // `ret` is not known at compile time when checking builtin results.
+            // The out of gas results are contiguous, see `is_out_of_gas`.
+            let oog_offset = self.bcx.isub_imm(ret, InstructionResult::OutOfGas as i64);
+            let is_oog = self.bcx.icmp_imm(
+                IntCC::UnsignedLessThanOrEqual,
+                oog_offset,
+                InstructionResult::InvalidOperandOOG as i64 - InstructionResult::OutOfGas as i64,
+            );
+            let zero = self.bcx.iconst(self.isize_type, 0);
+            let refund = self.bcx.iconst(self.isize_type, refund as i64);
+            let refund = self.bcx.select(is_oog, zero, refund);
+            self.gas_refund(refund);
+            self.bcx.br(return_block);
+        }
+
         if self.return_block.is_none() {
             self.bcx.switch_to_block(return_block);
             self.bcx.ret(&[ret]);
@@ -1587,6 +1942,8 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
 
     /// Builds a branch to the failure block.
     fn build_fail_imm(&mut self, ret: InstructionResult) {
+        let refund = self.failure_refund(true, ret);
+        self.gas_refund_imm(refund);
         let ret_value = self.bcx.iconst(self.i8_type, ret as i64);
         self.build_fail(ret_value);
         if self.config.comments {
@@ -1596,6 +1953,8 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
 
     /// Builds a branch to the failure block.
     fn build_fail(&mut self, ret: B::Value) {
+        // The stack is not observable after a failure, so there is no need to spill.
+        self.stack_values.clear();
         if let Some(block) = self.failure_block {
             self.incoming_failures.push((ret, self.bcx.current_block().unwrap()));
             self.bcx.br(block);
@@ -1901,83 +2260,501 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         self.bcx.ret(&[r]);
     }
 
-    fn call_mload(&mut self, offset: B::Value) -> B::Value {
-        let out_slot = self.bcx.new_stack_slot(self.word_type, "mload.out.slot");
-        let out_addr = out_slot.addr(&mut self.bcx);
-        self.call_mem_op(offset, out_addr, MemOpKind::Load);
-        out_slot.load(&mut self.bcx, "mload.out")
+    /// Returns `true` if `SLOAD` and `SSTORE` look up the storage cache before calling the host.
+    fn uses_storage_cache(&self) -> bool {
+        self.config.storage_cache
+            && self.config.gas_metering
+            && self.bytecode.spec_id.is_enabled_in(SpecId::BERLIN)
     }
 
-    fn call_mstore(&mut self, offset: B::Value, value: B::Value) {
-        self.call_mem_op(offset, value, MemOpKind::Store);
-    }
+    /// Builds a lookup of `key` in `ecx.storage_cache`, branching to `hit` or `miss`.
+    ///
+    /// Returns the pointer to the entry, which can only be used in `hit`.
+    fn storage_cache_lookup(
+        &mut self,
+        key: B::Value,
+        hit: B::BasicBlock,
+        miss: B::BasicBlock,
+    ) -> B::Value {
+        let cache_ptr = self.get_field(
+            self.ecx,
+            mem::offset_of!(EvmContext<'_>, storage_cache),
+            "ecx.storage_cache.addr",
+        );
+        let cache = self.bcx.load(self.ptr_type, cache_ptr, "ecx.storage_cache");
+        let probe = self.create_block_after_current("storage_cache.probe");
+        let is_null = self.bcx.is_null(cache);
+        self.bcx.brif(is_null, miss, probe);
+
+        // See `StorageCache::index`.
+        self.bcx.switch_to_block(probe);
+        let i64_type = self.bcx.type_int(64);
+        let mut folded = self.bcx.ireduce(i64_type, key);
+        for i in 1..4 {
+            let shift = self.bcx.iconst_256(U256::from(i * 64));
+            let limb = self.bcx.ushr(key, shift);
+            let limb = self.bcx.ireduce(i64_type, limb);
+            folded = self.bcx.bitxor(folded, limb);
+        }
+        let hash = self.bcx.imul_imm(folded, crate::StorageCache::HASH_MULTIPLIER as i64);
+        let shift = self.bcx.iconst(i64_type, 32);
+        let mut hash = self.bcx.ushr(hash, shift);
+        if self.bcx.type_bit_width(self.isize_type) < 64 {
+            hash = self.bcx.ireduce(self.isize_type, hash);
+        }
+        let mask = {
+            let ptr = self.get_field(
+                cache,
+                mem::offset_of!(pf::StorageCache, mask),
+                "ecx.storage_cache.mask.addr",
+            );
+            self.bcx.load(self.isize_type, ptr, "ecx.storage_cache.mask")
+        };
+        let index = self.bcx.bitand(hash, mask);
+        let entries = {
+            let ptr = self.get_field(
+                cache,
+                mem::offset_of!(pf::StorageCache, entries),
+                "ecx.storage_cache.entries.addr",
+            );
+            self.bcx.load(self.ptr_type, ptr, "ecx.storage_cache.entries")
+        };
+        let offset = self.bcx.imul_imm(index, mem::size_of::<StorageCacheEntry>() as i64);
+        let entry = self.bcx.gep(self.i8_type, entries, &[offset], "storage_cache.entry");
+
+        let flags = {
+            let ptr = self.get_field(
+                entry,
+                mem::offset_of!(StorageCacheEntry, flags),
+                "entry.flags.addr",
+            );
+            self.bcx.load(self.isize_type, ptr, "entry.flags")
+        };
+        let occupied = self.bcx.bitand_imm(flags, StorageCacheEntry::OCCUPIED as i64);
+        let occupied = self.bcx.icmp_imm(IntCC::NotEqual, occupied, 0);
+        let entry_key = {
+            let ptr =
+                self.get_field(entry, mem::offset_of!(StorageCacheEntry, key), "entry.key.addr");
+            self.bcx.load(self.word_type, ptr, "entry.key")
+        };
+        let same_key = self.bcx.icmp(IntCC::Equal, entry_key, key);
+        let is_hit = self.bcx.bitand(occupied, same_key);
+        self.bcx.brif(is_hit, hit, miss);
+
+        entry
+    }
+
+    /// Builds an `SLOAD` that reads the value from the storage cache on a hit, and calls the
+    /// builtin otherwise.
+    fn sload_cached(&mut self, sp: B::Value) {
+        let key = self.bcx.load(self.word_type, sp, "sload.key");
+        let hit = self.create_block_after_current("sload.hit");
+        let miss = self.create_block_after(hit, "sload.miss");
+        let contd = self.create_block_after(miss, "sload.contd");
+        let entry = self.storage_cache_lookup(key, hit, miss);
+
+        // Cached slots are always warm.
+        self.bcx.switch_to_block(hit);
+        self.gas_cost_imm(gas::WARM_STORAGE_READ_COST);
+        let present = {
+            let ptr = self.get_field(
+                entry,
+                mem::offset_of!(StorageCacheEntry, present),
+                "entry.present.addr",
+            );
+            self.bcx.load(self.word_type, ptr, "entry.present")
+        };
+        self.bcx.store(present, sp);
+        self.bcx.br(contd);
+
+        self.bcx.switch_to_block(miss);
+        let spec_id = self.const_spec_id();
+        self.call_fallible_builtin(Builtin::Sload, &[self.ecx, sp, spec_id]);
+        self.bcx.br(contd);
 
-    fn call_mstore8(&mut self, offset: B::Value, value: B::Value) {
-        self.call_mem_op(offset, value, MemOpKind::Store8);
+        self.bcx.switch_to_block(contd);
     }
 
-    fn call_mem_op(&mut self, offset: B::Value, value: B::Value, kind: MemOpKind) {
-        let name = match kind {
-            MemOpKind::Load => "mload",
-            MemOpKind::Store => "mstore",
-            MemOpKind::Store8 => "mstore8",
+    /// Builds an `SSTORE` that writes the value to the storage cache on a hit, and calls the
+    /// builtin otherwise.
+    ///
+    /// Gas and refunds are computed as in `gas::sstore_cost` and `gas::sstore_refund` for a warm
+    /// slot.
+    fn sstore_cached(&mut self, sp: B::Value) {
+        // `sp` points to `[value, key]`.
+        let value = self.bcx.load(self.word_type, sp, "sstore.value");
+        let key = {
+            let one = self.bcx.iconst(self.isize_type, 1);
+            let ptr = self.bcx.gep(self.word_type, sp, &[one], "sstore.key.addr");
+            self.bcx.load(self.word_type, ptr, "sstore.key")
         };
-        let value_ty = match kind {
-            MemOpKind::Load => self.ptr_type,
-            MemOpKind::Store => self.word_type,
-            MemOpKind::Store8 => self.i8_type,
+        let hit = self.create_block_after_current("sstore.hit");
+        let update = self.create_block_after(hit, "sstore.update");
+        let miss = self.create_block_after(update, "sstore.miss");
+        let contd = self.create_block_after(miss, "sstore.contd");
+        let entry = self.storage_cache_lookup(key, hit, miss);
+
+        // Let the builtin fail in static calls and below the EIP-2200 call stipend.
+        self.bcx.switch_to_block(hit);
+        let is_static = {
+            let ptr = self.get_field(
+                self.ecx,
+                mem::offset_of!(EvmContext<'_>, is_static),
+                "ecx.is_static.addr",
+            );
+            let is_static = self.bcx.load(self.i8_type, ptr, "ecx.is_static");
+            self.bcx.icmp_imm(IntCC::NotEqual, is_static, 0)
         };
-        let ret = self
-            .call_ir_builtin(
-                name,
-                &[offset, value, self.ecx],
-                &[self.word_type, value_ty, self.ptr_type],
-                Some(self.i8_type),
-                |this| this.build_mem_op(kind),
-            )
-            .expect("memory builtin returns a value");
-        self.build_check_instruction_result(ret);
+        let gas_remaining = self.load_gas_remaining();
+        let below_stipend = self.bcx.icmp_imm(
+            IntCC::UnsignedLessThanOrEqual,
+            gas_remaining,
+            gas::CALL_STIPEND as i64,
+        );
+        let fallback = self.bcx.bitor(is_static, below_stipend);
+        self.bcx.brif(fallback, miss, update);
+
+        self.bcx.switch_to_block(update);
+        let original_ptr = self.get_field(
+            entry,
+            mem::offset_of!(StorageCacheEntry, original),
+            "entry.original.addr",
+        );
+        let original = self.bcx.load(self.word_type, original_ptr, "entry.original");
+        let present_ptr = self.get_field(
+            entry,
+            mem::offset_of!(StorageCacheEntry, present),
+            "entry.present.addr",
+        );
+        let present = self.bcx.load(self.word_type, present_ptr, "entry.present");
+
+        let is_noop = self.bcx.icmp(IntCC::Equal, value, present);
+        let is_clean = self.bcx.icmp(IntCC::Equal, original, present);
+        let is_restored = self.bcx.icmp(IntCC::Equal, original, value);
+        let original_is_zero = self.bcx.icmp_imm(IntCC::Equal, original, 0);
+        let present_is_zero = self.bcx.icmp_imm(IntCC::Equal, present, 0);
+        let new_is_zero = self.bcx.icmp_imm(IntCC::Equal, value, 0);
+
+        let iconst = |this: &mut Self, value: i64| this.bcx.iconst(this.isize_type, value);
+
+        // `istanbul_sstore_cost::<WARM_STORAGE_READ_COST, WARM_SSTORE_RESET>`
+        let cost = {
+            let set = iconst(self, gas::SSTORE_SET as i64);
+            let reset = iconst(self, gas::WARM_SSTORE_RESET as i64);
+            let sload = iconst(self, gas::WARM_STORAGE_READ_COST as i64);
+            let clean_cost = self.bcx.select(original_is_zero, set, reset);
+            let cost = self.bcx.select(is_clean, clean_cost, sload);
+            self.bcx.select(is_noop, sload, cost)
+        };
+        self.gas_cost(cost);
+
+        // `sstore_refund`
+        let refund = {
+            let clears = if self.bytecode.spec_id.is_enabled_in(SpecId::LONDON) {
+                (gas::SSTORE_RESET - gas::COLD_SLOAD_COST + gas::ACCESS_LIST_STORAGE_KEY) as i64
+            } else {
+                gas::REFUND_SSTORE_CLEARS
+            };
+            let zero = iconst(self, 0);
+            let clears_pos = iconst(self, clears);
+            let clears_neg = iconst(self, -clears);
+
+            // `original == present && new == 0`
+            let clean_refund = self.bcx.select(new_is_zero, clears_pos, zero);
+
+            // `original != 0`
+            let cleared_refund = self.bcx.select(new_is_zero, clears_pos, zero);
+            let cleared_refund = self.bcx.select(present_is_zero, clears_neg, cleared_refund);
+            let cleared_refund = self.bcx.select(original_is_zero, zero, cleared_refund);
+            // `original == new`
+            let restored_refund = {
+                let set = iconst(self, (gas::SSTORE_SET - gas::WARM_STORAGE_READ_COST) as i64);
+                let reset =
+                    iconst(self, (gas::WARM_SSTORE_RESET - gas::WARM_STORAGE_READ_COST) as i64);
+                let restored_refund = self.bcx.select(original_is_zero, set, reset);
+                self.bcx.select(is_restored, restored_refund, zero)
+            };
+            let dirty_refund = self.bcx.iadd(cleared_refund, restored_refund);
+
+            let refund = self.bcx.select(is_clean, clean_refund, dirty_refund);
+            self.bcx.select(is_noop, zero, refund)
+        };
+        let refunded = self.gas_refunded.load(&mut self.bcx, "gas.refunded");
+        let refunded = self.bcx.iadd(refunded, refund);
+        self.gas_refunded.store(&mut self.bcx, refunded);
+
+        self.bcx.store(value, present_ptr);
+        let flags_ptr =
+            self.get_field(entry, mem::offset_of!(StorageCacheEntry, flags), "entry.flags.addr");
+        let flags = self.bcx.load(self.isize_type, flags_ptr, "entry.flags");
+        let flags = self.bcx.bitor_imm(flags, StorageCacheEntry::DIRTY as i64);
+        self.bcx.store(flags, flags_ptr);
+        self.bcx.br(contd);
+
+        self.bcx.switch_to_block(miss);
+        let spec_id = self.const_spec_id();
+        self.call_fallible_builtin(Builtin::Sstore, &[self.ecx, sp, spec_id]);
+        self.bcx.br(contd);
+
+        self.bcx.switch_to_block(contd);
     }
 
-    /// Builds:
-    /// - `Load` => `fn mload(offset: u256, out: ptr, ecx: ptr) -> InstructionResult`
-    /// - `Store` => `fn mstore(offset: u256, value: u256, ecx: ptr) -> InstructionResult`
-    /// - `Store8` => `fn mstore(offset: u256, value: u8, ecx: ptr) -> InstructionResult`
-    fn build_mem_op(&mut self, kind: MemOpKind) {
-        let is_load = matches!(kind, MemOpKind::Load);
-        // TODO: If `store` is inlined it can cause segfaults. https://github.com/paradigmxyz/revmc/issues/61
-        if !is_load {
-            self.bcx.add_function_attribute(
-                None,
-                Attribute::NoInline,
-                FunctionAttributeLocation::Function,
-            );
-        }
-        let ptr_args = if is_load { &[1, 2][..] } else { &[2][..] };
-        for &ptr_arg in ptr_args {
-            for attr in default_attrs::for_ref() {
-                self.bcx.add_function_attribute(
-                    None,
-                    attr,
-                    FunctionAttributeLocation::Param(ptr_arg),
-                )
+    /// Builds an arithmetic or comparison instruction whose inputs are known to be small enough
+    /// to be computed in a narrower integer type than a word. See `RangeAnalysis`.
+    ///
+    /// `bits` are the upper bounds on the bit widths of the inputs, in pop order.
+    fn build_narrow_op(&mut self, opcode: u8, bits: [u16; 3]) {
+        let [a_bits, b_bits, c_bits] = bits;
+        match opcode {
+            op::ADD | op::MUL | op::LT | op::GT | op::EQ => {
+                let bits = match opcode {
+                    op::ADD => a_bits.max(b_bits) + 1,
+                    op::MUL => a_bits + b_bits,
+                    _ => a_bits.max(b_bits),
+                };
+                let ty = self.narrow_type(bits, true);
+                let [a, b] = self.popn();
+                let a = self.narrow(ty, a);
+                let b = self.narrow(ty, b);
+                let r = match opcode {
+                    op::ADD => self.bcx.iadd(a, b),
+                    op::MUL => self.bcx.imul(a, b),
+                    op::LT => self.bcx.icmp(IntCC::UnsignedLessThan, a, b),
+                    op::GT => self.bcx.icmp(IntCC::UnsignedGreaterThan, a, b),
+                    op::EQ => self.bcx.icmp(IntCC::Equal, a, b),
+                    _ => unreachable!(),
+                };
+                let r = self.bcx.zext(self.word_type, r);
+                self.push(r);
+            }
+            op::DIV | op::MOD => {
+                let ty = self.narrow_type(a_bits.max(b_bits), false);
+                let [a, b] = self.popn();
+                let a = self.narrow(ty, a);
+                let b = self.narrow(ty, b);
+                let r = self.build_urem_or_zero(ty, a, b, opcode == op::DIV);
+                let r = self.bcx.zext(self.word_type, r);
+                self.push(r);
+            }
+            op::ADDMOD | op::MULMOD => {
+                // The sum or product of the first two inputs fits in a word.
+                let bits =
+                    if opcode == op::ADDMOD { a_bits.max(b_bits) + 1 } else { a_bits + b_bits };
+                let ty = self.narrow_type(bits.max(c_bits), false);
+                let [a, b, n] = self.popn();
+                let a = self.narrow(ty, a);
+                let b = self.narrow(ty, b);
+                let n = self.narrow(ty, n);
+                let x =
+                    if opcode == op::ADDMOD { self.bcx.iadd(a, b) } else { self.bcx.imul(a, b) };
+                let r = self.build_urem_or_zero(ty, x, n, false);
+                let r = self.bcx.zext(self.word_type, r);
+                self.push(r);
             }
+            op::EXP => {
+                let [base, exponent] = self.popn();
+                let exponent = self.bcx.ireduce(self.i8_type, exponent);
+
+                // Same as `gas::dyn_exp_cost`, the exponent is at most one byte long.
+                let gas_byte = if self.bytecode.spec_id.is_enabled_in(SpecId::SPURIOUS_DRAGON) {
+                    50
+                } else {
+                    10
+                };
+                let is_zero = self.bcx.icmp_imm(IntCC::Equal, exponent, 0);
+                let zero = self.bcx.iconst(self.isize_type, 0);
+                let gas_byte = self.bcx.iconst(self.isize_type, gas_byte);
+                let cost = self.bcx.select(is_zero, zero, gas_byte);
+                self.gas_cost(cost);
+
+                // Unrolled square-and-multiply over the bits of the exponent.
+                let mut r = self.bcx.iconst_256(U256::from(1));
+                let mut pow = base;
+                for i in 0..b_bits {
+                    if i > 0 {
+                        pow = self.bcx.imul(pow, pow);
+                    }
+                    let shift = self.bcx.iconst(self.i8_type, i as i64);
+                    let bit = self.bcx.ushr(exponent, shift);
+                    let bit = self.bcx.bitand_imm(bit, 1);
+                    let is_set = self.bcx.icmp_imm(IntCC::NotEqual, bit, 0);
+                    let mul = self.bcx.imul(r, pow);
+                    r = self.bcx.select(is_set, mul, r);
+                }
+                self.push(r);
+            }
+            _ => unreachable!("not a narrow instruction: {opcode:#04x}"),
         }
-        if is_load {
-            self.bcx.add_function_attribute(
-                None,
-                Attribute::WriteOnly,
-                FunctionAttributeLocation::Param(1),
-            );
+    }
+
+    /// Returns the narrowest integer type, of at least 64 bits, that fits values of `bits` bits.
+    ///
+    /// If `i128` is `false`, only 64-bit integers are considered, as not all backends support
+    /// 128-bit division.
+    fn narrow_type(&mut self, bits: u16, i128: bool) -> B::Type {
+        if bits <= 64 {
+            self.bcx.type_int(64)
+        } else if i128 && bits <= 128 {
+            self.bcx.type_int(128)
+        } else {
+            self.word_type
         }
+    }
 
-        let offset = self.bcx.fn_param(0);
-        let value = self.bcx.fn_param(1);
-        let ecx = self.bcx.fn_param(2);
+    /// Truncates a word to `ty`.
+    fn narrow(&mut self, ty: B::Type, value: B::Value) -> B::Value {
+        if ty == self.word_type {
+            value
+        } else {
+            self.bcx.ireduce(ty, value)
+        }
+    }
+
+    /// Builds `a / b` if `div` is `true`, or `a % b` otherwise, or `0` if `b` is `0`.
+    fn build_urem_or_zero(&mut self, ty: B::Type, a: B::Value, b: B::Value, div: bool) -> B::Value {
+        let b_is_zero = self.bcx.icmp_imm(IntCC::Equal, b, 0);
+        // Divide by `1` instead of `0` so that the discarded result is well-defined.
+        let one = self.bcx.iconst(ty, 1);
+        let divisor = self.bcx.select(b_is_zero, one, b);
+        let r = if div { self.bcx.udiv(a, divisor) } else { self.bcx.urem(a, divisor) };
+        let zero = self.bcx.iconst(ty, 0);
+        self.bcx.select(b_is_zero, zero, r)
+    }
+
+    /// Builds the `JUMPI` of the first arm of a function dispatcher as a `switch` over the
+    /// selector, skipping the other arms. See `Dispatcher`.
+    ///
+    /// The static gas of the skipped arms is charged on each branch, as it would have been paid by
+    /// walking the chain.
+    fn build_dispatcher(&mut self) {
+        let dispatcher = self.bytecode.dispatcher(self.current_inst).expect("no dispatcher");
+        let _cond = self.pop();
+        let selector = self.stack_value(self.len_offset as i64 - 1, "selector");
+        self.spill_stack();
+
+        let mut targets = SwitchTargets::<B>::with_capacity(dispatcher.arms.len());
+        let mut charges = Vec::new();
+        let mut gas = 0;
+        for (i, arm) in dispatcher.arms.iter().enumerate() {
+            if i > 0 {
+                gas += arm.gas;
+            }
+            // Only the first arm with a given selector can be taken.
+            let selector = arm.selector as u64;
+            if targets.iter().any(|&(s, _)| s == selector) {
+                continue;
+            }
+            let target = self.inst_entries[arm.target];
+            let block = self.build_dispatcher_charge(gas, target, &mut charges);
+            targets.push((selector, block));
+        }
+        let fallthrough = self.inst_entries[dispatcher.fallthrough];
+        let default = self.build_dispatcher_charge(gas, fallthrough, &mut charges);
+
+        self.bcx.switch(selector, default, &targets, false);
+        for (block, gas, target) in charges {
+            self.bcx.switch_to_block(block);
+            self.gas_cost_imm(gas);
+            self.bcx.br(target);
+        }
+    }
+
+    /// Returns the block that charges `gas` before branching to `target` in a dispatcher, which is
+    /// `target` itself if there is nothing to charge.
+    fn build_dispatcher_charge(
+        &mut self,
+        gas: u64,
+        target: B::BasicBlock,
+        charges: &mut Vec<(B::BasicBlock, u64, B::BasicBlock)>,
+    ) -> B::BasicBlock {
+        if gas == 0 || !self.config.gas_metering {
+            return target;
+        }
+        let block = self.create_block_after_current("dispatch");
+        charges.push((block, gas, target));
+        block
+    }
+
+    /// Builds `KECCAK256` with a constant length of 32 or 64 bytes, whose gas cost is included in
+    /// the base gas. See `RangeAnalysis`.
+    fn build_keccak256_const(&mut self, len: u64) {
+        let [offset, _len] = self.popn();
+        // Same as `as_usize_or_fail!`.
+        let max_usize = ((1u128 << self.bcx.type_bit_width(self.isize_type)) - 1u128) as u64;
+        let max_usize = self.bcx.iconst_256(U256::from(max_usize));
+        let offset_too_big = self.bcx.icmp(IntCC::UnsignedGreaterThan, offset, max_usize);
+        self.build_check(offset_too_big, InstructionResult::InvalidOperandOOG);
+
+        let data = self.build_memory_slice(offset, len);
+        let out = self.bcx.new_stack_slot(self.word_type, "keccak256.out.slot");
+        let out_addr = out.addr(&mut self.bcx);
+        let builtin = match len {
+            32 => Builtin::Keccak256For32,
+            64 => Builtin::Keccak256For64,
+            _ => unreachable!("unsupported KECCAK256 length: {len}"),
+        };
+        let _ = self.call_builtin(builtin, &[out_addr, data]);
+        let hash = out.load(&mut self.bcx, "keccak256.out");
+        self.push(hash);
+    }
+
+    fn build_mload(&mut self, offset: B::Value) -> B::Value {
+        self.build_mem_op(offset, None, MemOpKind::Load).expect("mload returns a value")
+    }
+
+    fn build_mstore(&mut self, offset: B::Value, value: B::Value) {
+        self.build_mem_op(offset, Some(value), MemOpKind::Store);
+    }
+
+    fn build_mstore8(&mut self, offset: B::Value, value: B::Value) {
+        self.build_mem_op(offset, Some(value), MemOpKind::Store8);
+    }
+
+    /// Builds an inline memory access, expanding the memory out of line only if it has to grow.
+    ///
+    /// Returns the loaded value for `Load`.
+    fn build_mem_op(
+        &mut self,
+        offset: B::Value,
+        value: Option<B::Value>,
+        kind: MemOpKind,
+    ) -> Option<B::Value> {
+        let len = match kind {
+            MemOpKind::Load | MemOpKind::Store => 32,
+            MemOpKind::Store8 => 1,
+        };
+        let slot = self.build_memory_slice(offset, len);
+        match kind {
+            MemOpKind::Load => {
+                let loaded = self.bcx.load_unaligned(self.word_type, slot, "slot.value");
+                let loaded =
+                    if cfg!(target_endian = "little") { self.bcx.bswap(loaded) } else { loaded };
+                Some(loaded)
+            }
+            MemOpKind::Store | MemOpKind::Store8 => {
+                let value = value.expect("store without a value");
+                let value = if matches!(kind, MemOpKind::Store) && cfg!(target_endian = "little") {
+                    self.bcx.bswap(value)
+                } else {
+                    value
+                };
+                self.bcx.store_unaligned(value, slot);
+                None
+            }
+        }
+    }
 
+    /// Builds an inline bounds check of `len` bytes of memory at the word `offset`, expanding the
+    /// memory out of line only if it has to grow.
+    ///
+    /// Returns a pointer to the memory at `offset`.
+    fn build_memory_slice(&mut self, offset: B::Value, len: u64) -> B::Value {
         let memory_ptr = {
-            let memory_ptr_ptr =
-                self.get_field(ecx, mem::offset_of!(EvmContext<'_>, memory), "ecx.memory.addr");
+            let memory_ptr_ptr = self.get_field(
+                self.ecx,
+                mem::offset_of!(EvmContext<'_>, memory),
+                "ecx.memory.addr",
+            );
             self.bcx.load(self.ptr_type, memory_ptr_ptr, "ecx.memory")
         };
 
@@ -2007,28 +2784,25 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         let offset_too_big = self.bcx.icmp(IntCC::UnsignedGreaterThan, offset, max_isize_u256);
         let offset = self.bcx.ireduce(self.isize_type, offset);
         let (new_size, new_size_overflow) = {
-            let slot_size = match kind {
-                MemOpKind::Load | MemOpKind::Store => 32,
-                MemOpKind::Store8 => 1,
-            };
-            let slot_size = self.bcx.iconst(self.isize_type, slot_size as i64);
-            self.bcx.uadd_overflow(offset, slot_size)
+            let len = self.bcx.iconst(self.isize_type, len as i64);
+            self.bcx.uadd_overflow(offset, len)
         };
         let new_size_overflow = self.bcx.bitor(offset_too_big, new_size_overflow);
         let new_size = self.bcx.select(new_size_overflow, max_isize, new_size);
         let cond = self.bcx.icmp(IntCC::UnsignedGreaterThan, new_size, buffer_len);
 
-        let resize = self.bcx.create_block("resize");
-        let cont = self.bcx.create_block("contd");
-        self.bcx.brif_cold(cond, resize, cont, true);
+        let resize = self.create_block_after_current("mem.resize");
+        let contd = self.create_block_after(resize, "mem.contd");
+        self.bcx.brif_cold(cond, resize, contd, true);
 
         self.bcx.switch_to_block(resize);
-        self.call_fallible_builtin(Builtin::ResizeMemory, &[ecx, new_size]);
-        self.bcx.br(cont);
+        self.build_memory_expansion(buffer_len, new_size);
+        self.bcx.br(contd);
 
         // `ecx.memory.buffer[last_checkpoint + offset..]`
         // Implemented as `ecx.memory.buffer[last_checkpoint..][offset..]`
-        self.bcx.switch_to_block(cont);
+        // The buffer pointer must be loaded after the expansion, which may reallocate it.
+        self.bcx.switch_to_block(contd);
         let shared_buffer_ptr = {
             let ptr = self.get_field(
                 memory_ptr,
@@ -2043,26 +2817,64 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
             &[last_checkpoint],
             "ecx.memory.buffer.ptr",
         );
-        let slot = self.bcx.gep(self.i8_type, buffer_ptr, &[offset], "slot");
-        match kind {
-            MemOpKind::Load => {
-                let loaded = self.bcx.load(self.word_type, slot, "slot.value");
-                let loaded =
-                    if cfg!(target_endian = "little") { self.bcx.bswap(loaded) } else { loaded };
-                self.bcx.store(loaded, value);
-            }
-            MemOpKind::Store | MemOpKind::Store8 => {
-                let value = if matches!(kind, MemOpKind::Store) && cfg!(target_endian = "little") {
-                    self.bcx.bswap(value)
-                } else {
-                    value
-                };
-                self.bcx.store(value, slot);
-            }
+        self.bcx.gep(self.i8_type, buffer_ptr, &[offset], "slot")
+    }
+
+    /// Builds the expansion of the memory from `len` to `new_size` bytes.
+    ///
+    /// The expansion cost is computed and charged inline as in `gas::memory_gas`, and only the
+    /// growth of the buffer is left to a builtin.
+    fn build_memory_expansion(&mut self, len: B::Value, new_size: B::Value) {
+        // Sizes whose quadratic cost could overflow are left to `ResizeMemory`; they are out of
+        // gas for any realistic gas limit.
+        let max_inline_size =
+            if self.bcx.type_bit_width(self.isize_type) >= 64 { u32::MAX as u64 } else { 0xffff };
+        let is_large =
+            self.bcx.icmp_imm(IntCC::UnsignedGreaterThan, new_size, max_inline_size as i64);
+        let large = self.create_block_after_current("mem.resize.large");
+        let small = self.create_block_after(large, "mem.resize.small");
+        let done = self.create_block_after(small, "mem.resize.done");
+        self.bcx.brif_cold(is_large, large, small, true);
+
+        self.bcx.switch_to_block(large);
+        self.call_fallible_builtin(Builtin::ResizeMemory, &[self.ecx, new_size]);
+        self.bcx.br(done);
+
+        self.bcx.switch_to_block(small);
+        let new_words = self.num_words(new_size);
+        if self.config.gas_metering {
+            let new_cost = self.memory_gas(new_words);
+            let words = self.num_words(len);
+            let cost = self.memory_gas(words);
+            let cost = self.bcx.isub(new_cost, cost);
+
+            // Same as `gas_cost`, but the interpreter fails with `MemoryOOG` instead.
+            let gas_remaining = self.load_gas_remaining();
+            let (res, overflow) = self.bcx.usub_overflow(gas_remaining, cost);
+            self.build_check(overflow, InstructionResult::MemoryOOG);
+            self.store_gas_remaining(res);
         }
+        let new_len = self.bcx.imul_imm(new_words, 32);
+        let _ = self.call_builtin(Builtin::GrowMemory, &[self.ecx, new_len]);
+        self.bcx.br(done);
 
-        let cont = self.const_continue();
-        self.bcx.ret(&[cont]);
+        self.bcx.switch_to_block(done);
+    }
+
+    /// Builds `gas::num_words(len)`.
+    fn num_words(&mut self, len: B::Value) -> B::Value {
+        let len = self.bcx.iadd_imm(len, 31);
+        let shift = self.bcx.iconst(self.isize_type, 5);
+        self.bcx.ushr(len, shift)
+    }
+
+    /// Builds `gas::memory_gas(num_words)`: `MEMORY * num_words + num_words^2 / 512`.
+    fn memory_gas(&mut self, num_words: B::Value) -> B::Value {
+        let linear = self.bcx.imul_imm(num_words, gas::MEMORY as i64);
+        let quadratic = self.bcx.imul(num_words, num_words);
+        let shift = self.bcx.iconst(self.isize_type, 9);
+        let quadratic = self.bcx.ushr(quadratic, shift);
+        self.bcx.iadd(linear, quadratic)
     }
 
     fn call_func_stack_push(&mut self, pc: B::Value, new_idx: usize) {
@@ -2249,6 +3061,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         let f = self.bcx.get_or_build_function(name, arg_types, ret, linkage, |bcx| {
             let prev_return_block = this.return_block.take();
             let prev_failure_block = this.failure_block.take();
+            let prev_unspent_gas = mem::take(&mut this.unspent_gas);
             mem::swap(&mut this.bcx, bcx);
 
             for attr in default_attrs::for_fn().chain(std::iter::once(Attribute::NoUnwind)) {
@@ -2262,6 +3075,7 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
             build(this);
 
             mem::swap(&mut this.bcx, bcx);
+            this.unspent_gas = prev_unspent_gas;
             this.failure_block = prev_failure_block;
             this.return_block = prev_return_block;
         });
@@ -2312,10 +3126,18 @@ mod pf {
         /// The remaining gas.
         pub(super) remaining: u64,
         /// Refunded gas. This is used only at the end of execution.
-        refunded: i64,
+        pub(super) refunded: i64,
     }
     const _: [(); mem::size_of::<revm_interpreter::Gas>()] = [(); mem::size_of::<Gas>()];
 
+    #[repr(C)]
+    pub(super) struct StorageCache {
+        pub(super) entries: *mut StorageCacheEntry,
+        pub(super) mask: usize,
+        buf: Box<[StorageCacheEntry]>,
+    }
+    const _: [(); mem::size_of::<crate::StorageCache>()] = [(); mem::size_of::<StorageCache>()];
+
     #[allow(unexpected_cfgs)]
     pub(super) struct SharedMemory {
         pub(super) buffer: Vec<u8>,
@@ -2365,6 +3187,18 @@ mod pf {
     }
 }
 
+/// Returns `true` if `ret` is an out of gas error, on which all of the gas is consumed.
+const fn is_out_of_gas(ret: InstructionResult) -> bool {
+    matches!(
+        ret,
+        InstructionResult::OutOfGas
+            | InstructionResult::MemoryOOG
+            | InstructionResult::MemoryLimitOOG
+            | InstructionResult::PrecompileOOG
+            | InstructionResult::InvalidOperandOOG
+    )
+}
+
 fn get_field<B: Builder>(bcx: &mut B, ptr: B::Value, offset: usize, name: &str) -> B::Value {
     let offset = bcx.iconst(bcx.type_ptr_sized_int(), offset as i64);
     bcx.gep(bcx.type_int(8), ptr, &[offset], name)
@@ -12,11 +12,14 @@ use alloy_primitives as _;
 mod bytecode;
 pub use bytecode::*;
 
+mod cache;
+pub use cache::{CacheKey, ObjectCache};
+
 mod compiler;
-pub use compiler::{EvmCompiler, EvmCompilerInput};
+pub use compiler::{AotContract, BudgetExceeded, CompileError, EvmCompiler, EvmCompilerInput};
 
 mod linker;
-pub use linker::Linker;
+pub use linker::{Linker, LinkerError, LinkerOutput};
 
 /// Internal tests and testing utilities. Not public API.
 #[cfg(any(test, feature = "__fuzzing"))]
@@ -1,11 +1,91 @@
-use std::path::{Path, PathBuf};
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+/// The kind of file produced by a [`Linker`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LinkerOutput {
+    /// A shared library (`.so`, `.dylib`), linked with `cc -shared`. This is the default.
+    #[default]
+    Shared,
+    /// A static archive (`.a`), created with `ar`.
+    ///
+    /// Archives are not linked, so [extra libraries](Linker::libs) are ignored.
+    Static,
+    /// A single relocatable object file (`.o`) merging all the inputs, linked with `cc -r`.
+    Relocatable,
+}
+
+/// An error returned by [`Linker::link`].
+#[derive(Debug)]
+pub enum LinkerError {
+    /// The tool could not be spawned.
+    Spawn {
+        /// The program that was run.
+        program: PathBuf,
+        /// The underlying I/O error.
+        source: io::Error,
+    },
+    /// A file could not be accessed.
+    Io {
+        /// The path of the file.
+        path: PathBuf,
+        /// The underlying I/O error.
+        source: io::Error,
+    },
+    /// The tool exited unsuccessfully.
+    Failed {
+        /// The program that was run.
+        program: PathBuf,
+        /// The exit status of the program.
+        status: ExitStatus,
+        /// The captured standard error of the program.
+        stderr: String,
+    },
+}
+
+impl fmt::Display for LinkerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spawn { program, source } => {
+                write!(f, "failed to run `{}`: {source}", program.display())
+            }
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Failed { program, status, stderr } => {
+                write!(f, "`{}` failed with {status}", program.display())?;
+                let stderr = stderr.trim();
+                if !stderr.is_empty() {
+                    write!(f, ":\n{stderr}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Spawn { source, .. } | Self::Io { source, .. } => Some(source),
+            Self::Failed { .. } => None,
+        }
+    }
+}
 
 /// EVM bytecode compiler linker.
 #[derive(Debug)]
 pub struct Linker {
     cc: Option<PathBuf>,
     linker: Option<PathBuf>,
+    ar: Option<PathBuf>,
     cflags: Vec<String>,
+    output: LinkerOutput,
+    strip_debug: bool,
+    objects: Vec<OsString>,
+    libs: Vec<String>,
 }
 
 impl Default for Linker {
@@ -17,7 +97,16 @@ impl Default for Linker {
 impl Linker {
     /// Creates a new linker.
     pub fn new() -> Self {
-        Self { cc: None, linker: None, cflags: vec![] }
+        Self {
+            cc: None,
+            linker: None,
+            ar: None,
+            cflags: vec![],
+            output: LinkerOutput::default(),
+            strip_debug: true,
+            objects: vec![],
+            libs: vec![],
+        }
     }
 
     /// Sets the C compiler to use for linking. Default: "cc".
@@ -30,63 +119,138 @@ impl Linker {
         self.linker = linker;
     }
 
+    /// Sets the archiver to use for creating static archives. Default: "ar".
+    pub fn ar(&mut self, ar: Option<PathBuf>) {
+        self.ar = ar;
+    }
+
     /// Sets the C compiler flags to use for linking.
     pub fn cflags(&mut self, cflags: impl IntoIterator<Item = impl Into<String>>) {
         self.cflags.extend(cflags.into_iter().map(Into::into));
     }
 
-    /// Links the given object files into a shared library at the given path.
+    /// Sets the kind of file to produce. Default: [`LinkerOutput::Shared`].
+    pub fn output(&mut self, output: LinkerOutput) {
+        self.output = output;
+    }
+
+    /// Sets whether to strip debug info from shared libraries.
+    ///
+    /// Should be disabled to keep the line tables of objects compiled with
+    /// [`EvmCompiler::debug_info`](crate::EvmCompiler::debug_info). Default: `true`.
+    pub fn strip_debug(&mut self, yes: bool) {
+        self.strip_debug = yes;
+    }
+
+    /// Adds extra object files or archives to include in every output.
+    pub fn objects(&mut self, objects: impl IntoIterator<Item = impl AsRef<OsStr>>) {
+        self.objects.extend(objects.into_iter().map(|o| o.as_ref().to_os_string()));
+    }
+
+    /// Adds libraries to link against, as passed to `-l`.
+    pub fn libs(&mut self, libs: impl IntoIterator<Item = impl Into<String>>) {
+        self.libs.extend(libs.into_iter().map(Into::into));
+    }
+
+    /// Links the given object files into the [configured output](Self::output) at the given path.
     #[instrument(level = "debug", skip_all)]
     pub fn link(
         &self,
         out: &Path,
-        objects: impl IntoIterator<Item = impl AsRef<std::ffi::OsStr>>,
-    ) -> std::io::Result<()> {
-        let storage;
-        let cc = match &self.cc {
-            Some(cc) => cc,
-            None => {
-                let str = match std::env::var_os("CC") {
-                    Some(cc) => {
-                        storage = cc;
-                        storage.as_os_str()
-                    }
-                    None => "cc".as_ref(),
-                };
-                Path::new(str)
-            }
-        };
+        objects: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> Result<(), LinkerError> {
+        let objects = objects.into_iter().map(|o| o.as_ref().to_os_string());
+        let objects = objects.chain(self.objects.iter().cloned());
+        match self.output {
+            LinkerOutput::Shared | LinkerOutput::Relocatable => self.link_cc(out, objects),
+            LinkerOutput::Static => self.archive(out, objects),
+        }
+    }
 
-        let mut cmd = std::process::Command::new(cc);
+    fn link_cc(
+        &self,
+        out: &Path,
+        objects: impl Iterator<Item = OsString>,
+    ) -> Result<(), LinkerError> {
+        let mut cmd = Command::new(tool(self.cc.as_deref(), "CC", "cc"));
         cmd.arg("-o").arg(out);
-        cmd.arg("-shared");
-        cmd.arg("-O3");
+        match self.output {
+            LinkerOutput::Shared => {
+                cmd.arg("-shared");
+                cmd.arg("-O3");
+            }
+            LinkerOutput::Relocatable => {
+                cmd.arg("-r");
+                cmd.arg("-nostdlib");
+            }
+            LinkerOutput::Static => unreachable!(),
+        }
         if let Some(linker) = &self.linker {
             cmd.arg(format!("-fuse-ld={}", linker.display()));
         } else {
             cmd.arg("-fuse-ld=lld");
         }
-        if cfg!(target_vendor = "apple") {
-            cmd.arg("-Wl,-dead_strip,-undefined,dynamic_lookup");
-        } else {
-            cmd.arg("-Wl,--gc-sections,--strip-debug");
+        // Sections can't be garbage collected without knowing the entry points.
+        if self.output == LinkerOutput::Shared {
+            if cfg!(target_vendor = "apple") {
+                cmd.arg("-Wl,-dead_strip,-undefined,dynamic_lookup");
+            } else {
+                cmd.arg("-Wl,--gc-sections");
+                if self.strip_debug {
+                    cmd.arg("-Wl,--strip-debug");
+                }
+            }
         }
         cmd.args(&self.cflags);
         cmd.args(objects);
-        debug!(cmd=?cmd.get_program(), "linking");
-        trace!(?cmd, "full linking command");
-        let output = cmd.output()?;
-        if !output.status.success() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("cc failed with {output:#?}"),
-            ));
+        cmd.args(self.libs.iter().map(|lib| format!("-l{lib}")));
+        run(cmd, "linking")
+    }
+
+    fn archive(
+        &self,
+        out: &Path,
+        objects: impl Iterator<Item = OsString>,
+    ) -> Result<(), LinkerError> {
+        let ar = tool(self.ar.as_deref(), "AR", "ar");
+        // `ar` appends to existing archives.
+        match std::fs::remove_file(out) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(source) => return Err(LinkerError::Io { path: out.to_path_buf(), source }),
         }
-        Ok(())
+        let mut cmd = Command::new(ar);
+        cmd.arg("crs").arg(out);
+        cmd.args(objects);
+        run(cmd, "archiving")
+    }
+}
+
+/// Returns the configured tool, falling back to the given environment variable and then to the
+/// default program.
+fn tool(configured: Option<&Path>, env: &str, default: &str) -> PathBuf {
+    match configured {
+        Some(path) => path.to_path_buf(),
+        None => std::env::var_os(env).map_or_else(|| default.into(), PathBuf::from),
+    }
+}
+
+fn run(mut cmd: Command, what: &str) -> Result<(), LinkerError> {
+    let program = PathBuf::from(cmd.get_program());
+    debug!(cmd=?program, "{what}");
+    trace!(?cmd, "full {what} command");
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(source) => return Err(LinkerError::Spawn { program, source }),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        return Err(LinkerError::Failed { program, status: output.status, stderr });
     }
+    Ok(())
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "llvm"))]
 mod tests {
     use super::*;
     use revm_primitives::SpecId;
@@ -94,23 +258,9 @@ mod tests {
     #[test]
     fn basic() {
         let tmp = tempfile::tempdir().expect("could not create temp dir");
-        let obj = tmp.path().join("out.o");
+        let obj = compile_object(tmp.path(), "link_test_basic");
         let so = tmp.path().join("out.so");
 
-        // Compile and build object file.
-        let cx = crate::llvm::inkwell::context::Context::create();
-        let opt_level = revmc_backend::OptimizationLevel::Aggressive;
-        let backend = crate::EvmLlvmBackend::new(&cx, true, opt_level).unwrap();
-        let mut compiler = crate::EvmCompiler::new(backend);
-        if let Err(e) = compiler.translate("link_test_basic", &[][..], SpecId::CANCUN) {
-            panic!("failed to compile: {e}");
-        }
-
-        if let Err(e) = compiler.write_object_to_file(&obj) {
-            panic!("failed to write object: {e}");
-        }
-        assert!(obj.exists());
-
         // Link object to shared library.
         let mut linker = Linker::new();
         let mut n = 0;
@@ -130,6 +280,88 @@ mod tests {
         assert!(n > 0, "no C compiler found");
     }
 
+    #[test]
+    fn static_archive() {
+        let tmp = tempfile::tempdir().expect("could not create temp dir");
+        let obj1 = compile_object(tmp.path(), "link_test_static_1");
+        let obj2 = compile_object(tmp.path(), "link_test_static_2");
+        let a = tmp.path().join("out.a");
+
+        let mut linker = Linker::new();
+        linker.output(LinkerOutput::Static);
+        linker.objects([&obj2]);
+        for _ in 0..2 {
+            if let Err(e) = linker.link(&a, [&obj1]) {
+                panic!("failed to archive: {e}");
+            }
+            let archive = std::fs::read(&a).unwrap();
+            assert!(archive.starts_with(b"!<arch>\n"));
+        }
+    }
+
+    #[test]
+    fn relocatable() {
+        if !command_v("cc") {
+            return;
+        }
+        let tmp = tempfile::tempdir().expect("could not create temp dir");
+        let obj1 = compile_object(tmp.path(), "link_test_relocatable_1");
+        let obj2 = compile_object(tmp.path(), "link_test_relocatable_2");
+        let o = tmp.path().join("merged.o");
+
+        let mut linker = Linker::new();
+        linker.output(LinkerOutput::Relocatable);
+        if let Err(e) = linker.link(&o, [&obj1, &obj2]) {
+            panic!("failed to link: {e}");
+        }
+        assert!(o.exists());
+    }
+
+    #[test]
+    fn error() {
+        let tmp = tempfile::tempdir().expect("could not create temp dir");
+        let not_an_object = tmp.path().join("not_an_object.o");
+        std::fs::write(&not_an_object, "not an object").unwrap();
+
+        let mut linker = Linker::new();
+        linker.cc(Some(tmp.path().join("does-not-exist")));
+        let err = linker.link(&tmp.path().join("out.so"), [&not_an_object]).unwrap_err();
+        assert!(matches!(err, LinkerError::Spawn { .. }), "{err}");
+
+        // Existing archives are removed before archiving.
+        let dir = tmp.path().join("dir.a");
+        std::fs::create_dir(&dir).unwrap();
+        linker.output(LinkerOutput::Static);
+        let err = linker.link(&dir, [&not_an_object]).unwrap_err();
+        assert!(matches!(err, LinkerError::Io { .. }), "{err}");
+        linker.output(LinkerOutput::Shared);
+
+        if !command_v("cc") {
+            return;
+        }
+        linker.cc(Some("cc".into()));
+        let err = linker.link(&tmp.path().join("out.so"), [&not_an_object]).unwrap_err();
+        let LinkerError::Failed { stderr, .. } = &err else { panic!("{err}") };
+        assert!(!stderr.is_empty());
+    }
+
+    fn compile_object(dir: &Path, name: &str) -> PathBuf {
+        let obj = dir.join(name).with_extension("o");
+        let cx = crate::llvm::inkwell::context::Context::create();
+        let opt_level = revmc_backend::OptimizationLevel::Aggressive;
+        let backend = crate::EvmLlvmBackend::new(&cx, true, opt_level).unwrap();
+        let mut compiler = crate::EvmCompiler::new(backend);
+        if let Err(e) = compiler.translate(name, &[][..], SpecId::CANCUN) {
+            panic!("failed to compile: {e}");
+        }
+
+        if let Err(e) = compiler.write_object_to_file(&obj) {
+            panic!("failed to write object: {e}");
+        }
+        assert!(obj.exists());
+        obj
+    }
+
     fn command_v(cmd: &str) -> bool {
         let Ok(output) = std::process::Command::new(cmd).arg("--version").output() else {
             return false;
@@ -0,0 +1,74 @@
+use super::DEF_SPEC;
+use crate::{Backend, BudgetExceeded, CompileError, EvmCompiler};
+use revm_interpreter::opcode as op;
+use std::time::Duration;
+
+matrix_tests!(bytecode_size = |compiler| run(compiler, bytecode_size));
+matrix_tests!(instructions = |compiler| run(compiler, instructions));
+matrix_tests!(dynamic_jump_targets = |compiler| run(compiler, dynamic_jump_targets));
+matrix_tests!(optimization_time = |compiler| run(compiler, optimization_time));
+matrix_tests!(optimization_time_unfinalized = run_optimization_time_unfinalized);
+
+#[rustfmt::skip]
+const CODE: &[u8] = &[
+    op::PUSH0, op::CALLDATALOAD, op::JUMP,
+    op::JUMPDEST, op::PUSH1, 0x69, op::STOP,
+    op::JUMPDEST, op::PUSH1, 0x42, op::STOP,
+];
+
+fn bytecode_size<B: Backend>(compiler: &mut EvmCompiler<B>, limit: bool) -> Option<BudgetExceeded> {
+    compiler.max_bytecode_size(Some(if limit { CODE.len() - 1 } else { CODE.len() }));
+    Some(BudgetExceeded::BytecodeSize { size: CODE.len(), limit: CODE.len() - 1 })
+}
+
+fn instructions<B: Backend>(compiler: &mut EvmCompiler<B>, limit: bool) -> Option<BudgetExceeded> {
+    compiler.max_instructions(Some(if limit { 7 } else { 9 }));
+    Some(BudgetExceeded::Instructions { count: 9, limit: 7 })
+}
+
+fn dynamic_jump_targets<B: Backend>(
+    compiler: &mut EvmCompiler<B>,
+    limit: bool,
+) -> Option<BudgetExceeded> {
+    compiler.max_dynamic_jump_targets(Some(if limit { 1 } else { 2 }));
+    Some(BudgetExceeded::DynamicJumpTargets { count: 2, limit: 1 })
+}
+
+fn optimization_time<B: Backend>(
+    compiler: &mut EvmCompiler<B>,
+    limit: bool,
+) -> Option<BudgetExceeded> {
+    compiler.max_optimization_time(Some(if limit { Duration::ZERO } else { Duration::MAX }));
+    // The elapsed time is not known in advance.
+    None
+}
+
+fn run<B: Backend>(
+    compiler: &mut EvmCompiler<B>,
+    set_limit: fn(&mut EvmCompiler<B>, bool) -> Option<BudgetExceeded>,
+) {
+    unsafe { compiler.clear() }.unwrap();
+    set_limit(compiler, false);
+    unsafe { compiler.jit("budget", CODE, DEF_SPEC) }.unwrap();
+
+    unsafe { compiler.clear() }.unwrap();
+    let expected = set_limit(compiler, true);
+    let err = unsafe { compiler.jit("budget", CODE, DEF_SPEC) }.unwrap_err();
+    let CompileError::BudgetExceeded(exceeded) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    match expected {
+        Some(expected) => assert_eq!(exceeded, expected),
+        None => assert!(matches!(exceeded, BudgetExceeded::OptimizationTime { .. }), "{exceeded}"),
+    }
+}
+
+// Exceeding the optimization time budget does not leave the module finalized.
+fn run_optimization_time_unfinalized<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    compiler.max_optimization_time(Some(Duration::ZERO));
+    let id = compiler.translate("budget", CODE, DEF_SPEC).unwrap();
+    let err = unsafe { compiler.jit_function(id) }.unwrap_err();
+    assert!(matches!(err, CompileError::BudgetExceeded(_)), "{err}");
+    compiler.translate("budget_2", CODE, DEF_SPEC).unwrap();
+    unsafe { compiler.clear() }.unwrap();
+}
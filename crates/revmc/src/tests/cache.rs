@@ -0,0 +1,57 @@
+use super::{with_evm_context, DEF_SPEC};
+use crate::{Backend, EvmCompiler, ObjectCache};
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::{SpecId, U256};
+
+// Loading objects is not supported by the Cranelift backend.
+mod jit_cached {
+    use super::*;
+
+    matrix_tests!(@llvm run);
+}
+
+#[rustfmt::skip]
+const TEST: &[u8] = &[
+    op::PUSH1, 0x42,
+    op::PUSH1, 0x69,
+    op::ADD,
+    op::STOP,
+];
+
+fn run<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    let root = tempfile::tempdir().unwrap();
+    let cache = ObjectCache::new(root.path()).unwrap();
+    compiler.inspect_stack_length(true);
+
+    let key = compiler.cache_key(TEST, DEF_SPEC);
+    assert_ne!(key, compiler.cache_key(TEST, SpecId::LATEST));
+    assert_ne!(key, compiler.cache_key(&TEST[..TEST.len() - 1], DEF_SPEC));
+
+    // Miss: translates and populates the cache.
+    assert!(!cache.contains(&key));
+    let f1 = unsafe { compiler.jit_cached(&cache, TEST, DEF_SPEC) }.unwrap();
+    assert!(cache.contains(&key));
+    check(f1);
+
+    // Hit: loads the cached object without translating.
+    unsafe { compiler.clear() }.unwrap();
+    let f2 = unsafe { compiler.jit_cached(&cache, TEST, DEF_SPEC) }.unwrap();
+    check(f2);
+
+    // Loading the same object again returns the same function.
+    let f3 = unsafe { compiler.jit_cached(&cache, TEST, DEF_SPEC) }.unwrap();
+    assert_eq!(f2, f3);
+
+    // Changing the configuration changes the key.
+    compiler.gas_metering(false);
+    assert_ne!(key, compiler.cache_key(TEST, DEF_SPEC));
+}
+
+fn check(f: crate::EvmCompilerFn) {
+    with_evm_context(TEST, |ecx, stack, stack_len| {
+        let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+        assert_eq!(r, InstructionResult::Stop);
+        assert_eq!(*stack_len, 1);
+        assert_eq!(stack.as_slice()[0].to_u256(), U256::from(0x42 + 0x69));
+    });
+}
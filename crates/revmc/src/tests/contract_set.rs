@@ -0,0 +1,133 @@
+use super::{eof_sections, with_evm_context, DEF_SPEC};
+use crate::{
+    llvm::with_llvm_context, AotContract, AotContractInfoFn, AotLookupFn, EvmCompiler,
+    EvmCompilerFn, EvmLlvmBackend, Linker, OptimizationLevel, AOT_CONTRACT_INFO_SYMBOL,
+    AOT_LOOKUP_SYMBOL,
+};
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::{keccak256, SpecId, B256, U256};
+use std::mem::{self, MaybeUninit};
+
+const NAMES: [&str; 3] = ["contract_set_a", "contract_set_b", "contract_set_c"];
+
+#[test]
+fn lookup() {
+    with_llvm_context(|cx| {
+        let object = compile(cx);
+        // The object is loaded into a JIT in place of a shared library.
+        let backend = EvmLlvmBackend::new(cx, false, OptimizationLevel::None).unwrap();
+        let mut compiler = EvmCompiler::new(backend);
+        let lookup = unsafe { compiler.jit_object(AOT_LOOKUP_SYMBOL, &object) }.unwrap();
+        let lookup = unsafe { mem::transmute::<_, AotLookupFn>(lookup.into_inner()) };
+
+        for x in 0..NAMES.len() as u8 {
+            let f = unsafe { lookup(&keccak256(code(x))) }.unwrap();
+            check(EvmCompilerFn::new(f), x);
+        }
+        assert!(unsafe { lookup(&B256::ZERO) }.is_none());
+    });
+}
+
+#[test]
+fn contract_info() {
+    with_llvm_context(|cx| {
+        let object = compile(cx);
+        let backend = EvmLlvmBackend::new(cx, false, OptimizationLevel::None).unwrap();
+        let mut compiler = EvmCompiler::new(backend);
+        let info = unsafe { compiler.jit_object(AOT_CONTRACT_INFO_SYMBOL, &object) }.unwrap();
+        let info = unsafe { mem::transmute::<_, AotContractInfoFn>(info.into_inner()) };
+
+        let mut infos = Vec::new();
+        let mut out = MaybeUninit::uninit();
+        while unsafe { info(infos.len(), &mut out) } {
+            infos.push(unsafe { out.assume_init() });
+        }
+        assert_eq!(infos.len(), NAMES.len());
+        for (x, info) in infos.iter().enumerate() {
+            assert_eq!(info.name_str(), NAMES[x]);
+            assert_eq!(info.spec_id(), Some(DEF_SPEC));
+            assert_eq!(info.code_hash, keccak256(code(x as u8)));
+            check(info.function(), x as u8);
+        }
+    });
+}
+
+#[test]
+fn shared_library() {
+    let tmp = tempfile::tempdir().expect("could not create temp dir");
+    let obj = tmp.path().join("contract_set.o");
+    let so = tmp.path().join("contract_set.so");
+    let eof_code = [op::PUSH1, 2, op::PUSH1, 0x69, op::ADD, op::STOP];
+    let eof = eof_sections(&[&eof_code]);
+
+    with_llvm_context(|cx| {
+        let backend = EvmLlvmBackend::new(cx, true, OptimizationLevel::Aggressive).unwrap();
+        let mut compiler = EvmCompiler::new(backend);
+        compiler.inspect_stack_length(true);
+        compiler.validate_eof(false);
+        let codes = (0..2).map(code).collect::<Vec<_>>();
+        let contracts = NAMES[..2]
+            .iter()
+            .zip(&codes)
+            .map(|(&name, code)| AotContract::new(name, &code[..], DEF_SPEC))
+            .chain([AotContract::new(NAMES[2], &eof, SpecId::PRAGUE_EOF)]);
+        compiler.translate_contract_set(contracts).unwrap();
+        compiler.write_object_to_file(&obj).unwrap();
+    });
+    if let Err(e) = Linker::new().link(&so, [&obj]) {
+        panic!("failed to link: {e}");
+    }
+
+    let lib = unsafe { libloading::Library::new(&so) }.unwrap();
+    let lookup = unsafe { lib.get::<AotLookupFn>(AOT_LOOKUP_SYMBOL.as_bytes()) }.unwrap();
+    for x in 0..2 {
+        let f = unsafe { lookup(&keccak256(code(x))) }.unwrap();
+        check(EvmCompilerFn::new(f), x);
+    }
+    // EOF contracts are found by the hash of the whole container, not of the code section.
+    assert!(unsafe { lookup(&keccak256(eof_code)) }.is_none());
+    let f = unsafe { lookup(&keccak256(&eof)) }.unwrap();
+    check_code(EvmCompilerFn::new(f), &eof, 2);
+}
+
+#[test]
+fn duplicate_code_hash() {
+    with_llvm_context(|cx| {
+        let backend = EvmLlvmBackend::new(cx, true, OptimizationLevel::None).unwrap();
+        let mut compiler = EvmCompiler::new(backend);
+        let code = code(0);
+        let contracts = NAMES[..2].iter().map(|&name| AotContract::new(name, &code[..], DEF_SPEC));
+        let err = compiler.translate_contract_set(contracts).unwrap_err();
+        assert!(err.to_string().contains("same code hash"), "{err}");
+    });
+}
+
+fn compile(cx: &crate::llvm::inkwell::context::Context) -> Vec<u8> {
+    let backend = EvmLlvmBackend::new(cx, true, OptimizationLevel::Aggressive).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.inspect_stack_length(true);
+    let codes = (0..NAMES.len() as u8).map(code).collect::<Vec<_>>();
+    let contracts =
+        NAMES.iter().zip(&codes).map(|(&name, code)| AotContract::new(name, &code[..], DEF_SPEC));
+    compiler.translate_contract_set(contracts).unwrap();
+    let mut object = Vec::new();
+    compiler.write_object(&mut object).unwrap();
+    object
+}
+
+fn code(x: u8) -> [u8; 5] {
+    [op::PUSH1, x, op::PUSH1, 0x69, op::ADD]
+}
+
+fn check(f: EvmCompilerFn, x: u8) {
+    check_code(f, &code(x), x);
+}
+
+fn check_code(f: EvmCompilerFn, code: &[u8], x: u8) {
+    with_evm_context(code, |ecx, stack, stack_len| {
+        let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+        assert_eq!(r, InstructionResult::Stop);
+        assert_eq!(*stack_len, 1);
+        assert_eq!(stack.as_slice()[0].to_u256(), U256::from(x as u64 + 0x69));
+    });
+}
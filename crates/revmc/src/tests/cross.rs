@@ -0,0 +1,108 @@
+//! AOT compilation for targets other than the host.
+
+use super::DEF_SPEC;
+use crate::{
+    llvm::{inkwell::context::Context, with_llvm_context},
+    AotContract, Backend, EvmCompiler, EvmLlvmBackend, OptimizationLevel, Result, Target,
+    TargetDataLayout, AOT_LOOKUP_SYMBOL,
+};
+use object::{
+    Architecture, BinaryFormat, Object, ObjectSection, ObjectSymbol, RelocationKind,
+    RelocationTarget, SectionKind, SymbolKind,
+};
+use revm_interpreter::opcode as op;
+
+const NAME: &str = "cross_test";
+
+#[rustfmt::skip]
+const CODE: &[u8] = &[
+    op::PUSH1, 0x69, op::PUSH0, op::SSTORE,
+    op::PUSH1, 0x20, op::PUSH0, op::KECCAK256,
+    op::PUSH0, op::MSTORE,
+    op::PUSH1, 0x20, op::PUSH0, op::RETURN,
+];
+
+const TARGETS: &[(&str, Architecture)] = &[
+    ("aarch64-unknown-linux-gnu", Architecture::Aarch64),
+    ("riscv64gc-unknown-linux-gnu", Architecture::Riscv64),
+];
+
+#[test]
+fn objects() {
+    with_llvm_context(|cx| {
+        for &(triple, arch) in TARGETS {
+            let object = compile(cx, triple).unwrap();
+            let file = object::File::parse(&*object).unwrap();
+            assert_eq!(file.format(), BinaryFormat::Elf, "{triple}");
+            assert_eq!(file.architecture(), arch, "{triple}");
+            assert!(file.is_little_endian() && file.is_64(), "{triple}");
+
+            for name in [NAME, AOT_LOOKUP_SYMBOL] {
+                let symbol =
+                    file.symbol_by_name(name).unwrap_or_else(|| panic!("{triple}: {name}"));
+                assert!(symbol.is_definition() && symbol.is_global(), "{triple}: {name}");
+                assert_eq!(symbol.kind(), SymbolKind::Text, "{triple}: {name}");
+            }
+
+            // Builtins are left to be resolved by the linker, through position-independent
+            // relocations.
+            let mut builtins = Vec::new();
+            for section in file.sections().filter(|s| s.kind() == SectionKind::Text) {
+                for (offset, reloc) in section.relocations() {
+                    assert_ne!(
+                        reloc.kind(),
+                        RelocationKind::Absolute,
+                        "{triple}: absolute relocation in {}+{offset:#x}",
+                        section.name().unwrap(),
+                    );
+                    let RelocationTarget::Symbol(index) = reloc.target() else { continue };
+                    let symbol = file.symbol_by_index(index).unwrap();
+                    let name = symbol.name().unwrap();
+                    if symbol.is_undefined() && name.starts_with("__revmc_builtin_") {
+                        builtins.push(name);
+                    }
+                }
+            }
+            for builtin in ["__revmc_builtin_sstore", "__revmc_builtin_keccak256_32"] {
+                assert!(builtins.contains(&builtin), "{triple}: {builtins:?}");
+            }
+        }
+    });
+}
+
+#[test]
+fn data_layout() {
+    with_llvm_context(|cx| {
+        let backend = EvmLlvmBackend::new(cx, true, OptimizationLevel::None).unwrap();
+        assert_eq!(backend.target_data_layout(), TargetDataLayout::host());
+
+        for &(triple, _) in TARGETS {
+            let backend = new_backend(cx, triple).unwrap();
+            assert_eq!(backend.target_data_layout(), TargetDataLayout::host(), "{triple}");
+        }
+    });
+}
+
+#[test]
+fn unsupported_data_layout() {
+    with_llvm_context(|cx| {
+        // 32-bit and big-endian.
+        for triple in ["i686-unknown-linux-gnu", "powerpc64-unknown-linux-gnu"] {
+            let err = compile(cx, triple).unwrap_err();
+            assert!(err.to_string().contains("data layout"), "{triple}: {err}");
+        }
+    });
+}
+
+fn new_backend<'ctx>(cx: &'ctx Context, triple: &str) -> Result<EvmLlvmBackend<'ctx>> {
+    let target = Target::triple(triple);
+    EvmLlvmBackend::new_for_target(cx, true, OptimizationLevel::Aggressive, &target)
+}
+
+fn compile(cx: &Context, triple: &str) -> Result<Vec<u8>> {
+    let mut compiler = EvmCompiler::new(new_backend(cx, triple)?);
+    compiler.translate_contract_set([AotContract::new(NAME, CODE, DEF_SPEC)])?;
+    let mut object = Vec::new();
+    compiler.write_object(&mut object)?;
+    Ok(object)
+}
@@ -0,0 +1,90 @@
+use super::{eof, with_evm_context, DEF_SPEC};
+use crate::{Backend, EvmCompiler};
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::{SpecId, U256};
+
+matrix_tests!(legacy = |compiler| run(compiler, TEST, DEF_SPEC));
+matrix_tests!(eof = |compiler| run(compiler, &eof(TEST), SpecId::PRAGUE_EOF));
+
+// Includes calls to both external and IR builtins.
+#[rustfmt::skip]
+const TEST: &[u8] = &[
+    op::PUSH1, 3,
+    op::PUSH1, 2,
+    op::EXP,
+    op::PUSH0,
+    op::MSTORE,
+    op::PUSH1, 32,
+    op::PUSH0,
+    op::KECCAK256,
+    op::PUSH1, 0xff,
+    op::PUSH1, 0,
+    op::SIGNEXTEND,
+    op::STOP,
+];
+
+fn run<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[u8], spec_id: SpecId) {
+    // Done manually in `fn eof` and friends.
+    compiler.validate_eof(false);
+    compiler.inspect_stack_length(true);
+    compiler.debug_info(true);
+    let f = unsafe { compiler.jit("debug_info", code, spec_id) }.unwrap();
+
+    with_evm_context(code, |ecx, stack, stack_len| {
+        let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+        assert_eq!(r, InstructionResult::Stop);
+        assert_eq!(*stack_len, 2);
+        assert_eq!(stack.as_slice()[1].to_u256(), U256::MAX);
+    });
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn line_table() {
+    use crate::{llvm::with_llvm_context, EvmLlvmBackend, OpcodesIter, OptimizationLevel};
+    use object::{Object, ObjectSection};
+    use std::collections::BTreeMap;
+
+    let object = with_llvm_context(|cx| {
+        let backend = EvmLlvmBackend::new(cx, true, OptimizationLevel::None).unwrap();
+        let mut compiler = EvmCompiler::new(backend);
+        compiler.debug_info(true);
+        compiler.translate("debug_info", TEST, DEF_SPEC).unwrap();
+        let mut object = Vec::new();
+        compiler.write_object(&mut object).unwrap();
+        object
+    });
+    let file = object::File::parse(&*object).unwrap();
+    let section = file.section_by_name(".debug_line").expect("no .debug_line section");
+    let data = section.uncompressed_data().unwrap();
+
+    // Every row must point at the line of an instruction, or at line 0 for code that does not
+    // belong to any instruction.
+    let insts = OpcodesIter::new(TEST, DEF_SPEC)
+        .with_pc()
+        .map(|(pc, opcode)| (pc as u64, opcode))
+        .collect::<BTreeMap<_, _>>();
+    let mut pcs = Vec::new();
+    let debug_line = gimli::DebugLine::new(&data, gimli::LittleEndian);
+    let program = debug_line.program(gimli::DebugLineOffset(0), 8, None, None).unwrap();
+    let mut rows = program.rows();
+    while let Some((header, row)) = rows.next_row().unwrap() {
+        if row.end_sequence() {
+            continue;
+        }
+        let file = row.file(header).unwrap();
+        let gimli::AttributeValue::String(name) = file.path_name() else {
+            panic!("unexpected file name: {:?}", file.path_name())
+        };
+        assert_eq!(&*name, b"debug_info.evm");
+        let Some(line) = row.line() else { continue };
+        let pc = line.get() - 1;
+        assert!(insts.contains_key(&pc), "line {line} does not map to an instruction");
+        pcs.push(pc);
+    }
+    for (pc, opcode) in &insts {
+        if [op::EXP, op::KECCAK256, op::SIGNEXTEND].contains(&opcode.opcode) {
+            assert!(pcs.contains(pc), "no rows for {opcode} at pc {pc}: {pcs:?}");
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::{
+    eof_sections, eof_sections_unchecked, run_test_case, with_evm_context, TestCase,
+    GAS_WHAT_INTERPRETER_SAYS, STACK_WHAT_INTERPRETER_SAYS,
+};
+use crate::{Backend, Bytecode, EvmCompiler, TEST_SUSPEND};
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::{SpecId, U256};
+use std::borrow::Cow;
+
+matrix_tests!(
+    callf = |compiler| run(compiler, CALLF, &[U256::from(3), U256::from(2), U256::from(1)])
+);
+matrix_tests!(jumpf = |compiler| run(compiler, JUMPF, &[U256::from(3), U256::from(1)]));
+matrix_tests!(jumpf_entry = run_jumpf_entry);
+matrix_tests!(recursion = run_recursion);
+matrix_tests!(jumpf_loop = |compiler| run_out_of_gas(compiler, JUMPF_LOOP));
+matrix_tests!(jumpf_cycle = |compiler| run_out_of_gas(compiler, JUMPF_CYCLE));
+matrix_tests!(
+    same_as_flattened = |compiler| {
+        for code in [CALLF, JUMPF, JUMPF_ENTRY, SUSPEND, JUMPF_LOOP, JUMPF_CYCLE] {
+            run_same_as_flattened(compiler, code);
+        }
+    }
+);
+
+#[rustfmt::skip]
+const CALLF: &[&[u8]] = &[
+    &[op::CALLF, 0x00, 0x01, op::PUSH1, 1, op::STOP],
+    &[op::CALLF, 0x00, 0x02, op::PUSH1, 2, op::RETF],
+    &[                       op::PUSH1, 3, op::RETF],
+];
+
+#[rustfmt::skip]
+const JUMPF: &[&[u8]] = &[
+    &[op::CALLF, 0x00, 0x01, op::PUSH1, 1, op::STOP],
+    &[op::JUMPF, 0x00, 0x02, op::PUSH1, 2, op::RETF],
+    &[                       op::PUSH1, 3, op::RETF],
+];
+
+#[rustfmt::skip]
+const JUMPF_ENTRY: &[&[u8]] = &[
+    &[op::CALLF, 0x00, 0x01, op::STOP],
+    &[op::PUSH1, 1, op::POP, op::JUMPF, 0x00, 0x00],
+];
+
+#[rustfmt::skip]
+const SUSPEND: &[&[u8]] = &[
+    &[op::CALLF, 0x00, 0x01, op::PUSH1, 1, op::STOP],
+    &[op::PUSH1, 2, TEST_SUSPEND, op::RETF],
+];
+
+// Runs until it is out of gas.
+#[rustfmt::skip]
+const JUMPF_LOOP: &[&[u8]] = &[
+    &[op::CALLF, 0x00, 0x01, op::STOP],
+    &[op::PUSH1, 1, op::POP, op::JUMPF, 0x00, 0x01],
+];
+
+// Runs until it is out of gas.
+#[rustfmt::skip]
+const JUMPF_CYCLE: &[&[u8]] = &[
+    &[op::CALLF, 0x00, 0x01, op::STOP],
+    &[op::PUSH1, 1, op::POP, op::JUMPF, 0x00, 0x02],
+    &[op::PUSH1, 2, op::POP, op::JUMPF, 0x00, 0x01],
+];
+
+fn run<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[&[u8]], expected_stack: &[U256]) {
+    compiler.eof_section_functions(true);
+    run_test_case(
+        &TestCase {
+            bytecode: &eof_sections(code),
+            spec_id: SpecId::PRAGUE_EOF,
+            expected_stack,
+            expected_gas: GAS_WHAT_INTERPRETER_SAYS,
+            ..Default::default()
+        },
+        compiler,
+    );
+}
+
+fn run_jumpf_entry<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    compiler.eof_section_functions(true);
+    run_test_case(
+        &TestCase {
+            bytecode: &eof_sections_unchecked(&[
+                &[op::PUSH1, 1, op::JUMPF, 0x00, 0x01],
+                &[op::PUSH1, 2, op::STOP],
+            ])
+            .raw,
+            spec_id: SpecId::PRAGUE_EOF,
+            expected_stack: &[U256::from(1), U256::from(2)],
+            expected_gas: GAS_WHAT_INTERPRETER_SAYS,
+            ..Default::default()
+        },
+        compiler,
+    );
+}
+
+// The function stack overflows after 1024 nested `CALLF`s.
+fn run_recursion<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    compiler.eof_section_functions(true);
+    run_test_case(
+        &TestCase {
+            bytecode: &eof_sections_unchecked(&[
+                &[op::CALLF, 0x00, 0x01, op::STOP],
+                &[op::CALLF, 0x00, 0x01, op::RETF],
+            ])
+            .raw,
+            spec_id: SpecId::PRAGUE_EOF,
+            expected_return: InstructionResult::EOFFunctionStackOverflow,
+            expected_stack: STACK_WHAT_INTERPRETER_SAYS,
+            expected_gas: GAS_WHAT_INTERPRETER_SAYS,
+            ..Default::default()
+        },
+        compiler,
+    );
+}
+
+// `JUMPF` loops must not grow the native stack.
+fn run_out_of_gas<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[&[u8]]) {
+    compiler.eof_section_functions(true);
+    run_test_case(
+        &TestCase {
+            bytecode: &eof_sections_unchecked(code).raw,
+            spec_id: SpecId::PRAGUE_EOF,
+            expected_return: InstructionResult::OutOfGas,
+            expected_stack: STACK_WHAT_INTERPRETER_SAYS,
+            expected_gas: GAS_WHAT_INTERPRETER_SAYS,
+            ..Default::default()
+        },
+        compiler,
+    );
+}
+
+fn run_same_as_flattened<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[&[u8]]) {
+    let code = &eof_sections_unchecked(code).raw;
+    compiler.inspect_stack_length(true);
+    compiler.validate_eof(false);
+    compiler.eof_section_functions(true);
+    let sections = compiler.translate("sections", &code[..], SpecId::PRAGUE_EOF).unwrap();
+    compiler.eof_section_functions(false);
+    let flattened = compiler.translate("flattened", &code[..], SpecId::PRAGUE_EOF).unwrap();
+    let sections = unsafe { compiler.jit_function(sections) }.unwrap();
+    let flattened = unsafe { compiler.jit_function(flattened) }.unwrap();
+
+    let [sections, flattened] = [sections, flattened].map(|f| {
+        with_evm_context(code, |ecx, stack, stack_len| {
+            let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+            let stack = stack.as_slice()[..*stack_len]
+                .iter()
+                .map(|word| word.to_u256())
+                .collect::<Vec<_>>();
+            (r, stack, ecx.gas.remaining())
+        })
+    });
+    assert_eq!(sections, flattened);
+    unsafe { compiler.clear() }.unwrap();
+}
+
+#[test]
+fn fallback_to_flattened() {
+    let supports = |code: &[&[u8]]| {
+        let eof = eof_sections_unchecked(code);
+        let mut bytecode = Bytecode::new(&[], Some(Cow::Owned(eof)), SpecId::PRAGUE_EOF);
+        bytecode.analyze().unwrap();
+        bytecode.supports_eof_section_functions()
+    };
+    assert!(supports(CALLF));
+    assert!(supports(JUMPF));
+    assert!(supports(JUMPF_LOOP));
+    assert!(!supports(&[&[op::STOP]]));
+    assert!(!supports(JUMPF_ENTRY));
+    assert!(!supports(SUSPEND));
+    assert!(!supports(JUMPF_CYCLE));
+}
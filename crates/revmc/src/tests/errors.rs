@@ -0,0 +1,35 @@
+use super::{eof_sections_unchecked, DEF_SPEC};
+use crate::{Backend, CompileError, EvmCompiler, EvmCompilerInput};
+use revm_interpreter::opcode as op;
+use revm_primitives::SpecId;
+
+matrix_tests!(invalid_usage = |compiler| invalid_usage(compiler));
+matrix_tests!(invalid_bytecode = |compiler| invalid_bytecode(compiler));
+matrix_tests!(unsupported_spec = |compiler| unsupported_spec(compiler));
+
+fn invalid_usage<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    compiler.translate("errors", &[op::STOP][..], DEF_SPEC).unwrap();
+    let err = compiler.translate("errors", &[op::STOP][..], DEF_SPEC).unwrap_err();
+    assert!(matches!(err, CompileError::InvalidUsage(_)), "{err:?}");
+    assert!(err.to_string().contains("not unique"), "{err}");
+}
+
+fn invalid_bytecode<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    let code = [0xEF, 0x00, 0x01, 0xFF];
+    let err = compiler.translate("errors", &code[..], SpecId::PRAGUE_EOF).unwrap_err();
+    assert!(matches!(err, CompileError::InvalidBytecode(_)), "{err:?}");
+}
+
+fn unsupported_spec<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    let eof = eof_sections_unchecked(&[&[op::STOP]]);
+    let err =
+        compiler.translate("errors", EvmCompilerInput::Eof(&eof), SpecId::CANCUN).unwrap_err();
+    assert!(matches!(err, CompileError::UnsupportedSpec(SpecId::CANCUN)), "{err:?}");
+
+    // Converts into and back from a report.
+    let report = crate::Error::from(err);
+    assert!(
+        matches!(report.downcast_ref::<CompileError>(), Some(CompileError::UnsupportedSpec(_))),
+        "{report:?}"
+    );
+}
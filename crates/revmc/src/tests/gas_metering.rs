@@ -0,0 +1,146 @@
+use super::{with_evm_context, TestHost, DEF_SPEC};
+use crate::{Backend, EvmCompiler};
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::{spec_to_generic, SpecId};
+
+matrix_tests!(sections = |compiler| run_all(compiler, true));
+matrix_tests!(instructions = |compiler| run_all(compiler, false));
+
+#[rustfmt::skip]
+const SUCCESS: &[u8] = &[
+    op::PUSH1, 1,
+    op::PUSH1, 2,
+    op::ADD,
+    op::POP,
+    op::STOP,
+];
+
+// `RETURNDATACOPY` fails with `OutOfOffset` in the middle of the section.
+#[rustfmt::skip]
+const BUILTIN_FAILURE: &[u8] = &[
+    op::PUSH1, 1,
+    op::PUSH1, 0xff,
+    op::PUSH0,
+    op::RETURNDATACOPY,
+    op::PUSH0,
+    op::PUSH0,
+    op::ADD,
+    op::STOP,
+];
+
+#[rustfmt::skip]
+const UNKNOWN: &[u8] = &[
+    op::PUSH1, 1,
+    0x0c,
+    op::PUSH1, 2,
+    op::STOP,
+];
+
+// `PUSH0` is not activated before Shanghai.
+#[rustfmt::skip]
+const DISABLED: &[u8] = &[
+    op::PUSH1, 1,
+    op::PUSH0,
+    op::PUSH1, 2,
+    op::STOP,
+];
+
+#[rustfmt::skip]
+const INVALID: &[u8] = &[
+    op::PUSH1, 1,
+    op::INVALID,
+];
+
+#[rustfmt::skip]
+const MEMORY: &[u8] = &[
+    op::PUSH1, 0x20,
+    op::MLOAD,
+    op::PUSH2, 0x01, 0x00,
+    op::MSTORE,
+    op::PUSH1, 1,
+    op::PUSH2, 0x01, 0x1f,
+    op::MSTORE8,
+    op::PUSH1, 0x40,
+    op::MLOAD,
+    op::STOP,
+];
+
+#[rustfmt::skip]
+const MEMORY_OOG: &[u8] = &[
+    op::PUSH1, 1,
+    op::PUSH3, 0xff, 0xff, 0xff,
+    op::MSTORE,
+    op::STOP,
+];
+
+// Sizes whose expansion cost could overflow are charged by the `ResizeMemory` builtin.
+#[rustfmt::skip]
+const MEMORY_OOG_LARGE: &[u8] = &[
+    op::PUSH1, 1,
+    op::PUSH8, 0x10, 0, 0, 0, 0, 0, 0, 0,
+    op::MSTORE,
+    op::STOP,
+];
+
+// The `ResizeMemory` builtin fails with `MemoryOOG` in the middle of the section.
+#[rustfmt::skip]
+const BUILTIN_OOG: &[u8] = &[
+    op::PUSH1, 1,
+    op::PUSH8, 0x10, 0, 0, 0, 0, 0, 0, 0,
+    op::MSTORE,
+    op::PUSH0,
+    op::PUSH0,
+    op::ADD,
+    op::STOP,
+];
+
+fn run_all<B: Backend>(compiler: &mut EvmCompiler<B>, section_gas_metering: bool) {
+    compiler.section_gas_metering(section_gas_metering);
+    run(compiler, SUCCESS, DEF_SPEC, InstructionResult::Stop);
+    run(compiler, BUILTIN_FAILURE, DEF_SPEC, InstructionResult::OutOfOffset);
+    run(compiler, UNKNOWN, DEF_SPEC, InstructionResult::OpcodeNotFound);
+    run(compiler, DISABLED, SpecId::LONDON, InstructionResult::NotActivated);
+    run(compiler, INVALID, DEF_SPEC, InstructionResult::InvalidFEOpcode);
+    run(compiler, MEMORY, DEF_SPEC, InstructionResult::Stop);
+    run(compiler, MEMORY_OOG, DEF_SPEC, InstructionResult::MemoryOOG);
+    run(compiler, MEMORY_OOG_LARGE, DEF_SPEC, InstructionResult::MemoryOOG);
+
+    // All of the gas is consumed on out of gas errors, so the gas paid in advance for the rest of
+    // the section is not refunded, same as with `OutOfGas`.
+    let unrefunded = if section_gas_metering { 2 + 2 + 3 } else { 0 };
+    run_with(compiler, BUILTIN_OOG, DEF_SPEC, InstructionResult::MemoryOOG, unrefunded);
+}
+
+fn run<B: Backend>(
+    compiler: &mut EvmCompiler<B>,
+    code: &[u8],
+    spec_id: SpecId,
+    expected_return: InstructionResult,
+) {
+    run_with(compiler, code, spec_id, expected_return, 0);
+}
+
+fn run_with<B: Backend>(
+    compiler: &mut EvmCompiler<B>,
+    code: &[u8],
+    spec_id: SpecId,
+    expected_return: InstructionResult,
+    unrefunded_gas: u64,
+) {
+    unsafe { compiler.clear() }.unwrap();
+    let f = unsafe { compiler.jit("gas", code, spec_id) }.unwrap();
+
+    with_evm_context(code, |ecx, stack, stack_len| {
+        let table = spec_to_generic!(spec_id, op::make_instruction_table::<_, SPEC>());
+        let mut interpreter = ecx.to_interpreter(Default::default());
+        let memory = interpreter.take_memory();
+        interpreter.run(memory, &table, &mut TestHost::new());
+        assert_eq!(interpreter.instruction_result, expected_return, "interpreter return");
+
+        let actual_return = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+        assert_eq!(actual_return, expected_return, "return");
+        let mut expected_gas = interpreter.gas;
+        assert!(expected_gas.record_cost(unrefunded_gas));
+        assert_eq!(*ecx.gas, expected_gas, "gas mismatch");
+    });
+}
@@ -0,0 +1,69 @@
+use super::{with_evm_context, TestHost, DEF_GAS_LIMIT, DEF_SPEC};
+use crate::{Backend, EvmCompiler, EvmContext, InstructionInspector, InstructionStep};
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::U256;
+
+matrix_tests!(steps = |compiler| run(compiler, true));
+matrix_tests!(disabled = |compiler| run(compiler, false));
+
+#[rustfmt::skip]
+const TEST: &[u8] = &[
+    op::PUSH1, 1,
+    op::PUSH1, 2,
+    op::ADD,
+    op::PUSH1, 3,
+    op::MUL,
+    op::STOP,
+];
+
+#[derive(Default)]
+struct Recorder {
+    steps: Vec<(usize, u8, Vec<U256>)>,
+    gas_remaining: Vec<u64>,
+}
+
+impl InstructionInspector for Recorder {
+    fn step(&mut self, step: &InstructionStep<'_>, ecx: &mut EvmContext<'_>) {
+        self.gas_remaining.push(ecx.gas.remaining());
+        let stack = step.stack.iter().map(|word| word.to_u256()).collect();
+        self.steps.push((step.pc, step.opcode, stack));
+    }
+}
+
+fn run<B: Backend>(compiler: &mut EvmCompiler<B>, inspect: bool) {
+    compiler.inspect_stack_length(true);
+    compiler.inspect_instructions(inspect);
+    let f = unsafe { compiler.jit("inspect", TEST, DEF_SPEC) }.unwrap();
+
+    with_evm_context(TEST, |ecx, _stack, _stack_len| {
+        let mut interpreter = ecx.to_interpreter(Default::default());
+        let mut host = TestHost::new();
+        let mut recorder = Recorder::default();
+        unsafe {
+            f.call_with_interpreter_and_inspector(&mut interpreter, &mut host, &mut recorder)
+        };
+        assert_eq!(interpreter.instruction_result, InstructionResult::Stop);
+        assert_eq!(interpreter.stack.data(), &[U256::from(9)]);
+
+        if !inspect {
+            assert!(recorder.steps.is_empty());
+            return;
+        }
+        let u = U256::from;
+        assert_eq!(
+            recorder.steps,
+            [
+                (0, op::PUSH1, vec![]),
+                (2, op::PUSH1, vec![u(1)]),
+                (4, op::ADD, vec![u(1), u(2)]),
+                (5, op::PUSH1, vec![u(3)]),
+                (7, op::MUL, vec![u(3), u(3)]),
+                (8, op::STOP, vec![u(9)]),
+            ]
+        );
+        // Called before the static gas of the section is charged, which is then charged at once.
+        let section_gas = 3 + 3 + 3 + 3 + 5;
+        assert_eq!(recorder.gas_remaining[0], DEF_GAS_LIMIT);
+        assert!(recorder.gas_remaining[1..].iter().all(|&gas| gas == DEF_GAS_LIMIT - section_gas));
+    });
+}
@@ -1,5 +1,10 @@
 macro_rules! matrix_tests {
     ($run:ident) => {
+        matrix_tests!(@llvm $run);
+        matrix_tests!(@cranelift $run);
+    };
+
+    (@llvm $run:ident) => {
         #[cfg(feature = "llvm")]
         mod llvm {
             use super::*;
@@ -23,6 +28,36 @@ macro_rules! matrix_tests {
         }
     };
 
+    (@cranelift $run:ident) => {
+        #[cfg(feature = "cranelift")]
+        mod cranelift {
+            use super::*;
+            #[allow(unused_imports)]
+            use similar_asserts::assert_eq;
+
+            fn run_cranelift(compiler: &mut EvmCompiler<crate::EvmCraneliftBackend>) {
+                crate::tests::set_test_dump(compiler, module_path!());
+                $run(compiler);
+            }
+
+            #[test]
+            fn unopt() {
+                crate::tests::with_cranelift_backend_jit(
+                    crate::OptimizationLevel::None,
+                    run_cranelift,
+                );
+            }
+
+            #[test]
+            fn opt() {
+                crate::tests::with_cranelift_backend_jit(
+                    crate::OptimizationLevel::Aggressive,
+                    run_cranelift,
+                );
+            }
+        }
+    };
+
     ($name:ident = | $compiler:ident | $e:expr) => {
         mod $name {
             use super::*;
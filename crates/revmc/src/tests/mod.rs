@@ -20,8 +20,23 @@ mod macros;
 
 mod meta;
 
+mod budget;
+#[cfg(feature = "llvm")]
+mod cache;
+#[cfg(feature = "llvm")]
+mod contract_set;
+#[cfg(feature = "llvm")]
+mod cross;
+mod debug_info;
+mod eof_sections;
+mod errors;
 mod fibonacci;
+mod gas_metering;
+mod inspect;
+#[cfg(feature = "llvm")]
+mod orc;
 mod resume;
+mod storage_cache;
 
 mod runner;
 pub use runner::*;
@@ -189,6 +204,21 @@ tests! {
             expected_stack: &[2_U256, 1_U256, 3_U256],
             expected_gas: 3 + 3 + 3 + 3,
         }),
+        swap_pop(@raw {
+            bytecode: &[op::PUSH1, 1, op::PUSH1, 2, op::SWAP1, op::POP, op::DUP1],
+            expected_stack: &[2_U256, 2_U256],
+            expected_gas: 3 + 3 + 3 + 2 + 3,
+        }),
+        spill_before_builtin(@raw {
+            bytecode: &[op::PUSH1, 1, op::PUSH1, 2, op::PUSH1, 3, op::SWAP2, op::PUSH0, op::SLOAD, op::DUP2],
+            expected_stack: STACK_WHAT_INTERPRETER_SAYS,
+            expected_gas: GAS_WHAT_INTERPRETER_SAYS,
+        }),
+        spill_before_jump(@raw {
+            bytecode: &[op::PUSH1, 1, op::PUSH1, 2, op::PUSH1, 7, op::JUMP, op::JUMPDEST, op::SWAP1, op::POP, op::PUSH1, 3, op::DUP2],
+            expected_stack: &[2_U256, 3_U256, 2_U256],
+            expected_gas: 3 + 3 + 3 + 8 + 1 + 3 + 2 + 3 + 3,
+        }),
     }
 
     control_flow {
@@ -270,6 +300,40 @@ tests! {
             expected_stack: &[69_U256],
             expected_gas: 3 + (1 + 3 + 3 + 3 + 3 + 3 + 10) * 3 + 2 + 3,
         }),
+        internal_function(@raw {
+            bytecode: &[
+                op::PUSH1, 5,  // ret
+                op::PUSH1, 9,  // f, ret
+                op::JUMP,      // ret
+                op::JUMPDEST,  // ret:
+                op::PUSH1, 69, // 69
+                op::STOP,
+                op::JUMPDEST,  // f: ret
+                op::JUMP,      //
+            ],
+            expected_stack: &[69_U256],
+            expected_gas: 3 + 3 + 8 + 1 + 8 + 1 + 3,
+        }),
+        internal_function_multiple_returns(@raw {
+            bytecode: &[
+                op::PUSH1, 7,  // ret1
+                op::PUSH1, 1,  // 1, ret1
+                op::PUSH1, 17, // f, 1, ret1
+                op::JUMP,      // 1, ret1
+                op::JUMPDEST,  // ret1: 1
+                op::PUSH1, 15, // ret2, 1
+                op::PUSH1, 2,  // 2, ret2, 1
+                op::PUSH1, 17, // f, 2, ret2, 1
+                op::JUMP,      // 2, ret2, 1
+                op::JUMPDEST,  // ret2: 2, 1
+                op::STOP,
+                op::JUMPDEST,  // f: x, ret
+                op::SWAP1,     // ret, x
+                op::JUMP,      // x
+            ],
+            expected_stack: &[1_U256, 2_U256],
+            expected_gas: (3 + 3 + 3 + 8) + (1 + 3 + 8) + (1 + 3 + 3 + 3 + 8) + (1 + 3 + 8) + 1,
+        }),
 
         pc(@raw {
             bytecode: &[op::PC, op::PC, op::PUSH1, 69, op::PC, op::PUSH0, op::PC],
@@ -331,6 +395,27 @@ tests! {
             spec_id: SpecId::PRAGUE_EOF,
             expected_gas: 113,
         }),
+
+        dispatcher1(@raw {
+            bytecode: &dispatcher_code::<1>(),
+            expected_stack: &[1_U256, 20_U256],
+            expected_gas: 3 + 22 + 1 + 3,
+        }),
+        dispatcher2(@raw {
+            bytecode: &dispatcher_code::<2>(),
+            expected_stack: &[2_U256, 30_U256],
+            expected_gas: 3 + 22 * 2 + 1 + 3,
+        }),
+        dispatcher3(@raw {
+            bytecode: &dispatcher_code::<3>(),
+            expected_stack: &[3_U256, 50_U256],
+            expected_gas: 3 + 22 * 4 + 1 + 3,
+        }),
+        dispatcher_fallthrough(@raw {
+            bytecode: &dispatcher_code::<4>(),
+            expected_stack: &[4_U256, 10_U256],
+            expected_gas: 3 + 22 * 4 + 3,
+        }),
     }
 
     subroutines {
@@ -371,6 +456,7 @@ tests! {
         add3(op::ADD, 255_U256, 255_U256 => 510_U256),
         add4(op::ADD, U256::MAX, 1_U256 => 0_U256),
         add5(op::ADD, U256::MAX, 2_U256 => 1_U256),
+        add_narrow(op::ADD, U256::from(u128::MAX >> 1), U256::from(u128::MAX >> 1) => U256::from(u128::MAX - 1)),
 
         sub1(op::SUB, 3_U256, 2_U256 => 1_U256),
         sub2(op::SUB, 1_U256, 2_U256 => -1_U256),
@@ -380,6 +466,7 @@ tests! {
         mul1(op::MUL, 1_U256, 2_U256 => 2_U256),
         mul2(op::MUL, 32_U256, 32_U256 => 1024_U256),
         mul3(op::MUL, U256::MAX, 2_U256 => U256::MAX.wrapping_sub(1_U256)),
+        mul_narrow(op::MUL, U256::from(u64::MAX), U256::from(u64::MAX) => U256::from(u64::MAX as u128 * u64::MAX as u128)),
 
         div1(op::DIV, 32_U256, 32_U256 => 1_U256),
         div2(op::DIV, 1_U256, 2_U256 => 0_U256),
@@ -388,6 +475,7 @@ tests! {
         div5(op::DIV, 4_U256, 2_U256 => 2_U256),
         div_by_zero1(op::DIV, 0_U256, 0_U256 => 0_U256),
         div_by_zero2(op::DIV, 32_U256, 0_U256 => 0_U256),
+        div_narrow(op::DIV, U256::from(u64::MAX), 3_U256 => U256::from(u64::MAX / 3)),
 
         rem1(op::MOD, 32_U256, 32_U256 => 0_U256),
         rem2(op::MOD, 1_U256, 2_U256 => 1_U256),
@@ -421,6 +509,7 @@ tests! {
         addmod2(op::ADDMOD, 1_U256, 2_U256, 4_U256 => 3_U256),
         addmod3(op::ADDMOD, 1_U256, 2_U256, 2_U256 => 1_U256),
         addmod4(op::ADDMOD, 32_U256, 32_U256, 69_U256 => 64_U256),
+        addmod_narrow(op::ADDMOD, I256_MAX, I256_MAX, U256::MAX => U256::MAX - 1_U256),
 
         mulmod1(op::MULMOD, 0_U256, 0_U256, 1_U256 => 0_U256),
         mulmod2(op::MULMOD, 69_U256, 0_U256, 1_U256 => 0_U256),
@@ -428,6 +517,7 @@ tests! {
         mulmod4(op::MULMOD, 69_U256, 1_U256, 2_U256 => 1_U256),
         mulmod5(op::MULMOD, 69_U256, 1_U256, 30_U256 => 9_U256),
         mulmod6(op::MULMOD, 69_U256, 2_U256, 100_U256 => 38_U256),
+        mulmod_narrow(op::MULMOD, U256::from(u128::MAX), U256::from(u128::MAX), U256::MAX => U256::from(u128::MAX) * U256::from(u128::MAX)),
 
         exp1(op::EXP, 0_U256, 0_U256 => 1_U256; op_gas(10)),
         exp2(op::EXP, 2_U256, 0_U256 => 1_U256; op_gas(10)),
@@ -436,6 +526,7 @@ tests! {
         exp5(op::EXP, 2_U256, 3_U256 => 8_U256; op_gas(60)),
         exp6(op::EXP, 2_U256, 4_U256 => 16_U256; op_gas(60)),
         exp_overflow(op::EXP, 2_U256, 256_U256 => 0_U256; op_gas(110)),
+        exp_narrow(op::EXP, 3_U256, 255_U256 => 3_U256.pow(255_U256); op_gas(60)),
 
         signextend1(op::SIGNEXTEND, 0_U256, 0_U256 => 0_U256),
         signextend2(op::SIGNEXTEND, 1_U256, 0_U256 => 0_U256),
@@ -561,6 +652,12 @@ tests! {
             expected_memory: &0x6942_U256.to_be_bytes::<32>(),
             expected_gas: 3 + 2 + (3 + 3) + 3 + 2 + gas::keccak256_cost(32).unwrap(),
         }),
+        keccak256_64(@raw {
+            bytecode: &[op::PUSH2, 0x69, 0x42, op::PUSH1, 0x20, op::MSTORE, op::PUSH1, 0x40, op::PUSH0, op::KECCAK256],
+            expected_stack: &[keccak256([[0; 32], 0x6942_U256.to_be_bytes::<32>()].concat()).into()],
+            expected_memory: MEMORY_WHAT_INTERPRETER_SAYS,
+            expected_gas: 3 + 3 + (3 + 6) + 3 + 2 + gas::keccak256_cost(64).unwrap(),
+        }),
 
         address(@raw {
             bytecode: &[op::ADDRESS, op::ADDRESS],
@@ -1350,6 +1447,23 @@ fn bytecode_ternop(op: u8, a: U256, b: U256, c: U256) -> [u8; 100] {
     code
 }
 
+#[rustfmt::skip]
+fn dispatcher_code<const SELECTOR: u8>() -> [u8; 49] {
+    [
+        op::PUSH1, SELECTOR,
+        op::DUP1, op::PUSH1, 1, op::EQ, op::PUSH1, 33, op::JUMPI,
+        op::PUSH1, 2, op::DUP2, op::EQ, op::PUSH1, 37, op::JUMPI,
+        // Shadowed by the first arm.
+        op::DUP1, op::PUSH1, 1, op::EQ, op::PUSH1, 41, op::JUMPI,
+        op::DUP1, op::PUSH1, 3, op::EQ, op::PUSH1, 45, op::JUMPI,
+        /* _ => */ op::PUSH1, 10, op::STOP,
+        /* 1 => */ op::JUMPDEST, op::PUSH1, 20, op::STOP,
+        /* 2 => */ op::JUMPDEST, op::PUSH1, 30, op::STOP,
+        /* 1 => */ op::JUMPDEST, op::PUSH1, 40, op::STOP,
+        /* 3 => */ op::JUMPDEST, op::PUSH1, 50, op::STOP,
+    ]
+}
+
 #[rustfmt::skip]
 #[allow(clippy::erasing_op, clippy::identity_op)]
 fn rjumpv_code<const VALUE: u8>() -> Bytes {
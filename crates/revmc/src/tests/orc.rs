@@ -0,0 +1,133 @@
+use super::{with_evm_context, DEF_SPEC};
+use crate::{
+    compiler::perf_map_path,
+    llvm::{orc::ThreadSafeContext, EvmLlvmOrcJit, JitEventListeners},
+    EvmCompiler, EvmCompilerFn, EvmLlvmBackend, OptimizationLevel,
+};
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::U256;
+
+#[test]
+fn incremental() {
+    let jit = EvmLlvmOrcJit::new().unwrap();
+    let cx = ThreadSafeContext::new();
+    let backend = EvmLlvmBackend::new_orc(&cx, jit, OptimizationLevel::None).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.inspect_stack_length(true);
+
+    // No need to clear the compiler between functions.
+    let id1 = compiler.translate("a", &code(1)[..], DEF_SPEC).unwrap();
+    let f1 = unsafe { compiler.jit_function(id1) }.unwrap();
+    let f2 = unsafe { compiler.jit("b", &code(2)[..], DEF_SPEC) }.unwrap();
+    check(f1, 1);
+    check(f2, 2);
+
+    // Names are reserved until the function is freed.
+    assert!(unsafe { compiler.jit("a", &code(3)[..], DEF_SPEC) }.is_err());
+    unsafe { compiler.free_function(id1) }.unwrap();
+    let f3 = unsafe { compiler.jit("a", &code(3)[..], DEF_SPEC) }.unwrap();
+    check(f2, 2);
+    check(f3, 3);
+}
+
+#[test]
+fn free_shared_module() {
+    let jit = EvmLlvmOrcJit::new().unwrap();
+    let cx = ThreadSafeContext::new();
+    let backend = EvmLlvmBackend::new_orc(&cx, jit, OptimizationLevel::None).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.inspect_stack_length(true);
+
+    // Both functions are JIT-compiled in the same module.
+    let id1 = compiler.translate("a", &code(1)[..], DEF_SPEC).unwrap();
+    let id2 = compiler.translate("b", &code(2)[..], DEF_SPEC).unwrap();
+    let f1 = unsafe { compiler.jit_function(id1) }.unwrap();
+    let f2 = unsafe { compiler.jit_function(id2) }.unwrap();
+    let err = unsafe { compiler.free_function(id1) }.unwrap_err();
+    assert!(err.to_string().contains("same module"), "{err}");
+    check(f1, 1);
+    check(f2, 2);
+
+    unsafe { compiler.clear() }.unwrap();
+    let f3 = unsafe { compiler.jit("a", &code(3)[..], DEF_SPEC) }.unwrap();
+    check(f3, 3);
+}
+
+#[test]
+fn concurrent() {
+    let jit = EvmLlvmOrcJit::new().unwrap();
+    let threads = (0..4u8)
+        .map(|i| {
+            let jit = jit.clone();
+            std::thread::spawn(move || {
+                let cx = ThreadSafeContext::new();
+                let backend =
+                    EvmLlvmBackend::new_orc(&cx, jit, OptimizationLevel::Aggressive).unwrap();
+                let mut compiler = EvmCompiler::new(backend);
+                compiler.inspect_stack_length(true);
+                let fs = (0..4u8)
+                    .map(|j| {
+                        let x = i * 4 + j;
+                        let f = unsafe { compiler.jit(&format!("f{x}"), &code(x)[..], DEF_SPEC) };
+                        (x, f.unwrap())
+                    })
+                    .collect::<Vec<_>>();
+                for (x, f) in fs {
+                    check(f, x);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn perf_map() {
+    let listeners = JitEventListeners { perf_map: true, ..Default::default() };
+    let jit = EvmLlvmOrcJit::with_event_listeners(listeners).unwrap();
+    let cx = ThreadSafeContext::new();
+    let backend = EvmLlvmBackend::new_orc(&cx, jit, OptimizationLevel::None).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.inspect_stack_length(true);
+    compiler.perf_map(true);
+
+    let f = unsafe { compiler.jit("perf_map_test", &code(1)[..], DEF_SPEC) }.unwrap();
+    check(f, 1);
+
+    if cfg!(target_os = "linux") {
+        let map = std::fs::read_to_string(perf_map_path()).unwrap();
+        let line = map.lines().find(|line| line.ends_with(" perf_map_test")).unwrap();
+        let mut parts = line.split(' ');
+        let addr = usize::from_str_radix(parts.next().unwrap(), 16).unwrap();
+        let size = usize::from_str_radix(parts.next().unwrap(), 16).unwrap();
+        assert_eq!(addr, f.into_inner() as usize);
+        assert!(size > 0);
+    }
+}
+
+#[test]
+fn event_listeners() {
+    let listeners = JitEventListeners { gdb: true, ..Default::default() };
+    let jit = EvmLlvmOrcJit::with_event_listeners(listeners).unwrap();
+    let cx = ThreadSafeContext::new();
+    let backend = EvmLlvmBackend::new_orc(&cx, jit, OptimizationLevel::Aggressive).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.inspect_stack_length(true);
+    let f = unsafe { compiler.jit("a", &code(1)[..], DEF_SPEC) }.unwrap();
+    check(f, 1);
+}
+
+fn code(x: u8) -> [u8; 5] {
+    [op::PUSH1, x, op::PUSH1, 0x69, op::ADD]
+}
+
+fn check(f: EvmCompilerFn, x: u8) {
+    with_evm_context(&code(x), |ecx, stack, stack_len| {
+        let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+        assert_eq!(r, InstructionResult::Stop);
+        assert_eq!(*stack_len, 1);
+        assert_eq!(stack.as_slice()[0].to_u256(), U256::from(x as u64 + 0x69));
+    });
+}
@@ -2,7 +2,7 @@ use super::*;
 use interpreter::LoadAccountResult;
 use revm_interpreter::{opcode as op, Contract, DummyHost, Host};
 use revm_primitives::{
-    spec_to_generic, BlobExcessGasAndPrice, BlockEnv, CfgEnv, Env, HashMap, TxEnv, EOF_MAGIC_BYTES,
+    spec_to_generic, BlobExcessGasAndPrice, BlockEnv, CfgEnv, Env, HashMap, TxEnv,
 };
 use similar_asserts::assert_eq;
 use std::{fmt, path::Path, sync::OnceLock};
@@ -34,7 +34,7 @@ impl<'a> arbitrary::Arbitrary<'a> for TestCase<'a> {
         let spec_id = SpecId::try_from_u8(u.int_in_range(spec_id_range)?).unwrap_or(DEF_SPEC);
 
         let mut bytecode: &'a [u8] = u.arbitrary()?;
-        if is_eof_enabled && !bytecode.starts_with(&EOF_MAGIC_BYTES) && u.arbitrary()? {
+        if is_eof_enabled && !bytecode.starts_with(&primitives::EOF_MAGIC_BYTES) && u.arbitrary()? {
             let code = eof_sections_unchecked(&[bytecode]);
             if revm_interpreter::analysis::validate_eof(&code).is_ok() {
                 static mut STORAGE: Bytes = Bytes::new();
@@ -352,6 +352,14 @@ pub fn with_llvm_backend_jit(
     with_llvm_backend(opt_level, |backend| f(&mut EvmCompiler::new(backend)));
 }
 
+#[cfg(feature = "cranelift")]
+pub fn with_cranelift_backend_jit(
+    opt_level: OptimizationLevel,
+    f: fn(&mut EvmCompiler<EvmCraneliftBackend>),
+) {
+    f(&mut EvmCompiler::new(EvmCraneliftBackend::new(false, opt_level)));
+}
+
 pub fn set_test_dump<B: Backend>(compiler: &mut EvmCompiler<B>, module_path: &str) {
     let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().parent().unwrap();
     let mut dump_path = root.to_path_buf();
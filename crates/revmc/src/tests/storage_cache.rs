@@ -0,0 +1,78 @@
+use super::{def_storage, with_evm_context, TestHost, DEF_SPEC};
+use crate::{Backend, EvmCompiler, StorageCache};
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::{spec_to_generic, SpecId, U256};
+
+matrix_tests!(hits = |compiler| run(compiler, true));
+matrix_tests!(no_cache = |compiler| run(compiler, false));
+
+#[rustfmt::skip]
+const CODE: &[u8] = &[
+    // Hit.
+    op::PUSH1, 1,
+    op::SLOAD,
+
+    // Hit, dirty.
+    op::PUSH1, 3,
+    op::PUSH1, 1,
+    op::SSTORE,
+
+    // Hit, restored to the original value.
+    op::PUSH0,
+    op::PUSH1, 69,
+    op::SSTORE,
+
+    // Hit, reads the new value.
+    op::PUSH1, 1,
+    op::SLOAD,
+
+    // Miss, cold.
+    op::PUSH1, 2,
+    op::SLOAD,
+
+    op::STOP,
+];
+
+fn run<B: Backend>(compiler: &mut EvmCompiler<B>, with_cache: bool) {
+    compiler.inspect_stack_length(true);
+    compiler.storage_cache(true);
+    let f = unsafe { compiler.jit("storage_cache", CODE, DEF_SPEC) }.unwrap();
+
+    with_evm_context(CODE, |ecx, _stack, _stack_len| {
+        let table = spec_to_generic!(DEF_SPEC, op::make_instruction_table::<_, SPEC>());
+        let mut expected = ecx.to_interpreter(Default::default());
+        let memory = expected.take_memory();
+        expected.run(memory, &table, &mut TestHost::new());
+        assert_eq!(expected.instruction_result, InstructionResult::Stop);
+
+        // `DummyHost` considers the original value of all slots to be zero.
+        let mut cache = StorageCache::new(16);
+        for (&key, &value) in def_storage() {
+            assert_eq!(cache.insert(key, U256::ZERO, value), None);
+        }
+
+        let mut interpreter = ecx.to_interpreter(Default::default());
+        let mut host = TestHost::new();
+        unsafe {
+            if with_cache {
+                f.call_with_interpreter_and_storage_cache(&mut interpreter, &mut host, &mut cache)
+            } else {
+                f.call_with_interpreter(&mut interpreter, &mut host)
+            }
+        };
+        assert_eq!(interpreter.instruction_result, InstructionResult::Stop);
+        assert_eq!(interpreter.stack.data(), expected.stack.data());
+        assert_eq!(interpreter.gas, expected.gas);
+
+        let mut dirty = cache.take_dirty().collect::<Vec<_>>();
+        dirty.sort();
+        if with_cache {
+            assert_eq!(dirty, [(U256::from(1), U256::from(3)), (U256::from(69), U256::ZERO)]);
+            // Hits never reach the host.
+            assert_eq!(host.host.storage.get(&U256::from(1)), Some(&U256::from(2)));
+            assert!(cache.take_dirty().next().is_none());
+        } else {
+            assert!(dirty.is_empty());
+        }
+    });
+}
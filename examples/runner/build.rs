@@ -1,8 +1,5 @@
-use revmc::{
-    primitives::{hex, SpecId},
-    EvmCompiler, EvmLlvmBackend, OptimizationLevel, Result,
-};
-use std::path::PathBuf;
+use revmc::{primitives::hex, Result};
+use revmc_build::{Build, SpecId};
 
 include!("./src/common.rs");
 
@@ -11,19 +8,6 @@ fn main() -> Result<()> {
     // This not used if we are only using statically linked bytecodes.
     revmc_build::emit();
 
-    // Compile and statically link a bytecode.
-    let name = "fibonacci";
-    let bytecode = FIBONACCI_CODE;
-
-    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
-    let context = revmc::llvm::inkwell::context::Context::create();
-    let backend = EvmLlvmBackend::new(&context, true, OptimizationLevel::Aggressive)?;
-    let mut compiler = EvmCompiler::new(backend);
-    compiler.translate(name, bytecode, SpecId::CANCUN)?;
-    let object = out_dir.join(name).with_extension("o");
-    compiler.write_object_to_file(&object)?;
-
-    cc::Build::new().object(&object).static_flag(true).compile(name);
-
-    Ok(())
+    // Compile and statically link the bytecodes, and generate the `revmc_contracts` module.
+    Build::new().contract("fibonacci", hex::encode(FIBONACCI_CODE), SpecId::CANCUN).compile()
 }
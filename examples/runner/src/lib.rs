@@ -18,9 +18,9 @@ use revmc_context::EvmCompilerFn;
 
 include!("./common.rs");
 
-// The bytecode we statically linked.
-revmc_context::extern_revmc! {
-    fn fibonacci;
+// The bytecodes we statically linked, generated by `revmc_build::Build` in `build.rs`.
+mod contracts {
+    include!(concat!(env!("OUT_DIR"), "/revmc_contracts.rs"));
 }
 
 /// Build a [`revm::Evm`] with a custom handler that can call compiled functions.
@@ -40,12 +40,7 @@ impl ExternalContext {
     }
 
     fn get_function(&self, bytecode_hash: B256) -> Option<EvmCompilerFn> {
-        // Can use any mapping between bytecode hash and function.
-        if bytecode_hash == FIBONACCI_HASH {
-            return Some(EvmCompilerFn::new(fibonacci));
-        }
-
-        None
+        contracts::get_function(&bytecode_hash.0)
     }
 }
 